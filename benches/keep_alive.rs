@@ -0,0 +1,134 @@
+//! Compares sending requests over a pooled keep-alive connection against opening a new
+//! TCP + TLS connection for each request, using a local TLS server with a self-signed certificate.
+//!
+//! Run with `cargo bench --bench keep_alive`.
+
+use nxcloudnotes::httprequest::{HttpRequest, LiteHttpClient, RequestType};
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::ssl::{SslAcceptor, SslConnector, SslMethod};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509NameBuilder, X509};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const REQUESTS: u32 = 200;
+
+fn self_signed_certificate() -> (X509, PKey<Private>) {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "localhost").unwrap();
+    let name = name.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+    let san = SubjectAlternativeName::new().dns("localhost").build(&builder.x509v3_context(None, None)).unwrap();
+    builder.append_extension(san).unwrap();
+    builder.sign(&key, MessageDigest::sha256()).unwrap();
+    (builder.build(), key)
+}
+
+/// Starts a TLS server that answers every request on a connection with a small 200 response,
+/// for as long as the client keeps the connection open. Returns the port it listens on.
+fn start_server(certificate: &X509, key: &PKey<Private>) -> u16 {
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(certificate).unwrap();
+    acceptor.set_private_key(key).unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let acceptor = acceptor.clone();
+            thread::spawn(move || {
+                let stream = match acceptor.accept(stream) {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let mut reader = BufReader::new(stream);
+                loop {
+                    let mut content_length = 0;
+                    let mut line = String::new();
+                    // request line, then headers until the blank line
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    if reader.read_exact(&mut body).is_err() {
+                        return;
+                    }
+                    let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                    if reader.get_mut().write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    port
+}
+
+fn send_request(client: LiteHttpClient) {
+    let response = client
+        .set_request(RequestType::PUT, "/remote.php/dav/files/bench/note.txt")
+        .basic_auth("bench", "password")
+        .send_bytes(b"benchmark note body")
+        .unwrap();
+    assert_eq!(response.response_code, 200);
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!(
+        "{:<28} {:>5} requests in {:>8.2?} ({:>8.2?}/request)",
+        name,
+        REQUESTS,
+        elapsed,
+        elapsed / REQUESTS
+    );
+}
+
+fn main() {
+    let (certificate, key) = self_signed_certificate();
+    let port = start_server(&certificate, &key);
+
+    let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+    connector.cert_store_mut().add_cert(certificate).unwrap();
+    let connector = connector.build();
+
+    let client = LiteHttpClient::new("localhost".to_string(), port as u32).with_ssl_connector(connector.clone());
+    let start = Instant::now();
+    for _ in 0..REQUESTS {
+        send_request(client.clone());
+    }
+    report("pooled keep-alive", start.elapsed());
+
+    let start = Instant::now();
+    for _ in 0..REQUESTS {
+        // a fresh client has an empty pool, so every request pays for a new handshake
+        let client = LiteHttpClient::new("localhost".to_string(), port as u32).with_ssl_connector(connector.clone());
+        send_request(client);
+    }
+    report("new connection per request", start.elapsed());
+}
@@ -0,0 +1,240 @@
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use crate::asynchttp::{AsyncError, AsyncHttpRequest, AsyncResponse};
+use crate::httprequest::{HttpResponse, RequestType};
+use crate::nextcloudclient;
+use crate::utils;
+use crate::webdav;
+
+/// How many requests are sent at once by the bulk operations, unless configured otherwise.
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+/// Async counterpart of `NextCloudClient`, for operations on many files at once. The bulk operations send
+/// their requests concurrently, up to the parallelism limit, so they should be run on a runtime with
+/// the transport's requirements (a multi-threaded one to use more than one core).
+#[derive(Clone)]
+pub struct AsyncNextCloudClient<T>
+where
+    T: AsyncHttpRequest + Clone
+{
+    request_builder: T,
+    username: String,
+    password: String,
+    parallelism: usize,
+}
+
+impl<T: AsyncHttpRequest + Clone + Send + Sync + 'static> AsyncNextCloudClient<T> {
+    /// `t` is used as a template for every request the client sends, like with `NextCloudClient::new`.
+    pub fn new(t: T, username: String, password: String) -> Self {
+        AsyncNextCloudClient {
+            request_builder: t,
+            username,
+            password,
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+
+    /// Sets how many requests the bulk operations send at once.
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    fn files_path(&self, filepath: &str) -> String {
+        webdav::percent_encode_path(&format!("/remote.php/dav/files/{}{}", &self.username, filepath))
+    }
+
+    fn request(&self, req_type: RequestType, filepath: &str) -> T {
+        self.request_builder.clone()
+        .set_request(req_type, &self.files_path(filepath))
+        .basic_auth(&self.username, &self.password)
+        .set_header("OCS-APIRequest".to_string(), "true".to_string())
+    }
+
+    pub async fn create_or_replace_file(&self, filepath: &str, content: Vec<u8>) -> Result<(), AsyncError> {
+        let call_result = self.request(RequestType::PUT, filepath)
+        .set_header("Content-Type".to_string(), utils::content_type_for_path(filepath).to_string())
+        .send_bytes(content)
+        .await?;
+
+        check_response(call_result, "uploading file")?;
+        Ok(())
+    }
+
+    /// Creates the folder at `folderpath`, succeeding if it already exists.
+    pub async fn create_folder(&self, folderpath: &str) -> Result<(), AsyncError> {
+        let call_result = self.request(RequestType::MKCOL, folderpath)
+        .send_bytes(vec![])
+        .await?;
+
+        // MKCOL on a folder that already exists is rejected with 405 Method Not Allowed
+        if call_result.response_code == 405 {
+            return Ok(());
+        }
+        check_response(call_result, "creating folder")?;
+        Ok(())
+    }
+
+    /// Downloads the whole file at `filepath`.
+    pub async fn download_file(&self, filepath: &str) -> Result<Vec<u8>, AsyncError> {
+        let call_result = self.request(RequestType::GET, filepath)
+        .send_bytes(vec![])
+        .await?;
+
+        Ok(check_response(call_result, "downloading file")?.body)
+    }
+
+    /// Deletes the file or folder at `filepath`.
+    pub async fn delete_file(&self, filepath: &str) -> Result<(), AsyncError> {
+        let call_result = self.request(RequestType::DELETE, filepath)
+        .send_bytes(vec![])
+        .await?;
+
+        check_response(call_result, "deleting file")?;
+        Ok(())
+    }
+
+    /// Uploads each file, given as its path and content, returning the result for each in the same order.
+    /// One failing doesn't stop the others from being uploaded.
+    pub async fn upload_files(&self, files: Vec<(String, Vec<u8>)>) -> Vec<Result<(), AsyncError>> {
+        self.for_each_concurrently(files, |client, (filepath, content)| async move {
+            client.create_or_replace_file(&filepath, content).await
+        }).await
+    }
+
+    /// Downloads each file, returning the result for each in the same order as the paths.
+    pub async fn download_files(&self, filepaths: Vec<String>) -> Vec<Result<Vec<u8>, AsyncError>> {
+        self.for_each_concurrently(filepaths, |client, filepath| async move {
+            client.download_file(&filepath).await
+        }).await
+    }
+
+    /// Runs `operation` on every item as its own task, with no more than the parallelism limit running at once.
+    async fn for_each_concurrently<I, O, F, Fut>(&self, items: Vec<I>, operation: F) -> Vec<Result<O, AsyncError>>
+    where
+        I: Send + 'static,
+        O: Send + 'static,
+        F: Fn(Self, I) -> Fut,
+        Fut: Future<Output = Result<O, AsyncError>> + Send + 'static,
+    {
+        let permits = Arc::new(Semaphore::new(self.parallelism));
+        let mut tasks = JoinSet::new();
+        let count = items.len();
+        for (index, item) in items.into_iter().enumerate() {
+            let permits = Arc::clone(&permits);
+            let future = operation(self.clone(), item);
+            tasks.spawn(async move {
+                // the semaphore is never closed, so acquiring it can't fail
+                let _permit = permits.acquire_owned().await;
+                (index, future.await)
+            });
+        }
+
+        let mut results: Vec<Option<Result<O, AsyncError>>> = (0..count).map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((index, result)) => results[index] = Some(result),
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Err(_) => {},
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err("The request was cancelled".into())))
+            .collect()
+    }
+}
+
+fn check_response(call_result: AsyncResponse, action: &str) -> Result<AsyncResponse, AsyncError> {
+    if (199..300).contains(&call_result.response_code) {
+        Ok(call_result)
+    } else {
+        let response = HttpResponse::from(call_result);
+        Err(nextcloudclient::failure_message(&response, action).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    type SentFile = (String, Vec<u8>);
+
+    /// Counts how many requests are being sent at once, failing those for paths containing "missing".
+    #[derive(Clone, Default)]
+    struct CountingRequest {
+        uri: String,
+        in_flight: Arc<AtomicUsize>,
+        most_in_flight: Arc<AtomicUsize>,
+        /// Uri and body of each request, in the order they finished.
+        sent: Arc<Mutex<Vec<SentFile>>>,
+    }
+
+    impl AsyncHttpRequest for CountingRequest {
+        fn set_request(mut self, _req_type: RequestType, remote_uri: &str) -> Self {
+            self.uri = remote_uri.to_string();
+            self
+        }
+
+        fn set_header(self, _header: String, _value: String) -> Self {
+            self
+        }
+
+        fn basic_auth(self, _user: &str, _password: &str) -> Self {
+            self
+        }
+
+        async fn send_bytes(self, body: Vec<u8>) -> Result<AsyncResponse, AsyncError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            // give the other requests a chance to start while this one is in flight
+            for _ in 0..3 {
+                tokio::task::yield_now().await;
+            }
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.sent.lock().unwrap().push((self.uri.clone(), body));
+
+            let response_code = if self.uri.contains("missing") { 404 } else { 201 };
+            Ok(AsyncResponse { response_code, headers: HashMap::new(), body: self.uri.into_bytes() })
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn should_upload_files_up_to_parallelism_limit_at_once() {
+        let request = CountingRequest::default();
+        let client = AsyncNextCloudClient::new(request.clone(), "user".to_string(), "password".to_string()).with_parallelism(3);
+        let files = (0..10).map(|index| (format!("/Notes/{}.md", index), vec![index])).collect();
+
+        let results = block_on(client.upload_files(files));
+
+        assert_eq!(results.len(), 10);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(request.most_in_flight.load(Ordering::SeqCst), 3);
+        let mut sent = request.sent.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(sent[0], ("/remote.php/dav/files/user/Notes/0.md".to_string(), vec![0]));
+        assert_eq!(sent.len(), 10);
+    }
+
+    #[test]
+    fn should_report_each_result_in_order() {
+        let request = CountingRequest::default();
+        let client = AsyncNextCloudClient::new(request, "user".to_string(), "password".to_string());
+        let paths = vec!["/Notes/a.md".to_string(), "/Notes/missing.md".to_string(), "/Notes/b.md".to_string()];
+
+        let results = block_on(client.download_files(paths));
+
+        assert_eq!(results[0].as_ref().unwrap(), b"/remote.php/dav/files/user/Notes/a.md");
+        assert!(results[1].as_ref().unwrap_err().to_string().contains("Reponse code 404"));
+        assert_eq!(results[2].as_ref().unwrap(), b"/remote.php/dav/files/user/Notes/b.md");
+    }
+}
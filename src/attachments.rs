@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use crate::webdav::percent_encode_path;
+
+/// Extensions of files that are embedded in the note as images, rather than linked to.
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp"];
+
+/// Name of the folder the Notes app keeps a note's attachments in, which sits next to the note
+/// and is named after the note's file id.
+pub fn attachments_folder_name(note_file_id: &str) -> String {
+    format!(".attachments.{}", note_file_id)
+}
+
+/// File name an attachment is uploaded as, taken from its local path.
+pub fn attachment_file_name(local_path: &str) -> Option<String> {
+    Path::new(local_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+/// A local file to attach to a note, opened before anything is sent to the server so that a missing
+/// or unreadable file is reported without the note being changed.
+pub struct LocalAttachment {
+    /// Name the file is uploaded as.
+    pub file_name: String,
+    pub file: File,
+    pub length: u64,
+}
+
+/// Checks and opens every file to attach, failing on the first one that can't be attached.
+pub fn open_attachments(local_paths: &[String]) -> Result<Vec<LocalAttachment>, Box<dyn Error>> {
+    let mut attachments = Vec::new();
+    for local_path in local_paths {
+        let file_name = attachment_file_name(local_path)
+            .ok_or(format!("{} is not a file that can be attached", local_path))?;
+        let file = File::open(local_path)
+            .map_err(|e| format!("Unable to open attachment {}: {}", local_path, e))?;
+        let metadata = file.metadata()
+            .map_err(|e| format!("Unable to open attachment {}: {}", local_path, e))?;
+        if metadata.is_dir() {
+            Err(format!("{} is a folder, only files can be attached", local_path))?
+        }
+        attachments.push(LocalAttachment { file_name, file, length: metadata.len() });
+    }
+    Ok(attachments)
+}
+
+fn is_image(file_name: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// Markdown that references the attachment from the note body. Images are embedded
+/// (`![name](path)`), any other file is linked (`[name](path)`).
+pub fn markdown_reference(file_name: &str, attachments_folder: &str) -> String {
+    let path = percent_encode_path(&format!("{}/{}", attachments_folder, file_name));
+    // brackets in the name would end the link text early
+    let label = file_name.replace('[', "\\[").replace(']', "\\]");
+    if is_image(file_name) {
+        format!("![{}]({})", label, path)
+    } else {
+        format!("[{}]({})", label, path)
+    }
+}
+
+/// Adds the attachment references to the end of the note body, one per line.
+pub fn append_references(content: &str, references: &[String]) -> String {
+    if references.is_empty() {
+        return content.to_string();
+    }
+    let mut with_references = content.trim_end().to_string();
+    if !with_references.is_empty() {
+        with_references.push_str("\n\n");
+    }
+    with_references.push_str(&references.join("\n"));
+    with_references.push('\n');
+    with_references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_embed_images_and_link_other_files() {
+        let folder = attachments_folder_name("1234");
+
+        assert_eq!(markdown_reference("screen shot.PNG", &folder), "![screen shot.PNG](.attachments.1234/screen%20shot.PNG)");
+        assert_eq!(markdown_reference("output[1].log", &folder), "[output\\[1\\].log](.attachments.1234/output%5B1%5D.log)");
+    }
+
+    #[test]
+    fn should_append_references_after_note_body() {
+        let references = vec!["![a.png](.attachments.1/a.png)".to_string(), "[b.txt](.attachments.1/b.txt)".to_string()];
+
+        assert_eq!(append_references("my note\n", &references), "my note\n\n![a.png](.attachments.1/a.png)\n[b.txt](.attachments.1/b.txt)\n");
+        assert_eq!(append_references("", &references[..1]), "![a.png](.attachments.1/a.png)\n");
+        assert_eq!(append_references("my note", &[]), "my note");
+    }
+
+    #[test]
+    fn should_open_every_attachment_or_fail() {
+        let opened = open_attachments(&["Cargo.toml".to_string()]).unwrap();
+
+        assert_eq!(opened[0].file_name, "Cargo.toml");
+        assert!(opened[0].length > 0);
+        let missing = open_attachments(&["Cargo.toml".to_string(), "missing.png".to_string()]).err().unwrap();
+        assert!(missing.to_string().starts_with("Unable to open attachment missing.png"));
+        assert!(open_attachments(&["src".to_string()]).is_err());
+    }
+
+    #[test]
+    fn should_use_file_name_of_local_path() {
+        assert_eq!(attachment_file_name("/tmp/screenshots/shot.png"), Some("shot.png".to_string()));
+        assert_eq!(attachment_file_name("/"), None);
+    }
+}
@@ -0,0 +1,652 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+use crate::frontmatter;
+use crate::httprequest::HttpRequest;
+use crate::nextcloudclient::{CollisionPolicy, NextCloudClient};
+use crate::notes;
+use crate::terminal::Key;
+
+/// How much of a note is downloaded to preview it.
+const PREVIEW_LIMIT: u64 = 64 * 1024;
+
+/// What the browser is waiting for the user to do.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Mode {
+    Browse,
+    /// Typing the filter.
+    Filter,
+    ConfirmDelete,
+    /// Typing the new path of the selected note.
+    Rename(String),
+    /// Typing the title of a new note.
+    Create(String),
+}
+
+/// What the program running the browser needs to do after a key press.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Continue,
+    Quit,
+    /// Let the user edit the note at this path (relative to the notes directory) in their editor.
+    /// The note doesn't exist yet if it's being created.
+    Edit(String),
+}
+
+/// Scores how well `pattern` fuzzily matches `candidate`, or None if it doesn't match at all. Every character
+/// of the pattern has to appear in the candidate in order (ignoring case), and matches score higher
+/// when their characters are next to each other or at the start of words.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+    for wanted in pattern.chars().flat_map(char::to_lowercase) {
+        if wanted.is_whitespace() {
+            continue;
+        }
+        let found = position + candidate[position..].iter().position(|c| *c == wanted)?;
+        score += 1;
+        if previous_match.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(candidate[found - 1], '/' | ' ' | '-' | '_' | '.') {
+            score += 3;
+        }
+        // gaps count against the match, but only a little so long paths aren't ruled out
+        score -= (found - position).min(3) as i64;
+        previous_match = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// Interactive browser for the notes in the notes directory. It holds the state of the terminal UI and
+/// makes the requests that key presses call for, while drawing and reading keys is left to the caller.
+pub struct Browser<T: HttpRequest + Clone> {
+    client: NextCloudClient<T>,
+    notes_directory: String,
+    /// Extension new notes are created with.
+    extension: String,
+    /// Paths of every note, relative to the notes directory.
+    notes: Vec<String>,
+    filter: String,
+    /// Indexes into `notes` of those matching the filter, best match first.
+    matches: Vec<usize>,
+    /// Index into `matches` of the selected note.
+    selected: usize,
+    mode: Mode,
+    previews: HashMap<String, String>,
+    /// Etags of the notes read to be edited, so saving one doesn't overwrite a change made in the meantime.
+    etags: HashMap<String, String>,
+    /// Message shown in place of the key help until the next key press.
+    status: String,
+}
+
+impl<T: HttpRequest + Clone> Browser<T> {
+    pub fn new(client: NextCloudClient<T>, notes_directory: &str, extension: &str) -> Self {
+        Browser {
+            client,
+            notes_directory: notes_directory.trim_end_matches('/').to_string(),
+            extension: extension.to_string(),
+            notes: vec![],
+            filter: String::new(),
+            matches: vec![],
+            selected: 0,
+            mode: Mode::Browse,
+            previews: HashMap::new(),
+            etags: HashMap::new(),
+            status: String::new(),
+        }
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    /// The path of the selected note, relative to the notes directory.
+    pub fn selected_note(&self) -> Option<&str> {
+        self.matches.get(self.selected).map(|index| self.notes[*index].as_str())
+    }
+
+    /// The notes matching the filter, best match first.
+    pub fn visible_notes(&self) -> Vec<&str> {
+        self.matches.iter().map(|index| self.notes[*index].as_str()).collect()
+    }
+
+    /// Whether the note exists, rather than being one that's about to be created.
+    pub fn has_note(&self, note: &str) -> bool {
+        self.notes.iter().any(|existing| existing == note)
+    }
+
+    fn full_path(&self, note: &str) -> String {
+        format!("{}/{}", self.notes_directory, note)
+    }
+
+    /// Lists the notes again, keeping the same note selected if it's still there.
+    pub fn refresh(&mut self) -> Result<(), Box<dyn Error>> {
+        let selected = self.selected_note().map(String::from);
+        self.notes = self.client.list_notes(&self.notes_directory)?;
+        self.previews.clear();
+        self.update_matches(selected.as_deref());
+        Ok(())
+    }
+
+    fn update_matches(&mut self, keep_selected: Option<&str>) {
+        let mut scored: Vec<(i64, usize)> = self
+            .notes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, note)| fuzzy_score(&self.filter, note).map(|score| (score, index)))
+            .collect();
+        if !self.filter.is_empty() {
+            // stable, so notes that score the same stay in path order
+            scored.sort_by_key(|(score, _)| -score);
+        }
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = keep_selected
+            .and_then(|note| self.matches.iter().position(|index| self.notes[*index] == note))
+            .unwrap_or(0);
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as isize - 1;
+        self.selected = (self.selected as isize + by).clamp(0, last) as usize;
+    }
+
+    /// Downloads the selected note to preview it, unless it already has been.
+    pub fn load_preview(&mut self) {
+        let note = match self.selected_note() {
+            Some(note) if !self.previews.contains_key(note) => note.to_string(),
+            _ => return,
+        };
+        let preview = self.download(&note, Some(PREVIEW_LIMIT))
+            .map(|(preview, _)| preview)
+            .unwrap_or_else(|e| format!("Unable to load the note: {}", e));
+        self.previews.insert(note, preview);
+    }
+
+    fn download(&self, note: &str, limit: Option<u64>) -> Result<(String, Option<String>), Box<dyn Error>> {
+        let response = self.client.download_file(&self.full_path(note))?;
+        let etag = response.header("etag").map(|etag| etag.to_string());
+        let mut content = Vec::new();
+        match limit {
+            Some(limit) => response.body.take(limit).read_to_end(&mut content)?,
+            None => { response.body }.read_to_end(&mut content)?,
+        };
+        Ok((String::from_utf8_lossy(&content).to_string(), etag))
+    }
+
+    /// The whole content of a note, to be edited. Its etag is kept for `save_note`.
+    pub fn read_note(&mut self, note: &str) -> Result<String, Box<dyn Error>> {
+        let (content, etag) = self.download(note, None)?;
+        match etag {
+            Some(etag) => self.etags.insert(note.to_string(), etag),
+            None => self.etags.remove(note),
+        };
+        Ok(content)
+    }
+
+    /// Uploads a note after it's been edited. Unchanged notes aren't uploaded, and new notes that
+    /// were left empty aren't created. A note that was changed on the server since `read_note` read it
+    /// isn't overwritten, and an error is returned instead.
+    pub fn save_note(&mut self, note: &str, original: &str, edited: &str) -> Result<(), Box<dyn Error>> {
+        let exists = self.has_note(note);
+        let etag = self.etags.remove(note);
+        if edited == original {
+            self.status = if exists { format!("{} wasn't changed", note) } else { format!("{} was left empty, so wasn't created", note) };
+            return Ok(());
+        }
+        if exists {
+            match etag {
+                Some(etag) => {
+                    if !self.client.replace_file_if_unchanged(&self.full_path(note), edited.as_bytes(), &etag)? {
+                        Err(format!("{} was changed on the server while it was being edited, so it wasn't saved", note))?
+                    }
+                },
+                // without an etag there's no way to tell if it's changed
+                None => { self.client.create_or_replace_file(&self.full_path(note), edited.as_bytes())?; },
+            }
+            self.status = format!("saved {}", note);
+        } else {
+            if let Some((category, _)) = note.rsplit_once('/') {
+                self.client.create_folders(&self.notes_directory, category)?;
+            }
+            self.client.create_note(&self.full_path(note), edited.as_bytes(), CollisionPolicy::Fail)?;
+            self.notes.push(note.to_string());
+            self.notes.sort();
+            self.status = format!("created {}", note);
+        }
+        self.previews.remove(note);
+        self.update_matches(Some(note));
+        Ok(())
+    }
+
+    /// Handles a key press. Failed requests are reported in the status line rather than ending the browser.
+    pub fn handle_key(&mut self, key: Key) -> Action {
+        self.status.clear();
+        if key == Key::Ctrl('c') {
+            return Action::Quit;
+        }
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.handle_browse_key(key),
+            Mode::Filter => {
+                self.handle_filter_key(key);
+                Action::Continue
+            },
+            Mode::ConfirmDelete => {
+                if key == Key::Char('y') {
+                    let result = self.delete_selected();
+                    self.report(result);
+                }
+                Action::Continue
+            },
+            Mode::Rename(input) => match self.handle_input_key(key, input, Mode::Rename) {
+                Some(input) => {
+                    let result = self.rename_selected(&input);
+                    self.report(result);
+                    Action::Continue
+                },
+                None => Action::Continue,
+            },
+            Mode::Create(input) => match self.handle_input_key(key, input, Mode::Create) {
+                Some(title) => self.new_note_path(&title).map_or(Action::Continue, Action::Edit),
+                None => Action::Continue,
+            },
+        }
+    }
+
+    fn handle_browse_key(&mut self, key: Key) -> Action {
+        match key {
+            Key::Up | Key::Char('k') => self.move_selection(-1),
+            Key::Down | Key::Char('j') => self.move_selection(1),
+            Key::PageUp => self.move_selection(-10),
+            Key::PageDown => self.move_selection(10),
+            Key::Home | Key::Char('g') => self.move_selection(isize::MIN / 2),
+            Key::End | Key::Char('G') => self.move_selection(isize::MAX / 2),
+            Key::Char('/') => self.mode = Mode::Filter,
+            Key::Enter | Key::Char('e') => {
+                if let Some(note) = self.selected_note() {
+                    return Action::Edit(note.to_string());
+                }
+            },
+            Key::Char('n') => self.mode = Mode::Create(String::new()),
+            Key::Char('r') => {
+                if let Some(note) = self.selected_note() {
+                    self.mode = Mode::Rename(note.to_string());
+                }
+            },
+            Key::Char('d') if self.selected_note().is_some() => self.mode = Mode::ConfirmDelete,
+            Key::Ctrl('r') => {
+                let result = self.refresh();
+                self.report(result.map(|_| String::from("refreshed")));
+            },
+            // Esc clears the filter first, so it takes a second press to quit
+            Key::Esc if !self.filter.is_empty() => {
+                let selected = self.selected_note().map(String::from);
+                self.filter.clear();
+                self.update_matches(selected.as_deref());
+            },
+            Key::Esc | Key::Char('q') => return Action::Quit,
+            _ => {},
+        }
+        Action::Continue
+    }
+
+    fn handle_filter_key(&mut self, key: Key) {
+        match key {
+            Key::Char(c) => {
+                self.filter.push(c);
+                self.update_matches(None);
+            },
+            Key::Backspace => {
+                self.filter.pop();
+                self.update_matches(None);
+            },
+            Key::Up => self.move_selection(-1),
+            Key::Down => self.move_selection(1),
+            Key::Enter => return,
+            Key::Esc => {
+                self.filter.clear();
+                self.update_matches(None);
+                return;
+            },
+            _ => {},
+        }
+        self.mode = Mode::Filter;
+    }
+
+    /// Edits the text being typed into a prompt, returning it once Enter is pressed. Until then the browser
+    /// stays in the prompt's mode, made by `mode` from the text so far, unless Esc is pressed to leave it.
+    fn handle_input_key(&mut self, key: Key, mut input: String, mode: fn(String) -> Mode) -> Option<String> {
+        match key {
+            Key::Enter => return Some(input),
+            Key::Esc => return None,
+            Key::Char(c) => input.push(c),
+            Key::Backspace => {
+                input.pop();
+            },
+            _ => {},
+        }
+        self.mode = mode(input);
+        None
+    }
+
+    fn report(&mut self, result: Result<String, Box<dyn Error>>) {
+        self.status = match result {
+            Ok(message) => message,
+            Err(e) => format!("an error occured! {}", e),
+        };
+    }
+
+    fn delete_selected(&mut self) -> Result<String, Box<dyn Error>> {
+        let note = self.selected_note().ok_or("No note is selected")?.to_string();
+        self.client.delete_file(&self.full_path(&note))?;
+        self.notes.retain(|existing| *existing != note);
+        self.previews.remove(&note);
+        let selected = self.selected;
+        self.update_matches(None);
+        self.selected = selected.min(self.matches.len().saturating_sub(1));
+        Ok(format!("deleted {}", note))
+    }
+
+    /// A note path typed by the user, made safe and given the extension `extension` if it has none.
+    fn typed_note_path(typed: &str, extension: &str) -> Option<String> {
+        let path = notes::category_path(typed)?;
+        let file_name = path.rsplit('/').next().unwrap_or(&path);
+        if file_name.contains('.') {
+            Some(path)
+        } else {
+            Some(format!("{}.{}", path, extension))
+        }
+    }
+
+    fn rename_selected(&mut self, typed: &str) -> Result<String, Box<dyn Error>> {
+        let note = self.selected_note().ok_or("No note is selected")?.to_string();
+        let extension = note.rsplit_once('.').map_or(self.extension.as_str(), |(_, extension)| extension).to_string();
+        let renamed = Self::typed_note_path(typed, &extension).ok_or("The new name can't be empty")?;
+        if renamed == note {
+            return Ok(String::new());
+        }
+        if let Some((category, _)) = renamed.rsplit_once('/') {
+            self.client.create_folders(&self.notes_directory, category)?;
+        }
+        self.client.move_file(&self.full_path(&note), &self.full_path(&renamed))?;
+        for existing in self.notes.iter_mut().filter(|existing| **existing == note) {
+            *existing = renamed.clone();
+        }
+        self.notes.sort();
+        if let Some(preview) = self.previews.remove(&note) {
+            self.previews.insert(renamed.clone(), preview);
+        }
+        self.update_matches(Some(&renamed));
+        Ok(format!("renamed {} to {}", note, renamed))
+    }
+
+    fn new_note_path(&mut self, title: &str) -> Option<String> {
+        let path = match Self::typed_note_path(title, &self.extension) {
+            Some(path) => path,
+            None => {
+                self.status = String::from("The title can't be empty");
+                return None;
+            },
+        };
+        if self.notes.contains(&path) {
+            self.status = format!("{} already exists", path);
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Lays out the screen as lines of at most `width` characters: a header, the list of notes next to a
+    /// preview of the selected one, and a line for prompts, messages and key help.
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let mut lines = Vec::with_capacity(height);
+        let mut header = format!(" nxcloudnotes: {} of {} notes", self.matches.len(), self.notes.len());
+        if self.mode == Mode::Filter || !self.filter.is_empty() {
+            let cursor = if self.mode == Mode::Filter { "_" } else { "" };
+            header.push_str(&format!("   filter: {}{}", self.filter, cursor));
+        }
+        lines.push(format!("\x1b[1m{}\x1b[0m", fit(&header, width)));
+
+        let rows = height.saturating_sub(2);
+        let list_width = (width / 3).clamp(20.min(width), width);
+        let preview_width = width.saturating_sub(list_width + 3);
+        let preview: Vec<String> = match self.selected_note().and_then(|note| self.previews.get(note)) {
+            Some(content) if preview_width >= 10 => preview_lines(content),
+            _ => vec![],
+        };
+        // the list only scrolls once the selection goes past the bottom of it
+        let offset = self.selected.saturating_sub(rows.saturating_sub(1));
+        for row in 0..rows {
+            let index = offset + row;
+            let mut line = match self.matches.get(index) {
+                Some(note) if index == self.selected => format!("\x1b[7m{}\x1b[0m", pad(&fit(&format!("> {}", self.notes[*note]), list_width), list_width)),
+                Some(note) => pad(&fit(&format!("  {}", self.notes[*note]), list_width), list_width),
+                None => " ".repeat(list_width),
+            };
+            if preview_width >= 10 {
+                line.push_str(" │ ");
+                line.push_str(&fit(preview.get(row).map_or("", String::as_str), preview_width));
+            }
+            lines.push(line);
+        }
+
+        let footer = match &self.mode {
+            _ if !self.status.is_empty() => self.status.clone(),
+            Mode::Browse => String::from("↑↓ move  / filter  enter edit  n new  r rename  d delete  ^R refresh  q quit"),
+            Mode::Filter => String::from("type to filter  enter done  esc clear"),
+            Mode::ConfirmDelete => format!("Delete {}? (y/n)", self.selected_note().unwrap_or("")),
+            Mode::Rename(input) => format!("Rename to: {}_", input),
+            Mode::Create(input) => format!("New note title: {}_", input),
+        };
+        lines.push(fit(&footer, width));
+        lines
+    }
+}
+
+/// The lines of a note to show in the preview pane, with its tags shown in place of its front matter.
+fn preview_lines(content: &str) -> Vec<String> {
+    let (front_matter, body) = frontmatter::split(content);
+    let mut lines = Vec::new();
+    if let Some(front_matter) = front_matter.filter(|front_matter| !front_matter.tags.is_empty()) {
+        lines.push(format!("tags: {}", front_matter.tags.join(", ")));
+        lines.push(String::new());
+    }
+    lines.extend(body.lines().map(|line| line.replace('\t', "    ")));
+    lines
+}
+
+/// Cuts the text down to `width` characters, dropping control characters so a note can't send
+/// the terminal escape sequences.
+fn fit(text: &str, width: usize) -> String {
+    text.chars().filter(|c| !c.is_control()).take(width).collect()
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{:<width$}", text, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mockrequest::{folder_listing, MockRequest};
+
+    fn browser(mock: &MockRequest) -> Browser<MockRequest> {
+        let client = NextCloudClient::new(mock.clone(), "user".to_string(), "password".to_string());
+        let mut browser = Browser::new(client, "/Notes", "md");
+        browser.refresh().unwrap();
+        browser
+    }
+
+    fn listing() -> Vec<(u16, String)> {
+        vec![
+            (207, folder_listing("/Notes", &[("Work", true), ("Shopping list.md", false), ("Todo.md", false)])),
+            (207, folder_listing("/Notes/Work", &[("Meeting notes.md", false)])),
+        ]
+    }
+
+    fn mock_with(extra: &[(u16, &str)]) -> MockRequest {
+        let listing = listing();
+        let mut responses: Vec<(u16, &str)> = listing.iter().map(|(code, body)| (*code, body.as_str())).collect();
+        responses.extend_from_slice(extra);
+        MockRequest::with_responses(&responses)
+    }
+
+    fn type_text(browser: &mut Browser<MockRequest>, text: &str) {
+        for c in text.chars() {
+            browser.handle_key(Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn should_score_closer_matches_higher() {
+        assert!(fuzzy_score("mtg", "Work/Meeting notes.md").is_some());
+        assert_eq!(fuzzy_score("xyz", "Work/Meeting notes.md"), None);
+        assert!(fuzzy_score("todo", "Todo.md") > fuzzy_score("todo", "Work/To do later.md"));
+        assert!(fuzzy_score("mn", "Meeting notes") > fuzzy_score("mn", "Summing"));
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn should_filter_notes_as_typed() {
+        let mock = mock_with(&[]);
+        let mut browser = browser(&mock);
+        assert_eq!(browser.visible_notes(), vec!["Shopping list.md", "Todo.md", "Work/Meeting notes.md"]);
+
+        browser.handle_key(Key::Char('/'));
+        type_text(&mut browser, "meet");
+        assert_eq!(browser.visible_notes(), vec!["Work/Meeting notes.md"]);
+        assert_eq!(browser.mode(), &Mode::Filter);
+
+        browser.handle_key(Key::Enter);
+        assert_eq!(browser.mode(), &Mode::Browse);
+        assert_eq!(browser.selected_note(), Some("Work/Meeting notes.md"));
+
+        // esc clears the filter before it quits
+        assert_eq!(browser.handle_key(Key::Esc), Action::Continue);
+        assert_eq!(browser.visible_notes().len(), 3);
+        assert_eq!(browser.selected_note(), Some("Work/Meeting notes.md"));
+        assert_eq!(browser.handle_key(Key::Esc), Action::Quit);
+    }
+
+    #[test]
+    fn should_preview_selected_note_once() {
+        let mock = mock_with(&[(200, "---\ntags: [food]\n---\nmilk\neggs\n")]);
+        let mut browser = browser(&mock);
+
+        browser.load_preview();
+        browser.load_preview();
+        let screen = browser.render(60, 6);
+
+        let sent = mock.sent();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2].method, "GET");
+        assert_eq!(sent[2].uri, "/remote.php/dav/files/user/Notes/Shopping%20list.md");
+        assert_eq!(screen.len(), 6);
+        assert!(screen[1].contains("> Shopping list.md"));
+        assert!(screen[1].ends_with(" │ tags: food"));
+        assert!(screen[3].ends_with(" │ milk"));
+        assert!(screen[3].starts_with("  Work/Meeting notes │"));
+        assert!(screen[4].ends_with(" │ eggs"));
+    }
+
+    #[test]
+    fn should_delete_after_confirming() {
+        let mock = mock_with(&[(204, "")]);
+        let mut browser = browser(&mock);
+
+        browser.handle_key(Key::Char('d'));
+        browser.handle_key(Key::Char('n'));
+        assert_eq!(mock.sent().len(), 2);
+
+        browser.handle_key(Key::Down);
+        browser.handle_key(Key::Char('d'));
+        assert_eq!(browser.render(80, 5)[4], "Delete Todo.md? (y/n)");
+        browser.handle_key(Key::Char('y'));
+
+        let sent = mock.sent();
+        assert_eq!(sent[2].method, "DELETE");
+        assert_eq!(sent[2].uri, "/remote.php/dav/files/user/Notes/Todo.md");
+        assert_eq!(browser.visible_notes(), vec!["Shopping list.md", "Work/Meeting notes.md"]);
+        assert_eq!(browser.selected_note(), Some("Work/Meeting notes.md"));
+    }
+
+    #[test]
+    fn should_rename_into_a_category_keeping_extension() {
+        let mock = mock_with(&[(405, ""), (201, "")]);
+        let mut browser = browser(&mock);
+
+        browser.handle_key(Key::Char('r'));
+        assert_eq!(browser.mode(), &Mode::Rename("Shopping list.md".to_string()));
+        for _ in 0.."Shopping list.md".len() {
+            browser.handle_key(Key::Backspace);
+        }
+        type_text(&mut browser, "Work/Groceries");
+        browser.handle_key(Key::Enter);
+
+        let sent = mock.sent();
+        assert_eq!(sent[2].method, "MKCOL");
+        assert_eq!(sent[3].method, "MOVE");
+        assert_eq!(sent[3].headers.get("Destination").unwrap(), "/remote.php/dav/files/user/Notes/Work/Groceries.md");
+        assert_eq!(browser.selected_note(), Some("Work/Groceries.md"));
+        assert_eq!(browser.mode(), &Mode::Browse);
+    }
+
+    #[test]
+    fn should_not_overwrite_a_note_changed_while_it_was_edited() {
+        let mock = mock_with(&[(200, "milk\n"), (412, ""), (200, "milk\n"), (204, "")])
+            .with_headers(2, &[("etag", "\"first\"")])
+            .with_headers(4, &[("etag", "\"second\"")]);
+        let mut browser = browser(&mock);
+
+        let original = browser.read_note("Shopping list.md").unwrap();
+        let result = browser.save_note("Shopping list.md", &original, "milk\neggs\n");
+        assert_eq!(result.unwrap_err().to_string(), "Shopping list.md was changed on the server while it was being edited, so it wasn't saved");
+
+        let original = browser.read_note("Shopping list.md").unwrap();
+        browser.save_note("Shopping list.md", &original, "milk\neggs\n").unwrap();
+        let sent = mock.sent();
+        assert_eq!(sent[3].headers.get("If-Match").unwrap(), "\"first\"");
+        assert_eq!(sent[5].method, "PUT");
+        assert_eq!(sent[5].headers.get("If-Match").unwrap(), "\"second\"");
+    }
+
+    #[test]
+    fn should_drop_control_characters_from_the_screen() {
+        assert_eq!(fit("\x1b]52;c;bad\x07text\r", 80), "]52;c;badtext");
+    }
+
+    #[test]
+    fn should_create_notes_that_were_written() {
+        let mock = mock_with(&[(201, "")]);
+        let mut browser = browser(&mock);
+
+        browser.handle_key(Key::Char('n'));
+        type_text(&mut browser, "Todo");
+        // a note with that title already exists
+        assert_eq!(browser.handle_key(Key::Enter), Action::Continue);
+        assert_eq!(browser.render(80, 5)[4], "Todo.md already exists");
+
+        browser.handle_key(Key::Char('n'));
+        type_text(&mut browser, "Work/Agenda");
+        assert_eq!(browser.handle_key(Key::Enter), Action::Edit("Work/Agenda.md".to_string()));
+        browser.save_note("Work/Agenda.md", "", "").unwrap();
+        assert_eq!(mock.sent().len(), 2);
+
+        browser.save_note("Work/Agenda.md", "", "- budget\n").unwrap();
+        let sent = mock.sent();
+        assert_eq!(sent[2].method, "MKCOL");
+        assert_eq!(sent[3].method, "PUT");
+        assert_eq!(sent[3].uri, "/remote.php/dav/files/user/Notes/Work/Agenda.md");
+        assert_eq!(sent[3].body, b"- budget\n");
+        assert_eq!(browser.selected_note(), Some("Work/Agenda.md"));
+    }
+}
@@ -1,109 +1,568 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::fmt::Display;
-use std::vec::IntoIter;
-
-
-const USAGE: &'static str = "
-nxcloudnotes -- A NextCloud CLI dumping tool.
-Usage:
-  nxcloudnotes <command> [<args>...]
-  nxcloudnotes help
-  nxcloudnotes config-path
-  nxcloudnotes \"NOTES BODY\"
-  nxcloudnotes -p\"NxCloudPassword\" \"NOTES BODY\"
-  nxcloudnotes -t\"NOTES TITLE\" \"NOTES BODY\"
-Commands:
-  help             Display usage information.
-  config-path      Output path to .toml config file used for this application.
-  \"\"             Empty strings are treated as the notes body if no other commands are found.
-";
+use crate::completions::Shell;
+use crate::nextcloudclient::CollisionPolicy;
+
+
+/// How the shell can complete the value of a flag or argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Completion {
+    Nothing,
+    Files,
+    Choices(&'static [&'static str]),
+    /// Titles of the notes on the server, from the hidden `list --names`.
+    NoteTitles,
+    /// Names of the program's commands.
+    Commands,
+}
+
+/// A flag, which can be given as `--long=value` or (if it has one) as `-svalue`.
+#[derive(Debug)]
+pub struct FlagSpec {
+    pub short: Option<char>,
+    pub long: &'static str,
+    /// Placeholder for the flag's value in help text, or None for switches that don't take a value.
+    pub value_name: Option<&'static str>,
+    pub repeatable: bool,
+    /// Hidden flags are left out of help and completions, as they're for the program's own use.
+    pub hidden: bool,
+    pub help: &'static str,
+    pub completion: Completion,
+}
+
+/// A positional argument a command takes.
+#[derive(Debug)]
+pub struct ArgumentSpec {
+    pub name: &'static str,
+    pub required: bool,
+    pub completion: Completion,
+}
+
+/// A command, along with the flags it accepts.
+#[derive(Debug)]
+pub struct CommandSpec {
+    /// What the command is called, or an empty string for posting a note, which is done when no command is given.
+    pub name: &'static str,
+    /// The positional arguments, in the order they're given. Optional ones can only come after the required ones.
+    pub arguments: &'static [ArgumentSpec],
+    /// One line summary of the command.
+    pub help: &'static str,
+    /// What the command does in more detail, for its own help and the man page.
+    pub description: &'static str,
+    /// Long names of the flags the command accepts, apart from --help which every command accepts.
+    pub flags: &'static [&'static str],
+    pub examples: &'static [&'static str],
+}
+
+const NO_COMPLETION: Completion = Completion::Nothing;
+
+const fn flag(short: Option<char>, long: &'static str, value_name: Option<&'static str>, help: &'static str, completion: Completion) -> FlagSpec {
+    FlagSpec { short, long, value_name, repeatable: false, hidden: false, help, completion }
+}
+
+/// Every flag the program understands.
+pub const FLAGS: &[FlagSpec] = &[
+    flag(Some('t'), "title", Some("TITLE"), "Title of the note.", NO_COMPLETION),
+    flag(Some('p'), "password", Some("PASSWORD"), "NextCloud password. Deprecated as it ends up in shell history, use --password-stdin or NXCLOUDNOTES_PASSWORD.", NO_COMPLETION),
+    flag(None, "password-stdin", None, "Read the NextCloud password from the first line of stdin, if none is saved in the config file.", NO_COMPLETION),
+    FlagSpec { repeatable: true, ..flag(Some('a'), "attach", Some("FILE"), "File to attach to the note, can be given more than once.", Completion::Files) },
+    flag(Some('e'), "extension", Some("EXT"), "File extension of the note (ie txt or md), overriding the one in the config file.", NO_COMPLETION),
+    flag(Some('c'), "category", Some("CATEGORY"), "Category of the note (ie Work/Meetings). Created if it doesn't exist when posting.", NO_COMPLETION),
+    flag(Some('o'), "collision", Some("POLICY"), "What to do if a note with the same title exists: overwrite, fail, suffix or append.", Completion::Choices(&["overwrite", "fail", "suffix", "append"])),
+    FlagSpec { repeatable: true, ..flag(None, "tag", Some("TAG"), "Tag recorded in the note's front matter, or to filter listed notes by. Can be given more than once.", NO_COMPLETION) },
+    flag(None, "no-meta", None, "Don't add front matter to a posted note, or leave it out when reading one.", NO_COMPLETION),
+    flag(None, "clipboard", None, "Post the contents of the clipboard as the notes body.", NO_COMPLETION),
+    flag(None, "copy", None, "Copy the note that was read to the clipboard instead of outputting it.", NO_COMPLETION),
+    FlagSpec { repeatable: true, ..flag(Some('v'), "verbose", None, "Show each request with its response code and timing. Give twice (-vv) to also show headers and bodies.", NO_COMPLETION) },
+    flag(None, "debug", None, "Show requests and responses with their headers and bodies, the same as -vv.", NO_COMPLETION),
+    flag(Some('y'), "yes", None, "Empty the trash bin without asking for confirmation first.", NO_COMPLETION),
+    flag(None, "trace-file", Some("FILE"), "Record every request and response in FILE as HAR-like JSON, to attach to bug reports.", Completion::Files),
+    FlagSpec { hidden: true, ..flag(None, "names", None, "Output just the titles of the notes, for shell completion.", NO_COMPLETION) },
+    flag(Some('h'), "help", None, "Display help for the command instead of running it.", NO_COMPLETION),
+];
+
+const HELP_FLAG: &str = "help";
+
+/// Every command the program understands, with posting a note first as it's the default.
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "",
+        arguments: &[ArgumentSpec { name: "NOTES BODY", required: true, completion: NO_COMPLETION }],
+        help: "Post a note. Use - to read the notes body from stdin.",
+        description: "Uploads the notes body to the notes directory on the server. The title is taken from the first line of \
+            the body (or the title template in the config file) unless one is given, and front matter recording the note's tags, \
+            the machine and directory it was posted from and when is added unless turned off. Use - as the body to read it from \
+            stdin, or --clipboard to post what was last copied. Put -- before a body that starts with a -.",
+        flags: &["title", "password", "password-stdin", "attach", "extension", "category", "collision", "tag", "no-meta", "clipboard", "verbose", "debug", "trace-file"],
+        examples: &[
+            "\"NOTES BODY\"",
+            "-p\"NxCloudPassword\" \"NOTES BODY\"",
+            "-t\"NOTES TITLE\" \"NOTES BODY\"",
+            "-e\"md\" \"NOTES BODY\"",
+            "-t\"NOTES TITLE\" -o\"append\" \"NOTES BODY\"",
+            "-c\"Work/Meetings\" \"NOTES BODY\"",
+            "-a\"screenshot.png\" -a\"log.txt\" \"NOTES BODY\"",
+            "--tag=work --tag=deploy \"NOTES BODY\"",
+            "--clipboard",
+            "-- \"-5 degrees outside\"",
+        ],
+    },
+    CommandSpec {
+        name: "help",
+        arguments: &[ArgumentSpec { name: "COMMAND", required: false, completion: Completion::Commands }],
+        help: "Display usage information, or help for the given command.",
+        description: "Outputs the usage of the program, with every command and flag. Given a command, outputs what that \
+            command does along with its flags and examples instead, which is the same as giving the command --help.",
+        flags: &[],
+        examples: &["help", "help read"],
+    },
+    CommandSpec {
+        name: "config-path",
+        arguments: &[],
+        help: "Output path to .toml config file used for this application.",
+        description: "Outputs where the config file is kept. It's created the first time the program is run, by asking for \
+            the server details, and can be edited to change them or to set options that aren't asked for.",
+        flags: &[],
+        examples: &["config-path"],
+    },
+    CommandSpec {
+        name: "categories",
+        arguments: &[],
+        help: "List the categories (subfolders) of the notes directory.",
+        description: "Outputs every category in the notes directory, one per line, with subcategories given as their path \
+            (ie Work/Meetings). Hidden folders, such as those attachments are kept in, are left out.",
+        flags: &["password", "password-stdin", "verbose", "debug", "trace-file"],
+        examples: &["categories"],
+    },
+    CommandSpec {
+        name: "list",
+        arguments: &[],
+        help: "List the notes in the notes directory, optionally only those with the given tags.",
+        description: "Outputs the path of every note in the notes directory and its categories, one per line. When tags \
+            are given, each note is read to check its front matter and only those with all of the tags are listed.",
+        flags: &["password", "password-stdin", "tag", "names", "verbose", "debug", "trace-file"],
+        examples: &["list --tag=work", "list -v --trace-file=list.har"],
+    },
+    CommandSpec {
+        name: "read",
+        arguments: &[ArgumentSpec { name: "NOTES TITLE", required: true, completion: Completion::NoteTitles }],
+        help: "Output the note with the given title (ie \"Work/Meeting\" for a note in a category).",
+        description: "Finds the note with the given title, which can be given with or without its extension, and outputs \
+            it. Notes in a category are found by giving the category as part of the title or with --category.",
+        flags: &["password", "password-stdin", "category", "no-meta", "copy", "verbose", "debug", "trace-file"],
+        examples: &["read \"NOTES TITLE\"", "read --copy \"NOTES TITLE\""],
+    },
+    CommandSpec {
+        name: "versions",
+        arguments: &[ArgumentSpec { name: "NOTES TITLE", required: true, completion: Completion::NoteTitles }],
+        help: "List the previous versions of the note with the given title, newest first.",
+        description: "Outputs the versions Nextcloud has kept of the note from before it was changed, one per line with \
+            the id to restore it by, when it was last modified and its size. The note is found as it is for read.",
+        flags: &["password", "password-stdin", "category", "verbose", "debug", "trace-file"],
+        examples: &["versions \"NOTES TITLE\""],
+    },
+    CommandSpec {
+        name: "restore",
+        arguments: &[
+            ArgumentSpec { name: "NOTES TITLE", required: true, completion: Completion::NoteTitles },
+            ArgumentSpec { name: "VERSION", required: true, completion: NO_COMPLETION },
+        ],
+        help: "Restore the note with the given title to one of the versions listed by versions.",
+        description: "Replaces the note's content with that of the version with the given id. Nextcloud keeps the \
+            content being replaced as another version, so it can be restored in turn.",
+        flags: &["password", "password-stdin", "category", "verbose", "debug", "trace-file"],
+        examples: &["restore \"NOTES TITLE\" 1697712345"],
+    },
+    CommandSpec {
+        name: "trash",
+        arguments: &[
+            ArgumentSpec { name: "ACTION", required: true, completion: Completion::Choices(&["list", "restore", "empty"]) },
+            ArgumentSpec { name: "NAME", required: false, completion: NO_COMPLETION },
+        ],
+        help: "List, restore or permanently delete the notes in the trash bin.",
+        description: "Nextcloud keeps deleted notes in its trash bin. trash list outputs the ones deleted from the notes \
+            directory, most recently deleted first, with the name they have in the trash bin, where they were deleted \
+            from and when. trash restore puts the one with the given name back where it was, and trash empty deletes \
+            them for good, after asking on the terminal unless --yes is given. Anything else in the trash bin is left alone.",
+        flags: &["password", "password-stdin", "yes", "verbose", "debug", "trace-file"],
+        examples: &["trash list", "trash restore \"Meeting.md.d1697712345\"", "trash empty", "trash empty --yes"],
+    },
+    CommandSpec {
+        name: "browse",
+        arguments: &[],
+        help: "Browse, edit, create, rename and delete notes in a full screen terminal UI.",
+        description: "Lists the notes alongside a preview of the selected one. Type / to fuzzy find notes by title, \
+            Enter to edit the selected note in $VISUAL or $EDITOR, n to create a note, r to rename or move it to \
+            another category, d to delete it and q to quit. Requests can be recorded with --trace-file, \
+            but aren't logged to the terminal as that would draw over the browser.",
+        flags: &["password", "password-stdin", "trace-file"],
+        examples: &["browse"],
+    },
+    CommandSpec {
+        name: "completions",
+        arguments: &[ArgumentSpec { name: "SHELL", required: true, completion: Completion::Choices(&["bash", "zsh", "fish"]) }],
+        help: "Output a completion script for bash, zsh or fish.",
+        description: "Outputs a script that lets the shell complete the program's commands and flags, along with the \
+            titles of notes for the read command. Load it from the shell's startup file or install it where the shell \
+            looks for completions.",
+        flags: &[],
+        examples: &["completions bash > /etc/bash_completion.d/nxcloudnotes"],
+    },
+    CommandSpec {
+        name: "man",
+        arguments: &[],
+        help: "Output the man page, to be installed as nxcloudnotes.1.",
+        description: "Outputs the program's man page in roff format, built from the same information as its help.",
+        flags: &[],
+        examples: &["man > /usr/local/share/man/man1/nxcloudnotes.1"],
+    },
+];
+
+/// Looks up a flag by its long name.
+pub fn flag_spec(long: &str) -> Option<&'static FlagSpec> {
+    FLAGS.iter().find(|flag| flag.long == long)
+}
+
+/// Looks up a flag by its short name.
+fn short_flag_spec(short: char) -> Result<&'static FlagSpec, String> {
+    FLAGS.iter().find(|flag| flag.short == Some(short)).ok_or_else(|| format!("Unknown flag -{}", short))
+}
+
+/// Looks up a command by name. Posting a note has no name, so isn't found.
+pub fn command_spec(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|command| !command.name.is_empty() && command.name == name)
+}
+
+impl CommandSpec {
+    /// The flags the command accepts, leaving out hidden ones.
+    pub fn visible_flags(&self) -> impl Iterator<Item = &'static FlagSpec> + '_ {
+        self.flags
+            .iter()
+            .chain(std::iter::once(&HELP_FLAG))
+            .filter_map(|long| flag_spec(long))
+            .filter(|flag| !flag.hidden)
+    }
+
+    fn accepts_flag(&self, long: &str) -> bool {
+        long == HELP_FLAG || self.flags.contains(&long)
+    }
+
+    /// How the command is run, ie `nxcloudnotes read [<flags>] "NOTES TITLE"`.
+    pub fn synopsis(&self) -> String {
+        let mut synopsis = String::from("nxcloudnotes");
+        if !self.name.is_empty() {
+            synopsis.push(' ');
+            synopsis.push_str(self.name);
+        }
+        synopsis.push_str(" [<flags>]");
+        for argument in self.arguments {
+            if argument.required {
+                synopsis.push_str(&format!(" \"{}\"", argument.name));
+            } else {
+                synopsis.push_str(&format!(" [{}]", argument.name));
+            }
+        }
+        synopsis
+    }
+}
+
+impl FlagSpec {
+    /// How the flag is shown in help, ie `-t, --title=TITLE`.
+    pub fn synopsis(&self) -> String {
+        let long = match self.value_name {
+            Some(value_name) => format!("--{}={}", self.long, value_name),
+            None => format!("--{}", self.long),
+        };
+        match self.short {
+            Some(short) => format!("-{}, {}", short, long),
+            None => long,
+        }
+    }
+}
+
+/// Usage information for the whole program, built from the command and flag tables.
+pub fn usage() -> String {
+    let mut usage = String::from("\nnxcloudnotes -- A NextCloud CLI dumping tool.\nUsage:\n  nxcloudnotes <command> [<args>...]\n");
+    for command in COMMANDS.iter().skip(1) {
+        for example in command.examples {
+            usage.push_str(&format!("  nxcloudnotes {}\n", example));
+        }
+    }
+    for example in COMMANDS[0].examples {
+        usage.push_str(&format!("  nxcloudnotes {}\n", example));
+    }
+    usage.push_str("  some-command | nxcloudnotes -\nCommands:\n");
+    for command in COMMANDS.iter().skip(1) {
+        usage.push_str(&format!("  {:<17}{}\n", command.name, command.help));
+    }
+    usage.push_str(&format!("  {:<17}{}\n", "\"NOTES BODY\"", COMMANDS[0].help));
+    usage.push_str("Flags:\n");
+    for flag in FLAGS.iter().filter(|flag| !flag.hidden) {
+        usage.push_str(&format!("  {:<26}{}\n", flag.synopsis(), flag.help));
+    }
+    usage
+}
+
+/// Help for a single command: what it does, its flags and examples.
+pub fn command_help(command: &CommandSpec) -> String {
+    let mut help = format!("\n{}\n  {}\n\n", command.synopsis(), command.help);
+    help.push_str(&wrap(command.description, 78, "  "));
+    help.push_str("\nFlags:\n");
+    for flag in command.visible_flags() {
+        help.push_str(&format!("  {:<26}{}\n", flag.synopsis(), flag.help));
+    }
+    help.push_str("Examples:\n");
+    for example in command.examples {
+        help.push_str(&format!("  nxcloudnotes {}\n", example));
+    }
+    help
+}
+
+/// Wraps text at spaces so no line is longer than `width`, indenting every line.
+fn wrap(text: &str, width: usize, indent: &str) -> String {
+    let mut wrapped = String::new();
+    let mut line = String::from(indent);
+    for word in text.split_whitespace() {
+        if line.len() > indent.len() && line.len() + 1 + word.len() > width {
+            wrapped.push_str(line.trim_end());
+            wrapped.push('\n');
+            line = String::from(indent);
+        }
+        if line.len() > indent.len() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if line.len() > indent.len() {
+        wrapped.push_str(&line);
+        wrapped.push('\n');
+    }
+    wrapped
+}
 
 /// Supported commands that can be parsed from the program arguments
 #[derive(Debug, PartialEq)]
 pub enum ProgramCommands {
     PostNote(PostNoteCLIConfig),
     ConfigPath,
-    Help(&'static str),
+    Categories(ConnectionCLIConfig),
+    List(ListNotesCLIConfig),
+    Read(ReadNoteCLIConfig),
+    Versions(NoteVersionsCLIConfig),
+    Restore(RestoreVersionCLIConfig),
+    Trash(TrashCLIConfig),
+    Browse(ConnectionCLIConfig),
+    Completions(Shell),
+    Man,
+    Help(String),
 }
 
-// for internal use only, this is just the content parsed from the given arguments on the command line
-// what should be returned to the user is the ProgramCommands enum
-// this is used because initially the just the command and raw input is parsed
-// which is internally mapped to a config struxt
-enum ParsedCommands {
-    PostNote(String),
-    ConfigPath,
-    Help
+/// Where the password comes from when none is saved in the config file.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum PasswordSource {
+    /// Taken from the environment, or asked for.
+    #[default]
+    Unset,
+    /// Given with the deprecated --password flag.
+    Argument(String),
+    /// The first line of stdin, with --password-stdin.
+    Stdin,
+}
+
+/// How much of what's sent to the server is logged, for every command that connects to it.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LoggingCLIConfig {
+    /// How many times -v was given, where --debug counts as two.
+    pub verbosity: usize,
+    /// Where to record every request and response, with --trace-file.
+    pub trace_file: Option<String>,
 }
 
 /// Configuration for the PostNote command. Passed via command-line arguments
 #[derive(Debug, PartialEq)]
 pub struct PostNoteCLIConfig {
-    pub password: Option<String>,
+    pub password: PasswordSource,
+    pub logging: LoggingCLIConfig,
     pub title: Option<String>,
     pub content: String,
+    /// File extension of the note, overriding the configured one.
+    pub extension: Option<String>,
+    /// Category (subfolder of the notes directory) to post the note into, overriding the configured default.
+    pub category: Option<String>,
+    /// What to do if the note already exists, overriding the configured policy.
+    pub collision_policy: Option<CollisionPolicy>,
+    /// Paths of local files to upload alongside the note and reference from its body.
+    pub attachments: Vec<String>,
+    /// Tags recorded in the note's front matter.
+    pub tags: Vec<String>,
+    /// Whether to leave the front matter out of the note.
+    pub no_meta: bool,
+    /// Whether the note's body is taken from the clipboard instead of the content.
+    pub from_clipboard: bool,
 }
 
-#[derive(Debug)]
-enum Arg<FlagType> {
-    Plain(FlagType),
-    Short(FlagType, String),
+/// Configuration for the List command. Passed via command-line arguments
+#[derive(Debug, PartialEq)]
+pub struct ListNotesCLIConfig {
+    pub password: PasswordSource,
+    pub logging: LoggingCLIConfig,
+    /// Only list notes that have all of these tags in their front matter.
+    pub tags: Vec<String>,
+    /// Whether to output just the titles of the notes, as used by shell completion.
+    pub names: bool,
 }
 
-impl Arg<String> {
-    fn flag_as_ref(&self) -> Arg<&str> {
-        match *self {
-            Arg::Plain(ref x) => Arg::Plain(&x[..]),
-            Arg::Short(ref x, ref value) => Arg::Short(&x[..], value.clone()),
-        }
-    }
+/// Configuration for the Read command. Passed via command-line arguments
+#[derive(Debug, PartialEq)]
+pub struct ReadNoteCLIConfig {
+    pub password: PasswordSource,
+    pub logging: LoggingCLIConfig,
+    pub title: String,
+    /// Category the note is in, as an alternative to giving it as part of the title.
+    pub category: Option<String>,
+    /// Whether to leave the front matter out of the output.
+    pub no_meta: bool,
+    /// Whether to copy the note to the clipboard instead of outputting it.
+    pub copy: bool,
 }
 
-impl Display for Arg<String> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        match *self {
-            Arg::Short(ref arg, ref _val) => write!(f, "-{}", arg),
-            Arg::Plain(ref cmd) => write!(f, "{}", cmd),
-        }
-    }
+/// Configuration for the Versions command. Passed via command-line arguments
+#[derive(Debug, PartialEq)]
+pub struct NoteVersionsCLIConfig {
+    pub password: PasswordSource,
+    pub logging: LoggingCLIConfig,
+    pub title: String,
+    /// Category the note is in, as an alternative to giving it as part of the title.
+    pub category: Option<String>,
 }
 
-struct ArgIter {
-    args: IntoIter<String>,
+/// Configuration for the Restore command. Passed via command-line arguments
+#[derive(Debug, PartialEq)]
+pub struct RestoreVersionCLIConfig {
+    pub password: PasswordSource,
+    pub logging: LoggingCLIConfig,
+    pub title: String,
+    /// Category the note is in, as an alternative to giving it as part of the title.
+    pub category: Option<String>,
+    /// Id of the version to restore, as listed by the Versions command.
+    pub version: String,
 }
 
-impl ArgIter {
-    pub fn new(args: Vec<String>) -> ArgIter {
-        ArgIter {
-            args: args.into_iter(),
-        }
-    }
+/// What the Trash command does with the trash bin.
+#[derive(Debug, PartialEq)]
+pub enum TrashAction {
+    List,
+    /// Restore the item with the given name.
+    Restore(String),
+    Empty,
 }
 
-impl Iterator for ArgIter {
-    type Item = Arg<String>;
+/// Configuration for the Trash command. Passed via command-line arguments
+#[derive(Debug, PartialEq)]
+pub struct TrashCLIConfig {
+    pub password: PasswordSource,
+    pub logging: LoggingCLIConfig,
+    pub action: TrashAction,
+    /// Empty the trash bin without asking first.
+    pub yes: bool,
+}
 
-    fn next(&mut self) -> Option<Arg<String>> {
-        let arg = self.args.next()?;
+/// Configuration for commands that only need to connect to the server. Passed via command-line arguments
+#[derive(Debug, PartialEq)]
+pub struct ConnectionCLIConfig {
+    pub password: PasswordSource,
+    pub logging: LoggingCLIConfig,
+}
 
-        // to simplify this, the program only supports - arguments
-        // eg -t"TEST_TITLE"
-        if arg.starts_with("-") {
-            let mut flag = String::from(&arg[1..]);
-            if flag.len() > 1 {
-                let user_value = flag.split_off(1);
-                flag.truncate(1);
-                return Some(Arg::Short(flag, user_value));
-            }
+/// An argument that isn't a flag, ie a command name or a notes body.
+struct Positional {
+    value: String,
+    /// Given after `--`, so never treated as a command name.
+    literal: bool,
+}
+
+/// Values given for each flag, in the order they were given, keyed by the flag's long name.
+#[derive(Default)]
+struct FlagMap {
+    flags: HashMap<&'static str, Vec<String>>,
+    /// Every flag that was given, in order, to check them against the command once it's known.
+    given: Vec<&'static FlagSpec>,
+}
+
+impl FlagMap {
+    fn insert(&mut self, flag: &'static FlagSpec, value: String) {
+        self.flags.entry(flag.long).or_default().push(value);
+        self.given.push(flag);
+    }
+
+    /// For flags that only take one value, the last one given wins.
+    fn last_value(&mut self, flag: &str) -> Option<String> {
+        self.flags.remove(flag).and_then(|mut values| values.pop())
+    }
+
+    fn all_values(&mut self, flag: &str) -> Vec<String> {
+        self.flags.remove(flag).unwrap_or_default()
+    }
+
+    fn is_set(&self, flag: &str) -> bool {
+        self.flags.contains_key(flag)
+    }
+
+    fn password_source(&mut self) -> Result<PasswordSource, String> {
+        match (self.last_value("password"), self.is_set("password-stdin")) {
+            (Some(_), true) => Err(String::from("Give either --password or --password-stdin, not both")),
+            (Some(password), false) => Ok(PasswordSource::Argument(password)),
+            (None, true) => Ok(PasswordSource::Stdin),
+            (None, false) => Ok(PasswordSource::Unset),
         }
+    }
+
+    fn logging(&mut self) -> LoggingCLIConfig {
+        let debug = if self.is_set("debug") { 2 } else { 0 };
+        LoggingCLIConfig {
+            verbosity: self.all_values("verbose").len().max(debug),
+            trace_file: self.last_value("trace-file"),
+        }
+    }
+}
+
+/// The value of a flag: the one attached to it (`--title=x` or `-tx`), otherwise the next argument
+/// for flags that take a value. Switches can't be given a value.
+fn flag_value<I: Iterator<Item = String>>(flag: &FlagSpec, given_as: &str, attached: Option<&str>, args: &mut I) -> Result<String, String> {
+    match (flag.value_name, attached) {
+        (Some(_), Some(value)) => Ok(String::from(value)),
+        (Some(value_name), None) => args
+            .next()
+            .ok_or_else(|| format!("{} needs a value ({})", given_as, value_name)),
+        (None, None) => Ok(String::new()),
+        (None, Some(_)) => Err(format!("{} is a switch and doesn't take a value", given_as)),
+    }
+}
 
-        Some(Arg::Plain(arg))
+/// Help for the command, which for posting a note is the usage of the whole program as that's what's run without a command.
+fn help_for(command: &CommandSpec) -> String {
+    if command.name.is_empty() {
+        usage()
+    } else {
+        command_help(command)
+    }
+}
+
+/// Describes a command for error messages.
+fn command_description(command: &CommandSpec) -> String {
+    if command.name.is_empty() {
+        String::from("posting a note")
+    } else {
+        format!("the {} command", command.name)
     }
 }
 
 /// parses the given argv vector. This SHOULD contain the executable name in the vector.
-/// Defaults to `ParsedCommands::Help` if no arguments are present.
+/// Defaults to `ProgramCommands::Help` if no arguments are present.
+///
+/// Flags can be given anywhere, either as `--long=value`, `--long value`, `-svalue` or `-s value`,
+/// and everything after `--` is treated as a plain argument. The first plain argument is the command,
+/// or the notes body if it isn't the name of one.
 /// ## Example: 
 /// ```
 /// use nxcloudnotes::cliarguments;
@@ -112,38 +571,183 @@ impl Iterator for ArgIter {
 /// let command = cliarguments::parse_args(pattern).unwrap();
 /// ```
 pub fn parse_args(argv: Vec<String>) -> Result<ProgramCommands, String> {
-    let mut args = ArgIter::new(argv);
+    let mut args = argv.into_iter();
     // Skip the executable name
     args.next();
 
-    let mut operator = ParsedCommands::Help;
-    // each supported flag is placed with its corresponding user input
-    let mut flag_map = HashMap::new();
+    let mut positionals: Vec<Positional> = Vec::new();
+    let mut flag_map = FlagMap::default();
 
     while let Some(arg) = args.next() {
-        match arg.flag_as_ref() {
-            Arg::Plain("config-path") => operator = ParsedCommands::ConfigPath,
-            Arg::Plain("help") => operator = ParsedCommands::Help,
-            Arg::Plain(any_other_val) => operator = ParsedCommands::PostNote(String::from(any_other_val)),
-            Arg::Short(flag, value) => {flag_map.insert(String::from(flag), value);}
+        if arg == "--" {
+            positionals.extend(args.by_ref().map(|value| Positional { value, literal: true }));
+            break;
         }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, attached) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            let flag = flag_spec(name).ok_or_else(|| format!("Unknown flag --{}", name))?;
+            let value = flag_value(flag, &format!("--{}", name), attached, &mut args)?;
+            flag_map.insert(flag, value);
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            let mut chars = arg[1..].chars();
+            let short = chars.next().unwrap_or_default();
+            let attached = Some(chars.as_str()).filter(|value| !value.is_empty());
+            let flag = short_flag_spec(short)?;
+            // switches can be grouped, ie -vv for -v -v
+            let grouped: Option<Vec<&'static FlagSpec>> = match (flag.value_name, attached) {
+                (None, Some(rest)) => rest.chars().map(|short| short_flag_spec(short).ok().filter(|flag| flag.value_name.is_none())).collect(),
+                _ => None,
+            };
+            match grouped {
+                Some(grouped) => {
+                    for flag in std::iter::once(flag).chain(grouped) {
+                        flag_map.insert(flag, String::new());
+                    }
+                },
+                None => {
+                    let value = flag_value(flag, &format!("-{}", short), attached, &mut args)?;
+                    flag_map.insert(flag, value);
+                },
+            }
+        } else {
+            // a lone - is the notes body being read from stdin
+            positionals.push(Positional { value: arg, literal: false });
+        }
+    }
+
+    if positionals.is_empty() && flag_map.given.is_empty() {
+        return Ok(ProgramCommands::Help(usage()));
     }
 
-    let cmd = match operator {
-        ParsedCommands::ConfigPath => ProgramCommands::ConfigPath,
-        ParsedCommands::Help => ProgramCommands::Help(USAGE),
-        ParsedCommands::PostNote(content) => ProgramCommands::PostNote(parse_flags_to_post_note_cli_config(flag_map, content))
+    let named_command = positionals.first().filter(|first| !first.literal).and_then(|first| command_spec(&first.value));
+    let command = match named_command {
+        Some(command) => {
+            positionals.remove(0);
+            command
+        },
+        None => &COMMANDS[0],
+    };
+
+    if let Some(flag) = flag_map.given.iter().find(|flag| !command.accepts_flag(flag.long)) {
+        return Err(format!("{} isn't a flag for {}", flag.synopsis(), command_description(command)));
+    }
+    if flag_map.is_set(HELP_FLAG) {
+        return Ok(ProgramCommands::Help(help_for(command)));
+    }
+
+    let mut arguments: Vec<String> = positionals.into_iter().map(|positional| positional.value).collect();
+    if let Some(unexpected) = arguments.get(command.arguments.len()) {
+        let hint = if command.name.is_empty() { ", put the notes body in quotes if it has spaces" } else { "" };
+        return Err(format!("Unexpected argument \"{}\" for {}{}", unexpected, command_description(command), hint));
+    }
+    // the clipboard takes the place of the notes body, so no body is needed to post a note
+    let clipboard_body = command.name.is_empty() && flag_map.is_set("clipboard");
+    if let Some(missing) = command.arguments[arguments.len()..].iter().find(|expected| expected.required && !clipboard_body) {
+        return Err(format!("Missing {} for {}", missing.name, command_description(command)));
+    }
+    arguments.resize(command.arguments.len(), String::new());
+    let mut arguments = arguments.into_iter();
+    let argument = arguments.next().unwrap_or_default();
+
+    let cmd = match command.name {
+        "config-path" => ProgramCommands::ConfigPath,
+        "categories" => ProgramCommands::Categories(ConnectionCLIConfig {
+            password: flag_map.password_source()?,
+            logging: flag_map.logging(),
+        }),
+        "list" => ProgramCommands::List(ListNotesCLIConfig {
+            password: flag_map.password_source()?,
+            logging: flag_map.logging(),
+            tags: flag_map.all_values("tag"),
+            names: flag_map.is_set("names"),
+        }),
+        "read" => ProgramCommands::Read(ReadNoteCLIConfig {
+            password: flag_map.password_source()?,
+            logging: flag_map.logging(),
+            title: argument,
+            category: flag_map.last_value("category"),
+            no_meta: flag_map.is_set("no-meta"),
+            copy: flag_map.is_set("copy"),
+        }),
+        "versions" => ProgramCommands::Versions(NoteVersionsCLIConfig {
+            password: flag_map.password_source()?,
+            logging: flag_map.logging(),
+            title: argument,
+            category: flag_map.last_value("category"),
+        }),
+        "restore" => ProgramCommands::Restore(RestoreVersionCLIConfig {
+            password: flag_map.password_source()?,
+            logging: flag_map.logging(),
+            title: argument,
+            category: flag_map.last_value("category"),
+            version: arguments.next().unwrap_or_default(),
+        }),
+        "trash" => ProgramCommands::Trash(TrashCLIConfig {
+            password: flag_map.password_source()?,
+            logging: flag_map.logging(),
+            action: parse_trash_action(&argument, arguments.next().unwrap_or_default())?,
+            yes: flag_map.is_set("yes"),
+        }),
+        "browse" => ProgramCommands::Browse(ConnectionCLIConfig {
+            password: flag_map.password_source()?,
+            logging: flag_map.logging(),
+        }),
+        "completions" => ProgramCommands::Completions(argument.parse()?),
+        "man" => ProgramCommands::Man,
+        "help" if !argument.is_empty() => {
+            let topic = command_spec(&argument)
+                .ok_or_else(|| format!("Unknown command {}, see nxcloudnotes help for the commands there are", argument))?;
+            ProgramCommands::Help(help_for(topic))
+        },
+        "" => ProgramCommands::PostNote(parse_flags_to_post_note_cli_config(flag_map, argument)?),
+        _ => ProgramCommands::Help(usage()),
     };
 
     Ok(cmd)
 }
 
-fn parse_flags_to_post_note_cli_config(mut flag_map: HashMap<String, String>, content: String) -> PostNoteCLIConfig {
-    PostNoteCLIConfig {
-        title: flag_map.remove("t"),
-        password: flag_map.remove("p"),
-        content
+/// Only restoring takes the name of an item in the trash bin.
+fn parse_trash_action(action: &str, name: String) -> Result<TrashAction, String> {
+    match (action, name.is_empty()) {
+        ("list", true) => Ok(TrashAction::List),
+        ("empty", true) => Ok(TrashAction::Empty),
+        ("restore", false) => Ok(TrashAction::Restore(name)),
+        ("restore", true) => Err(String::from("Missing NAME for trash restore, see nxcloudnotes trash list for the names")),
+        ("list", false) | ("empty", false) => Err(format!("Unexpected argument \"{}\" for trash {}", name, action)),
+        _ => Err(format!("Unknown trash action {}, expected one of list, restore or empty", action)),
+    }
+}
+
+fn parse_flags_to_post_note_cli_config(mut flag_map: FlagMap, content: String) -> Result<PostNoteCLIConfig, String> {
+    let from_clipboard = flag_map.is_set("clipboard");
+    if from_clipboard && !content.is_empty() {
+        return Err(String::from("Give either the notes body or --clipboard, not both"));
+    }
+    let password = flag_map.password_source()?;
+    if password == PasswordSource::Stdin && content == "-" {
+        return Err(String::from("The notes body and the password can't both be read from stdin"));
     }
+    let collision_policy = match flag_map.last_value("collision") {
+        Some(policy) => Some(policy.parse()?),
+        None => None,
+    };
+    Ok(PostNoteCLIConfig {
+        title: flag_map.last_value("title"),
+        password,
+        logging: flag_map.logging(),
+        extension: flag_map.last_value("extension"),
+        category: flag_map.last_value("category"),
+        collision_policy,
+        content,
+        attachments: flag_map.all_values("attach"),
+        tags: flag_map.all_values("tag"),
+        no_meta: flag_map.is_set("no-meta"),
+        from_clipboard,
+    })
 
 }
 
@@ -158,26 +762,36 @@ mod tests {
     
     #[test]
     fn should_parse_help_command_correctly() {
-        let help_command = Ok(ProgramCommands::Help(USAGE));
+        let help_command = Ok(ProgramCommands::Help(usage()));
         assert_eq!(parse_slice(&["nxcloudnotes", "help"]), help_command);
-        assert_eq!(parse_slice(&["nxcloudnotes", "test", "bar" ,"help", "-ttestparams"]), help_command);
-        assert_eq!(parse_slice(&["nxcloudnotes", "-t\"testparams\"", "help"]), help_command)
+        assert_eq!(parse_slice(&["nxcloudnotes"]), help_command);
+        // only the first plain argument can be a command
+        assert!(parse_slice(&["nxcloudnotes", "test", "bar" ,"help", "-ttestparams"]).is_err());
+        assert!(parse_slice(&["nxcloudnotes", "-t\"testparams\"", "help"]).is_err())
     }
 
     #[test]
     fn should_parse_config_path_command_correctly() {
         let config_path_command = Ok(ProgramCommands::ConfigPath);
         assert_eq!(parse_slice(&["nxcloudnotes", "config-path"]), config_path_command);
-        assert_eq!(parse_slice(&["nxcloudnotes", "test", "bar" ,"config-path", "-ttestparams"]), config_path_command);
-        assert_eq!(parse_slice(&["nxcloudnotes", "-ttestparams", "config-path"]), config_path_command)
+        assert!(parse_slice(&["nxcloudnotes", "test", "bar" ,"config-path", "-ttestparams"]).is_err());
+        assert!(parse_slice(&["nxcloudnotes", "-ttestparams", "config-path"]).is_err())
     }
 
     #[test]
     fn should_parse_post_note_command_correctly() {
         let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
-            password: None,
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
             title: None,
-            content: "note content here".to_string()
+            content: "note content here".to_string(),
+            extension: None,
+            category: None,
+            collision_policy: None,
+            attachments: vec![],
+            tags: vec![],
+            no_meta: false,
+            from_clipboard: false,
         }));
         assert_eq!(parse_slice(&["nxcloudnotes", "note content here"]), post_note_command);
     }  
@@ -185,11 +799,324 @@ mod tests {
     #[test]
     fn should_parse_post_note_command_with_flags() {
         let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
-            password: Some("password".to_string()),
+            password: PasswordSource::Argument("password".to_string()),
+            logging: LoggingCLIConfig::default(),
             title: Some("title".to_string()),
-            content: String::from("note content here")
+            content: String::from("note content here"),
+            extension: None,
+            category: None,
+            collision_policy: None,
+            attachments: vec![],
+            tags: vec![],
+            no_meta: false,
+            from_clipboard: false,
         }));
         assert_eq!(parse_slice(&["nxcloudnotes", "note content here", "-ttitle", "-ppassword"]), post_note_command);   
         assert_eq!(parse_slice(&["nxcloudnotes", "-ttitle", "-ppassword", "note content here"]), post_note_command);   
      }
+
+    #[test]
+    fn should_parse_extension_flag() {
+        let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: None,
+            content: String::from("note content here"),
+            extension: Some("md".to_string()),
+            category: None,
+            collision_policy: None,
+            attachments: vec![],
+            tags: vec![],
+            no_meta: false,
+            from_clipboard: false,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "-emd", "note content here"]), post_note_command);
+    }
+
+    #[test]
+    fn should_parse_repeated_attachment_flags() {
+        let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: Some("second".to_string()),
+            content: String::from("note content here"),
+            extension: None,
+            category: None,
+            collision_policy: None,
+            attachments: vec!["screenshot.png".to_string(), "logs/output.txt".to_string()],
+            tags: vec![],
+            no_meta: false,
+            from_clipboard: false,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "-ascreenshot.png", "-tfirst", "note content here", "-alogs/output.txt", "-tsecond"]), post_note_command);
+    }
+
+    #[test]
+    fn should_parse_collision_policy_flag() {
+        let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: None,
+            content: String::from("note content here"),
+            extension: None,
+            category: None,
+            collision_policy: Some(CollisionPolicy::Suffix),
+            attachments: vec![],
+            tags: vec![],
+            no_meta: false,
+            from_clipboard: false,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "-osuffix", "note content here"]), post_note_command);
+        assert!(parse_slice(&["nxcloudnotes", "-oclobber", "note content here"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_category_flag() {
+        let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: None,
+            content: String::from("note content here"),
+            extension: None,
+            category: Some("Work/Meetings".to_string()),
+            collision_policy: None,
+            attachments: vec![],
+            tags: vec![],
+            no_meta: false,
+            from_clipboard: false,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "-cWork/Meetings", "note content here"]), post_note_command);
+    }
+
+    #[test]
+    fn should_parse_categories_command_correctly() {
+        let categories_command = Ok(ProgramCommands::Categories(ConnectionCLIConfig { password: PasswordSource::Argument("password".to_string()), logging: LoggingCLIConfig::default() }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "categories", "-ppassword"]), categories_command);
+    }
+
+    #[test]
+    fn should_read_password_from_stdin() {
+        let list_command = Ok(ProgramCommands::List(ListNotesCLIConfig { password: PasswordSource::Stdin, logging: LoggingCLIConfig::default(), tags: vec![], names: false }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "list", "--password-stdin"]), list_command);
+        assert!(parse_slice(&["nxcloudnotes", "list", "--password-stdin", "-ppassword"]).is_err());
+        // stdin can't hold both the body and the password
+        assert!(parse_slice(&["nxcloudnotes", "--password-stdin", "-"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_browse_command() {
+        let browse_command = Ok(ProgramCommands::Browse(ConnectionCLIConfig { password: PasswordSource::Unset, logging: LoggingCLIConfig::default() }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "browse"]), browse_command);
+        assert!(parse_slice(&["nxcloudnotes", "browse", "--tag", "work"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_tags_and_no_meta_long_flags() {
+        let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: None,
+            content: String::from("-"),
+            extension: None,
+            category: None,
+            collision_policy: None,
+            attachments: vec![],
+            tags: vec!["work".to_string(), "deploy".to_string()],
+            no_meta: true,
+            from_clipboard: false,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "--tag=work", "-", "--no-meta", "--tag=deploy"]), post_note_command);
+    }
+
+    #[test]
+    fn should_parse_list_command_correctly() {
+        let list_command = Ok(ProgramCommands::List(ListNotesCLIConfig {
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            tags: vec!["work".to_string()],
+            names: false,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "list", "--tag=work"]), list_command);
+    }
+
+    #[test]
+    fn should_parse_read_command_correctly() {
+        let read_command = Ok(ProgramCommands::Read(ReadNoteCLIConfig {
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: "list".to_string(),
+            category: Some("Work".to_string()),
+            no_meta: true,
+            copy: false,
+        }));
+        // the title comes after read, even if it looks like another command
+        assert_eq!(parse_slice(&["nxcloudnotes", "read", "--no-meta", "list", "-cWork"]), read_command);
+        assert!(parse_slice(&["nxcloudnotes", "read"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_restore_command_with_title_and_version() {
+        let restore_command = Ok(ProgramCommands::Restore(RestoreVersionCLIConfig {
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: "Meeting".to_string(),
+            category: Some("Work".to_string()),
+            version: "1697712345".to_string(),
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "restore", "Meeting", "-cWork", "1697712345"]), restore_command);
+        assert_eq!(parse_slice(&["nxcloudnotes", "restore", "Meeting"]), Err("Missing VERSION for the restore command".to_string()));
+        assert!(parse_slice(&["nxcloudnotes", "restore", "Meeting", "1697712345", "extra"]).is_err());
+        assert_eq!(command_spec("restore").unwrap().synopsis(), "nxcloudnotes restore [<flags>] \"NOTES TITLE\" \"VERSION\"");
+    }
+
+    #[test]
+    fn should_parse_trash_actions() {
+        let trash_command = |action| Ok(ProgramCommands::Trash(TrashCLIConfig {
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            action,
+            yes: false,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "trash", "list"]), trash_command(TrashAction::List));
+        assert_eq!(parse_slice(&["nxcloudnotes", "trash", "restore", "a.md.d1"]), trash_command(TrashAction::Restore("a.md.d1".to_string())));
+        assert_eq!(parse_slice(&["nxcloudnotes", "trash", "empty"]), trash_command(TrashAction::Empty));
+        let confirmed = Ok(ProgramCommands::Trash(TrashCLIConfig {
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            action: TrashAction::Empty,
+            yes: true,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "trash", "empty", "--yes"]), confirmed);
+        assert_eq!(parse_slice(&["nxcloudnotes", "trash", "-y", "empty"]), confirmed);
+        assert!(parse_slice(&["nxcloudnotes", "trash", "restore"]).unwrap_err().starts_with("Missing NAME"));
+        assert_eq!(parse_slice(&["nxcloudnotes", "trash", "empty", "a.md.d1"]), Err("Unexpected argument \"a.md.d1\" for trash empty".to_string()));
+        assert!(parse_slice(&["nxcloudnotes", "trash", "purge"]).is_err());
+        assert!(parse_slice(&["nxcloudnotes", "trash"]).is_err());
+    }
+
+    #[test]
+    fn should_parse_clipboard_and_copy_switches() {
+        let post_note_command = Ok(ProgramCommands::PostNote(PostNoteCLIConfig{
+            password: PasswordSource::Unset,
+            logging: LoggingCLIConfig::default(),
+            title: Some("Copied".to_string()),
+            content: String::new(),
+            extension: None,
+            category: None,
+            collision_policy: None,
+            attachments: vec![],
+            tags: vec![],
+            no_meta: false,
+            from_clipboard: true,
+        }));
+        assert_eq!(parse_slice(&["nxcloudnotes", "--clipboard", "-tCopied"]), post_note_command);
+        assert!(parse_slice(&["nxcloudnotes", "--clipboard", "NOTES BODY"]).is_err());
+
+        let read_command = parse_slice(&["nxcloudnotes", "read", "Meeting", "--copy"]);
+        assert!(matches!(read_command, Ok(ProgramCommands::Read(ReadNoteCLIConfig { copy: true, .. }))));
+    }
+
+    #[test]
+    fn should_accept_long_and_short_forms_of_flags() {
+        let short = parse_slice(&["nxcloudnotes", "-tTitle", "-cWork", "-osuffix", "body"]);
+        let long = parse_slice(&["nxcloudnotes", "--title=Title", "--category=Work", "--collision=suffix", "body"]);
+        assert_eq!(short, long);
+    }
+
+    #[test]
+    fn should_parse_verbosity_and_trace_file() {
+        let logging = |args: &[&'static str]| match parse_slice(args) {
+            Ok(ProgramCommands::List(config)) => Ok(config.logging),
+            other => Err(format!("{:?}", other)),
+        };
+
+        assert_eq!(logging(&["nxcloudnotes", "list", "-v"]).unwrap().verbosity, 1);
+        assert_eq!(logging(&["nxcloudnotes", "list", "-vv"]).unwrap().verbosity, 2);
+        assert_eq!(logging(&["nxcloudnotes", "-v", "list", "--verbose"]).unwrap().verbosity, 2);
+        assert_eq!(
+            logging(&["nxcloudnotes", "list", "--debug", "--trace-file=list.har"]),
+            Ok(LoggingCLIConfig { verbosity: 2, trace_file: Some("list.har".to_string()) })
+        );
+        assert_eq!(parse_slice(&["nxcloudnotes", "list", "-vx"]), Err("-v is a switch and doesn't take a value".to_string()));
+        assert!(parse_slice(&["nxcloudnotes", "browse", "-v"]).is_err());
+        assert!(parse_slice(&["nxcloudnotes", "browse", "--trace-file=browse.har"]).is_ok());
+    }
+
+    #[test]
+    fn should_parse_completions_command() {
+        assert_eq!(parse_slice(&["nxcloudnotes", "completions", "zsh"]), Ok(ProgramCommands::Completions(Shell::Zsh)));
+        assert!(parse_slice(&["nxcloudnotes", "completions", "cmd"]).is_err());
+        assert!(matches!(
+            parse_slice(&["nxcloudnotes", "list", "--names"]),
+            Ok(ProgramCommands::List(ListNotesCLIConfig { names: true, .. }))
+        ));
+    }
+
+    #[test]
+    fn should_list_every_visible_flag_in_usage() {
+        let usage = usage();
+        for flag in FLAGS {
+            assert_eq!(usage.contains(&format!("--{}", flag.long)), !flag.hidden, "{}", flag.long);
+        }
+        for command in COMMANDS {
+            assert!(usage.contains(command.help));
+        }
+    }
+
+    #[test]
+    fn should_take_flag_values_from_the_next_argument() {
+        let glued = parse_slice(&["nxcloudnotes", "-tTitle", "--tag=work", "body"]);
+        assert_eq!(parse_slice(&["nxcloudnotes", "-t", "Title", "--tag", "work", "body"]), glued);
+        assert_eq!(parse_slice(&["nxcloudnotes", "body", "--title", "Title", "--tag", "work"]), glued);
+    }
+
+    #[test]
+    fn should_treat_everything_after_terminator_as_plain() {
+        let post_note_command = parse_slice(&["nxcloudnotes", "-tTitle", "--", "-5 degrees"]);
+        assert!(matches!(post_note_command, Ok(ProgramCommands::PostNote(PostNoteCLIConfig { ref content, .. })) if content == "-5 degrees"));
+
+        let post_note_command = parse_slice(&["nxcloudnotes", "--", "help"]);
+        assert!(matches!(post_note_command, Ok(ProgramCommands::PostNote(PostNoteCLIConfig { ref content, .. })) if content == "help"));
+    }
+
+    #[test]
+    fn should_report_unknown_and_missing_arguments() {
+        assert_eq!(parse_slice(&["nxcloudnotes", "--frobnicate", "body"]), Err("Unknown flag --frobnicate".to_string()));
+        assert_eq!(parse_slice(&["nxcloudnotes", "-x", "body"]), Err("Unknown flag -x".to_string()));
+        assert_eq!(parse_slice(&["nxcloudnotes", "body", "-t"]), Err("-t needs a value (TITLE)".to_string()));
+        assert_eq!(parse_slice(&["nxcloudnotes", "--no-meta=yes", "body"]), Err("--no-meta is a switch and doesn't take a value".to_string()));
+        assert_eq!(parse_slice(&["nxcloudnotes", "list", "-tTitle"]), Err("-t, --title=TITLE isn't a flag for the list command".to_string()));
+        assert_eq!(parse_slice(&["nxcloudnotes", "--copy", "body"]), Err("--copy isn't a flag for posting a note".to_string()));
+        assert_eq!(parse_slice(&["nxcloudnotes", "-tTitle"]), Err("Missing NOTES BODY for posting a note".to_string()));
+        assert_eq!(parse_slice(&["nxcloudnotes", "categories", "extra"]), Err("Unexpected argument \"extra\" for the categories command".to_string()));
+        assert!(parse_slice(&["nxcloudnotes", "note", "body"]).unwrap_err().contains("in quotes"));
+    }
+
+    #[test]
+    fn should_parse_help_for_a_command() {
+        let read_help = Ok(ProgramCommands::Help(command_help(command_spec("read").unwrap())));
+        assert_eq!(parse_slice(&["nxcloudnotes", "help", "read"]), read_help);
+        assert_eq!(parse_slice(&["nxcloudnotes", "read", "--help"]), read_help);
+        assert_eq!(parse_slice(&["nxcloudnotes", "-h", "read", "Title", "--no-meta"]), read_help);
+        assert_eq!(parse_slice(&["nxcloudnotes", "--help"]), Ok(ProgramCommands::Help(usage())));
+        assert!(parse_slice(&["nxcloudnotes", "help", "frobnicate"]).is_err());
+        assert_eq!(parse_slice(&["nxcloudnotes", "man"]), Ok(ProgramCommands::Man));
+    }
+
+    #[test]
+    fn should_describe_command_in_its_help() {
+        let help = command_help(command_spec("read").unwrap());
+        assert!(help.starts_with("\nnxcloudnotes read [<flags>] \"NOTES TITLE\"\n"));
+        assert!(help.contains("  --no-meta                 Don't add front matter"));
+        assert!(help.contains("  -h, --help"));
+        assert!(help.contains("  nxcloudnotes read --copy \"NOTES TITLE\"\n"));
+        assert!(!help.contains("--title"));
+        assert!(help.lines().all(|line| line.len() <= 140));
+    }
+
+    #[test]
+    fn should_wrap_descriptions() {
+        assert_eq!(wrap("one two three four", 10, "  "), "  one two\n  three\n  four\n");
+        assert_eq!(wrap("", 10, "  "), "");
+    }
 }
@@ -0,0 +1,182 @@
+use std::error::Error;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// Somewhere text can be copied to and pasted from.
+pub trait Clipboard {
+    fn paste(&self) -> Result<String, Box<dyn Error>>;
+    fn copy(&self, text: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// A program (and its arguments) that pastes the clipboard to its stdout, or copies its stdin to the clipboard.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClipboardCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl ClipboardCommand {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        ClipboardCommand {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+
+    fn spawn_error(&self, e: std::io::Error) -> Box<dyn Error> {
+        if e.kind() == ErrorKind::NotFound {
+            format!("{} was not found, it needs to be installed to use the clipboard", self.program).into()
+        } else {
+            format!("Unable to run {}: {}", self.program, e).into()
+        }
+    }
+}
+
+/// The desktop's clipboard, used through the command line helpers of the display server:
+/// `wl-paste`/`wl-copy` (from wl-clipboard) on Wayland and `xclip` on X11.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandClipboard {
+    pub paste: ClipboardCommand,
+    pub copy: ClipboardCommand,
+}
+
+impl CommandClipboard {
+    /// Picks the helpers for the display server the program is running under.
+    pub fn detect() -> Result<Self, Box<dyn Error>> {
+        let wayland_display = std::env::var("WAYLAND_DISPLAY").ok();
+        let x_display = std::env::var("DISPLAY").ok();
+        Self::for_display(wayland_display.as_deref(), x_display.as_deref())
+    }
+
+    /// Picks the helpers given the values of `WAYLAND_DISPLAY` and `DISPLAY`. Wayland is preferred,
+    /// as X11 apps running under it through XWayland also have `DISPLAY` set.
+    pub fn for_display(wayland_display: Option<&str>, x_display: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        match (wayland_display, x_display) {
+            (Some(display), _) if !display.is_empty() => Ok(CommandClipboard {
+                paste: ClipboardCommand::new("wl-paste", &["--no-newline"]),
+                copy: ClipboardCommand::new("wl-copy", &[]),
+            }),
+            (_, Some(display)) if !display.is_empty() => Ok(CommandClipboard {
+                paste: ClipboardCommand::new("xclip", &["-selection", "clipboard", "-out"]),
+                copy: ClipboardCommand::new("xclip", &["-selection", "clipboard", "-in"]),
+            }),
+            _ => Err("No clipboard is available, as neither a Wayland nor an X11 display was found")?,
+        }
+    }
+}
+
+impl Clipboard for CommandClipboard {
+    fn paste(&self) -> Result<String, Box<dyn Error>> {
+        let output = self.paste.command()
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| self.paste.spawn_error(e))?;
+        if !output.status.success() {
+            Err(format!("{} failed to read the clipboard ({})", self.paste.program, output.status))?
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn copy(&self, text: &str) -> Result<(), Box<dyn Error>> {
+        // the helpers stay running to serve the clipboard, so only their stdin is waited on
+        // and their stdout is let go rather than being read until they exit
+        let mut child = self.copy.command()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| self.copy.spawn_error(e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            Err(format!("{} failed to write to the clipboard ({})", self.copy.program, status))?
+        }
+        Ok(())
+    }
+}
+
+/// The clipboard's contents, to be posted as a note. An empty clipboard is an error rather than an empty note.
+pub fn note_from_clipboard<C: Clipboard>(clipboard: &C) -> Result<String, Box<dyn Error>> {
+    let content = clipboard.paste()?;
+    if content.trim().is_empty() {
+        Err("The clipboard is empty, there's nothing to post")?
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct FakeClipboard {
+        contents: RefCell<String>,
+    }
+
+    impl Clipboard for FakeClipboard {
+        fn paste(&self) -> Result<String, Box<dyn Error>> {
+            Ok(self.contents.borrow().clone())
+        }
+
+        fn copy(&self, text: &str) -> Result<(), Box<dyn Error>> {
+            *self.contents.borrow_mut() = text.to_string();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_post_clipboard_contents() {
+        let clipboard = FakeClipboard::default();
+        clipboard.copy("copied text\n").unwrap();
+
+        assert_eq!(note_from_clipboard(&clipboard).unwrap(), "copied text\n");
+    }
+
+    #[test]
+    fn should_refuse_to_post_an_empty_clipboard() {
+        let clipboard = FakeClipboard::default();
+        clipboard.copy(" \n").unwrap();
+
+        assert!(note_from_clipboard(&clipboard).is_err());
+    }
+
+    #[test]
+    fn should_pick_helpers_for_display_server() {
+        let wayland = CommandClipboard::for_display(Some("wayland-0"), Some(":0")).unwrap();
+        assert_eq!(wayland.paste.program, "wl-paste");
+        assert_eq!(wayland.copy.program, "wl-copy");
+
+        let x11 = CommandClipboard::for_display(Some(""), Some(":0")).unwrap();
+        assert_eq!(x11.paste, ClipboardCommand::new("xclip", &["-selection", "clipboard", "-out"]));
+
+        assert!(CommandClipboard::for_display(None, None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_run_clipboard_helpers() {
+        let clipboard = CommandClipboard {
+            paste: ClipboardCommand::new("echo", &["-n", "pasted"]),
+            copy: ClipboardCommand::new("sh", &["-c", "cat > /dev/null"]),
+        };
+        assert_eq!(clipboard.paste().unwrap(), "pasted");
+        clipboard.copy("copied").unwrap();
+
+        let missing = CommandClipboard {
+            paste: ClipboardCommand::new("nxcloudnotes-missing-helper", &[]),
+            copy: ClipboardCommand::new("false", &[]),
+        };
+        assert!(missing.paste().unwrap_err().to_string().contains("was not found"));
+        assert!(missing.copy("copied").is_err());
+    }
+}
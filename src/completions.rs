@@ -0,0 +1,378 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use crate::cliarguments::{CommandSpec, Completion, FlagSpec, COMMANDS};
+
+/// Shells that completion scripts can be generated for.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("Unknown shell {}, expected bash, zsh or fish", value)),
+        }
+    }
+}
+
+impl Display for Shell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const PROGRAM: &str = "nxcloudnotes";
+
+/// The completion script for the given shell, generated from the command and flag tables.
+pub fn completion_script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash_script(),
+        Shell::Zsh => zsh_script(),
+        Shell::Fish => fish_script(),
+    }
+}
+
+fn named_commands() -> impl Iterator<Item = &'static CommandSpec> {
+    COMMANDS.iter().filter(|command| !command.name.is_empty())
+}
+
+fn command_names() -> Vec<&'static str> {
+    named_commands().map(|command| command.name).collect()
+}
+
+/// The command that outputs the note titles, one per line.
+fn note_titles_source() -> String {
+    format!("{} list --names 2>/dev/null", PROGRAM)
+}
+
+fn bash_completion(completion: Completion, word: &str) -> String {
+    match completion {
+        Completion::Nothing => String::from("COMPREPLY=()"),
+        Completion::Files => format!("COMPREPLY=( $(compgen -f -- \"{}\") )", word),
+        Completion::Choices(choices) => format!("COMPREPLY=( $(compgen -W \"{}\" -- \"{}\") )", choices.join(" "), word),
+        Completion::Commands => format!("COMPREPLY=( $(compgen -W \"{}\" -- \"{}\") )", command_names().join(" "), word),
+        Completion::NoteTitles => format!(
+            "local IFS=$'\\n'; COMPREPLY=( $(compgen -W \"$({})\" -- \"{}\") )",
+            note_titles_source(),
+            word
+        ),
+    }
+}
+
+/// Every way of giving a flag that takes its value from the next word, ie `-t` and `--title`.
+fn value_flag_forms() -> Vec<String> {
+    let mut forms = Vec::new();
+    for flag in crate::cliarguments::FLAGS.iter().filter(|flag| flag.value_name.is_some()) {
+        forms.extend(flag.short.map(|short| format!("-{}", short)));
+        forms.push(format!("--{}", flag.long));
+    }
+    forms
+}
+
+fn bash_script() -> String {
+    let command_names = command_names();
+    let mut script = format!(
+        "# bash completion for {program}
+_{program}() {{
+    # the word being completed, taken from the line so flags aren't split at their =
+    local line=\"${{COMP_LINE:0:COMP_POINT}}\"
+    local cur=\"${{line##* }}\"
+    local command=\"\" arguments=0 flag_value=\"\" word
+    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do
+        if [[ -n \"$flag_value\" ]]; then
+            flag_value=\"\"
+            continue
+        fi
+        case \"$word\" in
+            # flags whose value is the next word
+            {value_flags}) flag_value=1 ;;
+            -*) ;;
+            # only the first plain word can be a command, later ones are its arguments
+            {commands}) [[ -z \"$command\" && $arguments == 0 ]] && command=\"$word\" || arguments=$((arguments + 1)) ;;
+            *) arguments=$((arguments + 1)) ;;
+        esac
+    done
+
+    if [[ \"$cur\" == --*=* ]]; then
+        local flag=\"${{cur%%=*}}\"
+        case \"$flag\" in
+",
+        program = PROGRAM,
+        commands = command_names.join("|"),
+        value_flags = value_flag_forms().join("|"),
+    );
+    let mut flags_with_values: Vec<&FlagSpec> = Vec::new();
+    for command in COMMANDS {
+        for flag in command.visible_flags() {
+            if flag.value_name.is_some() && !flags_with_values.iter().any(|known| known.long == flag.long) {
+                flags_with_values.push(flag);
+            }
+        }
+    }
+    for flag in flags_with_values {
+        let completion = bash_completion(flag.completion, "${cur#*=}");
+        script.push_str(&format!("            --{}) {} ;;\n", flag.long, completion));
+    }
+    script.push_str(
+        "        esac
+        # bash only replaces the part after the = if = splits words
+        if [[ \"$COMP_WORDBREAKS\" != *=* ]]; then
+            COMPREPLY=( \"${COMPREPLY[@]/#/$flag=}\" )
+        fi
+        return
+    fi
+
+    local flags
+    case \"$command\" in
+",
+    );
+    for command in COMMANDS {
+        let flags: Vec<String> = command
+            .visible_flags()
+            .map(|flag| if flag.value_name.is_some() { format!("--{}=", flag.long) } else { format!("--{}", flag.long) })
+            .collect();
+        let pattern = if command.name.is_empty() { "\"\"" } else { command.name };
+        script.push_str(&format!("        {}) flags=\"{}\" ;;\n", pattern, flags.join(" ")));
+    }
+    script.push_str(
+        "    esac
+
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=( $(compgen -W \"$flags\" -- \"$cur\") )
+        [[ \"${COMPREPLY[0]}\" == *= ]] && compopt -o nospace
+        return
+    fi
+
+    # completes the argument at the position the word is in, after the arguments already given
+    case \"$command $arguments\" in
+",
+    );
+    script.push_str(&format!(
+        "        \" 0\") COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") ) ;;\n",
+        command_names.join(" ")
+    ));
+    for command in named_commands() {
+        for (position, argument) in command.arguments.iter().enumerate() {
+            let completion = bash_completion(argument.completion, "$cur");
+            script.push_str(&format!("        \"{} {}\") {} ;;\n", command.name, position, completion));
+        }
+    }
+    script.push_str(&format!("    esac\n}}\ncomplete -F _{program} {program}\n", program = PROGRAM));
+    script
+}
+
+/// Escapes text for use inside a single quoted zsh `_arguments` spec, where brackets and colons are special.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_action(completion: Completion) -> String {
+    match completion {
+        Completion::Nothing => String::from(" "),
+        Completion::Files => String::from("_files"),
+        Completion::Choices(choices) => format!("({})", choices.join(" ")),
+        Completion::Commands => format!("({})", command_names().join(" ")),
+        Completion::NoteTitles => format!("_{}_notes", PROGRAM),
+    }
+}
+
+fn zsh_flag_specs(command: &CommandSpec) -> Vec<String> {
+    let mut specs = Vec::new();
+    for flag in command.visible_flags() {
+        let repeat = if flag.repeatable { "*" } else { "" };
+        let help = zsh_escape(flag.help);
+        let forms = flag.short.map(|short| format!("-{}", short)).into_iter().chain(Some(format!("--{}", flag.long)));
+        for form in forms {
+            let spec = match flag.value_name {
+                Some(value_name) => {
+                    let separator = if form.starts_with("--") { "=" } else { "+" };
+                    format!("'{}{}{}[{}]:{}:{}'", repeat, form, separator, help, zsh_escape(value_name), zsh_action(flag.completion))
+                },
+                None => format!("'{}{}[{}]'", repeat, form, help),
+            };
+            specs.push(spec);
+        }
+    }
+    specs
+}
+
+fn zsh_script() -> String {
+    let mut script = format!(
+        "#compdef {program}
+
+_{program}_notes() {{
+    local -a notes
+    notes=(\"${{(@f)$({notes})}}\")
+    compadd -a notes
+}}
+
+_{program}() {{
+    local curcontext=\"$curcontext\" state line
+    local -a commands
+    commands=(
+",
+        program = PROGRAM,
+        notes = note_titles_source(),
+    );
+    for command in named_commands() {
+        script.push_str(&format!("        '{}:{}'\n", command.name, zsh_escape(command.help)));
+    }
+    script.push_str("    )\n\n    _arguments -C \\\n");
+    for spec in zsh_flag_specs(&COMMANDS[0]) {
+        script.push_str(&format!("        {} \\\n", spec));
+    }
+    script.push_str(
+        "        '1: :->command' \\
+        '*:: :->args'
+
+    case $state in
+        command)
+            _describe 'command' commands
+            ;;
+        args)
+            case $line[1] in
+",
+    );
+    for command in named_commands() {
+        let mut specs = zsh_flag_specs(command);
+        for (position, argument) in command.arguments.iter().enumerate() {
+            let name = zsh_escape(&argument.name.to_lowercase());
+            specs.push(format!("'{}:{}:{}'", position + 1, name, zsh_action(argument.completion)));
+        }
+        if specs.is_empty() {
+            script.push_str(&format!("                {}) ;;\n", command.name));
+        } else {
+            script.push_str(&format!("                {}) _arguments {} ;;\n", command.name, specs.join(" ")));
+        }
+    }
+    script.push_str(&format!(
+        "            esac
+            ;;
+    esac
+}}
+
+_{program} \"$@\"
+",
+        program = PROGRAM
+    ));
+    script
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish_arguments(completion: Completion) -> String {
+    match completion {
+        Completion::Nothing => String::new(),
+        Completion::Files => String::from(" -F"),
+        Completion::Choices(choices) => format!(" -a '{}'", choices.join(" ")),
+        Completion::Commands => format!(" -a '{}'", command_names().join(" ")),
+        Completion::NoteTitles => format!(" -a '({})'", note_titles_source()),
+    }
+}
+
+fn fish_script() -> String {
+    let command_names = command_names();
+    let no_command = format!("not __fish_seen_subcommand_from {}", command_names.join(" "));
+    let mut script = format!("# fish completion for {program}\ncomplete -c {program} -f\n", program = PROGRAM);
+    for command in named_commands() {
+        script.push_str(&format!(
+            "complete -c {} -n '{}' -a {} -d '{}'\n",
+            PROGRAM,
+            no_command,
+            command.name,
+            fish_escape(command.help)
+        ));
+    }
+    for command in COMMANDS {
+        let condition = if command.name.is_empty() {
+            no_command.clone()
+        } else {
+            format!("__fish_seen_subcommand_from {}", command.name)
+        };
+        for flag in command.visible_flags() {
+            let short = flag.short.map_or(String::new(), |short| format!(" -s {}", short));
+            let value = if flag.value_name.is_some() { format!(" -r{}", fish_arguments(flag.completion)) } else { String::new() };
+            script.push_str(&format!(
+                "complete -c {} -n '{}'{} -l {}{} -d '{}'\n",
+                PROGRAM,
+                condition,
+                short,
+                flag.long,
+                value,
+                fish_escape(flag.help)
+            ));
+        }
+        // fish can't tell which argument is being completed, so only the first one is
+        if let Some(argument) = command.arguments.first().filter(|_| !command.name.is_empty()) {
+            let arguments = fish_arguments(argument.completion);
+            if !arguments.is_empty() {
+                script.push_str(&format!("complete -c {} -n '{}'{}\n", PROGRAM, condition, arguments));
+            }
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_shell_names() {
+        assert_eq!("Bash".parse(), Ok(Shell::Bash));
+        assert_eq!("fish".parse(), Ok(Shell::Fish));
+        assert!("powershell".parse::<Shell>().is_err());
+    }
+
+    #[test]
+    fn should_complete_commands_flags_and_note_titles() {
+        let bash = completion_script(Shell::Bash);
+        assert!(bash.contains("help|config-path|categories|list|read|versions|restore|trash|browse|completions|man)"));
+        assert!(bash.contains("read) flags=\"--password= --password-stdin --category= --no-meta --copy --verbose --debug --trace-file= --help\""));
+        assert!(bash.contains("nxcloudnotes list --names"));
+        // arguments are completed by their position, so a version isn't completed with note titles
+        assert!(bash.contains("\"restore 0\") local IFS=$'\\n'; COMPREPLY=( $(compgen -W \"$(nxcloudnotes list --names"));
+        assert!(bash.contains("\"restore 1\") COMPREPLY=() ;;"));
+        // hidden flags aren't offered
+        assert!(!bash.contains("--names\""));
+
+        let zsh = completion_script(Shell::Zsh);
+        assert!(zsh.contains("'*--tag=[Tag recorded in the note'\\''s front matter"));
+        assert!(zsh.contains("'1:notes title:_nxcloudnotes_notes'"));
+        assert!(zsh.contains("restore) _arguments"));
+        assert!(zsh.contains("'1:notes title:_nxcloudnotes_notes' '2:version: '"));
+
+        let fish = completion_script(Shell::Fish);
+        assert!(fish.contains("complete -c nxcloudnotes -n '__fish_seen_subcommand_from read' -a '(nxcloudnotes list --names 2>/dev/null)'"));
+        assert!(fish.contains("-s o -l collision -r -a 'overwrite fail suffix append'"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_generate_valid_bash() {
+        let script = completion_script(Shell::Bash);
+        let status = std::process::Command::new("bash").arg("-n").arg("-c").arg(&script).status();
+        // shells other than bash can't check the syntax, so this only runs where bash is installed
+        if let Ok(status) = status {
+            assert!(status.success());
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read};
+use flate2::bufread::{MultiGzDecoder, ZlibDecoder};
+use flate2::read::GzEncoder;
+use flate2::Compression;
+
+/// The `Accept-Encoding` requests are sent with, for the content codings `decode_body` understands.
+pub const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// Decodes a response body sent with a `Content-Encoding` of gzip or deflate. The encoding and length
+/// headers are removed, as they describe the compressed body rather than what's read from the returned
+/// one. Bodies with any other encoding are returned as they are, with their headers left alone.
+pub fn decode_body<R: Read + Send + 'static>(headers: &mut HashMap<String, String>, body: R) -> Box<dyn Read + Send> {
+    let encoding = headers.get("content-encoding").map(|encoding| encoding.trim().to_lowercase());
+    let decoder = match encoding.as_deref() {
+        Some("gzip") | Some("x-gzip") => Decoder::Gzip(MultiGzDecoder::new(BufReader::new(body))),
+        Some("deflate") => Decoder::Zlib(ZlibDecoder::new(BufReader::new(body))),
+        _ => return Box::new(body),
+    };
+    headers.remove("content-encoding");
+    headers.remove("content-length");
+    // Apache marks the etag of a compressed response with a suffix, which it then doesn't recognise
+    // in an If-Match, so the etag is restored to the one of the file itself
+    if let Some(etag) = headers.get_mut("etag") {
+        if let Some(stripped) = etag.strip_suffix("-gzip\"") {
+            *etag = format!("{}\"", stripped);
+        }
+    }
+    Box::new(DecodedBody { decoder, finished: false })
+}
+
+/// Compresses a request body with gzip, to be sent with `Content-Encoding: gzip`.
+pub fn gzip<R: Read>(body: R) -> GzEncoder<R> {
+    GzEncoder::new(body, Compression::default())
+}
+
+/// Whether a response's `Accept-Encoding` header says the server takes gzipped request bodies (RFC 7694).
+pub fn accepts_gzip(headers: &HashMap<String, String>) -> bool {
+    headers.get("accept-encoding").is_some_and(|encodings| {
+        encodings.split(',').any(|encoding| {
+            let mut parts = encoding.split(';');
+            let name = parts.next().unwrap_or("").trim();
+            let refused = parts.any(|parameter| matches!(parameter.replace(' ', "").as_str(), "q=0" | "q=0.0" | "q=0.00" | "q=0.000"));
+            name.eq_ignore_ascii_case("gzip") && !refused
+        })
+    })
+}
+
+enum Decoder<R> {
+    Gzip(MultiGzDecoder<BufReader<R>>),
+    Zlib(ZlibDecoder<BufReader<R>>),
+}
+
+/// Reads the decompressed body. Once the compressed data ends, whatever is left of the body underneath
+/// is read too, so a pooled connection the body came over is finished with and goes back to the pool.
+struct DecodedBody<R> {
+    decoder: Decoder<R>,
+    finished: bool,
+}
+
+impl<R: Read> Read for DecodedBody<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.finished {
+            return Ok(0);
+        }
+        let read = match &mut self.decoder {
+            Decoder::Gzip(decoder) => decoder.read(buf)?,
+            Decoder::Zlib(decoder) => decoder.read(buf)?,
+        };
+        if read == 0 && !buf.is_empty() {
+            let rest = match &mut self.decoder {
+                Decoder::Gzip(decoder) => decoder.get_mut(),
+                Decoder::Zlib(decoder) => decoder.get_mut(),
+            };
+            io::copy(rest, &mut io::sink())?;
+            self.finished = true;
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn should_decode_gzip_and_deflate_bodies() {
+        let mut gzipped = Vec::new();
+        gzip(&b"note content"[..]).read_to_end(&mut gzipped).unwrap();
+        let mut deflated = Vec::new();
+        flate2::read::ZlibEncoder::new(&b"note content"[..], Compression::default()).read_to_end(&mut deflated).unwrap();
+
+        for (encoding, body) in [("gzip", gzipped), ("deflate", deflated)] {
+            let mut headers = headers(&[("content-encoding", encoding), ("content-length", "30"), ("etag", "\"5f3c-gzip\"")]);
+            let mut decoded = String::new();
+            decode_body(&mut headers, Cursor::new(body)).read_to_string(&mut decoded).unwrap();
+
+            assert_eq!(decoded, "note content");
+            assert_eq!(headers, self::headers(&[("etag", "\"5f3c\"")]));
+        }
+    }
+
+    #[test]
+    fn should_leave_bodies_with_other_encodings_alone() {
+        let mut headers = headers(&[("content-encoding", "br"), ("content-length", "3")]);
+        let mut body = Vec::new();
+        decode_body(&mut headers, Cursor::new(b"abc".to_vec())).read_to_end(&mut body).unwrap();
+
+        assert_eq!(body, b"abc");
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    fn should_read_accepted_request_encodings() {
+        assert!(accepts_gzip(&headers(&[("accept-encoding", "deflate, GZIP;q=0.5")])));
+        assert!(!accepts_gzip(&headers(&[("accept-encoding", "gzip;q=0, deflate")])));
+        assert!(!accepts_gzip(&headers(&[("accept-encoding", "identity")])));
+        assert!(!accepts_gzip(&HashMap::new()));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::configprovider::NxCloudNotesConfigData;
-use crate::utils::get_user_input;
+use crate::utils::{get_password_input, get_user_input};
 use std::io::{BufRead, Write};
 
 /// Routine for prompting the user for multiple lines of input required for nxcloudnotes configuration in the form
@@ -22,9 +22,8 @@ where
         let port = get_user_input(reader, writer, "Enter port number: ", true)
         .expect("Error getting port number input");
         let port_int = port.parse::<u32>();
-        match port_int {
-            Ok(port_as_int) => break port_as_int,
-            Err(_) => {}
+        if let Ok(port_as_int) = port_int {
+            break port_as_int;
         }
     };
 
@@ -39,7 +38,12 @@ where
     let user_name = get_user_input(reader, writer, "Enter your username:", true)
         .expect("Error getting username input");
 
-    let password = get_user_input(reader, writer, "Enter your password:", false)
+    let password = get_password_input(
+        reader,
+        writer,
+        "Enter your password, or leave it empty to be asked for it when it's needed (it isn't shown as it's typed):",
+        false,
+    )
         .expect("Error getting password input");
 
     let password = if password.is_empty() {
@@ -54,6 +58,19 @@ where
         base_notes_directory,
         user_name,
         password,
+        chunked_upload_threshold: None,
+        note_extension: None,
+        prepend_title_heading: None,
+        title_template: None,
+        collision_policy: None,
+        default_category: None,
+        front_matter: None,
+        front_matter_hostname: None,
+        front_matter_cwd: None,
+        upload_compression_threshold: None,
+        parallelism: None,
+        proxy: None,
+        resolve: None,
     }
 }
 
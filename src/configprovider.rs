@@ -5,6 +5,10 @@ use std::error::Error;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::Write;
+use crate::nextcloudclient::CollisionPolicy;
+
+/// Environment variable the password can be given in when it isn't saved in the config file.
+pub const PASSWORD_ENV_VAR: &str = "NXCLOUDNOTES_PASSWORD";
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NxCloudNotesConfigData {
@@ -13,6 +17,40 @@ pub struct NxCloudNotesConfigData {
     pub base_notes_directory: String,
     pub user_name: String,
     pub password: Option<String>,
+    /// Files larger than this many bytes are uploaded in chunks.
+    /// Defaults to `nextcloudclient::DEFAULT_CHUNKED_UPLOAD_THRESHOLD` if not set.
+    pub chunked_upload_threshold: Option<u64>,
+    /// File extension notes are saved with, ie `md`. Defaults to `txt` if not set.
+    pub note_extension: Option<String>,
+    /// Whether to start each note with its title as a Markdown `# heading`, which the Notes app shows as the title.
+    pub prepend_title_heading: Option<bool>,
+    /// Template for the title of notes posted without one, see `notes::render_title_template`.
+    /// Defaults to the first line of the note if not set.
+    pub title_template: Option<String>,
+    /// What to do when a note with the same title already exists. Defaults to overwriting it if not set.
+    pub collision_policy: Option<CollisionPolicy>,
+    /// Category (subfolder of the notes directory) notes are posted into when none is given.
+    pub default_category: Option<String>,
+    /// Whether posted notes start with front matter recording their tags and where they came from. Off by default.
+    pub front_matter: Option<bool>,
+    /// Whether the front matter records the name of the machine the note was posted from. Off by default.
+    pub front_matter_hostname: Option<bool>,
+    /// Whether the front matter records the directory the note was posted from. Off by default.
+    pub front_matter_cwd: Option<bool>,
+    /// PUT bodies of at least this many bytes are gzipped for servers that say they accept gzipped request
+    /// bodies, see `LiteHttpClient::with_upload_compression`. Uploads aren't compressed if not set.
+    pub upload_compression_threshold: Option<u64>,
+    /// How many notes or attachments are downloaded or uploaded at once, when built with the `async` feature.
+    /// Defaults to `asyncnextcloudclient::DEFAULT_PARALLELISM` if not set.
+    pub parallelism: Option<usize>,
+    /// Proxy to connect to the server through, ie `http://proxy.example.com:3128` or `socks5://localhost:1080`,
+    /// see `proxy::Proxy`. Taken from the `HTTPS_PROXY` environment variable if not set, and an empty one
+    /// means connecting directly.
+    pub proxy: Option<String>,
+    /// Addresses to connect to instead of looking up the server's, as `host:port:address` entries like
+    /// curl's `--resolve`, ie `["cloud.example.com:443:10.0.0.5"]` on a network where the public address
+    /// doesn't answer. See `resolver::ResolveOverride`.
+    pub resolve: Option<Vec<String>>,
 }
 
 pub trait NxCloudConfigRetriever {
@@ -32,7 +70,7 @@ pub struct FileSystemNxCloudConfig<'a> {
 
 impl<'a> FileSystemNxCloudConfig<'a> {
     pub fn new(config_directory: &'a Path, config_name: &'a str) -> Self {
-        return Self {
+        Self {
             config_directory,
             config_name,
             config_path: config_directory.join(config_name)
@@ -58,7 +96,7 @@ impl<'a> NxCloudConfigRetriever for FileSystemNxCloudConfig<'a> {
             fs::create_dir(config_dir)?;
         }
         let toml = toml::to_string(&config)?;
-        let config_path = config_dir.join(&self.config_name);
+        let config_path = config_dir.join(self.config_name);
         if config_path.exists() {
             fs::remove_file(&config_path)?;
         }
@@ -0,0 +1,270 @@
+//! Checks every `HttpRequest` implementation has to pass against a real server, so the backends
+//! can be swapped without NextCloudClient noticing. `conformance_tests!` runs them for a backend.
+use std::io::{Cursor, Read};
+use crate::compression;
+use crate::httprequest::{HttpRequest, RequestType};
+use crate::testserver::{Reply, TestServer};
+
+pub(crate) fn should_send_method_path_headers_and_body<T: HttpRequest>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|_| Reply::new(201, b""));
+
+    let response = make_client(&server)
+        .set_request(RequestType::PUT, "/remote.php/dav/files/user/Notes/a%20b.md")
+        .set_header("Content-Type".to_string(), "text/markdown".to_string())
+        .basic_auth("user", "password")
+        .send_bytes(b"# Title")
+        .unwrap();
+
+    assert_eq!(response.response_code, 201);
+    let request = &server.received()[0];
+    assert_eq!(request.method, "PUT");
+    assert_eq!(request.path, "/remote.php/dav/files/user/Notes/a%20b.md");
+    assert_eq!(request.header("content-type"), Some("text/markdown"));
+    assert_eq!(request.header("authorization"), Some("Basic dXNlcjpwYXNzd29yZA=="));
+    assert_eq!(request.body, b"# Title");
+}
+
+pub(crate) fn should_return_error_codes_as_responses<T: HttpRequest>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|_| Reply::new(404, b"not here").with_header("X-Reason", "missing"));
+
+    let response = make_client(&server).set_request(RequestType::GET, "/missing").send_bytes(&[]).unwrap();
+
+    assert_eq!(response.response_code, 404);
+    assert_eq!(response.response_msg, "not here");
+    assert_eq!(response.header("X-Reason"), Some("missing"));
+}
+
+pub(crate) fn should_read_chunked_responses<T: HttpRequest>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|_| Reply::new(200, b"a body sent in a few chunks").chunked());
+
+    let response = make_client(&server).set_request(RequestType::GET, "/").send_bytes(&[]).unwrap();
+
+    assert_eq!(response.response_msg, "a body sent in a few chunks");
+}
+
+pub(crate) fn should_stream_upload_with_known_length<T: HttpRequest>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|_| Reply::new(201, b""));
+    let body = vec![7; 100_000];
+
+    let response = make_client(&server)
+        .set_request(RequestType::PUT, "/large")
+        .send_stream(Cursor::new(body.clone()), Some(body.len() as u64))
+        .unwrap();
+
+    assert_eq!(response.response_code, 201);
+    let request = &server.received()[0];
+    assert_eq!(request.header("content-length"), Some("100000"));
+    assert_eq!(request.body, body);
+}
+
+pub(crate) fn should_stream_upload_of_unknown_length_in_chunks<T: HttpRequest>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|_| Reply::new(201, b""));
+
+    let response = make_client(&server)
+        .set_request(RequestType::PUT, "/piped")
+        .send_stream(Cursor::new(b"from a pipe".to_vec()), None)
+        .unwrap();
+
+    assert_eq!(response.response_code, 201);
+    let request = &server.received()[0];
+    assert_eq!(request.header("transfer-encoding"), Some("chunked"));
+    assert_eq!(request.body, b"from a pipe");
+}
+
+pub(crate) fn should_stream_binary_responses<T: HttpRequest>(make_client: fn(&TestServer) -> T) {
+    let content: Vec<u8> = (0..=255).cycle().take(70_000).collect();
+    let reply_content = content.clone();
+    let server = TestServer::start(move |_| Reply::new(200, &reply_content));
+
+    let mut response = make_client(&server).set_request(RequestType::GET, "/image.png").send_stream(std::io::empty(), Some(0)).unwrap();
+    let mut body = Vec::new();
+    response.body.read_to_end(&mut body).unwrap();
+
+    assert_eq!(response.response_code, 200);
+    assert_eq!(body, content);
+}
+
+pub(crate) fn should_reuse_connections<T: HttpRequest + Clone>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|request| Reply::new(200, request.path.as_bytes()));
+    let client = make_client(&server);
+
+    for path in ["/first", "/second", "/third"] {
+        let response = client.clone().set_request(RequestType::GET, path).send_bytes(&[]).unwrap();
+        assert_eq!(response.response_msg, path);
+    }
+
+    assert_eq!(server.connections(), 1);
+}
+
+pub(crate) fn should_handle_responses_without_body<T: HttpRequest + Clone>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|_| Reply::new(204, b""));
+    let client = make_client(&server);
+
+    for _ in 0..2 {
+        let response = client.clone().set_request(RequestType::DELETE, "/gone.md").send_bytes(&[]).unwrap();
+        assert_eq!(response.response_code, 204);
+        assert_eq!(response.response_msg, "");
+    }
+}
+
+pub(crate) fn should_send_webdav_methods<T: HttpRequest + Clone>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|request| match request.method.as_str() {
+        "PROPFIND" => Reply::new(207, b"<d:multistatus/>"),
+        _ => Reply::new(201, b""),
+    });
+    let client = make_client(&server);
+
+    client.clone().set_request(RequestType::MKCOL, "/folder").send_bytes(&[]).unwrap();
+    let listing = client.clone()
+        .set_request(RequestType::PROPFIND, "/folder")
+        .set_header("Depth".to_string(), "1".to_string())
+        .send_bytes(b"<d:propfind/>")
+        .unwrap();
+    client.clone()
+        .set_request(RequestType::MOVE, "/folder/a.md")
+        .set_header("Destination".to_string(), "/folder/b.md".to_string())
+        .send_bytes(&[])
+        .unwrap();
+
+    assert_eq!(listing.response_code, 207);
+    assert_eq!(listing.response_msg, "<d:multistatus/>");
+    let received = server.received();
+    let methods: Vec<&str> = received.iter().map(|request| request.method.as_str()).collect();
+    assert_eq!(methods, vec!["MKCOL", "PROPFIND", "MOVE"]);
+    assert_eq!(received[1].header("depth"), Some("1"));
+    assert_eq!(received[1].body, b"<d:propfind/>");
+    assert_eq!(received[2].header("destination"), Some("/folder/b.md"));
+}
+
+pub(crate) fn should_follow_redirects_without_sending_credentials_to_other_hosts<T: HttpRequest + Clone>(make_client: fn(&TestServer) -> T) {
+    let target = TestServer::start(|_| Reply::new(200, b"moved here"));
+    let location = format!("https://127.0.0.1:{}/b.md", target.port());
+    let server = TestServer::start(move |request| match request.path.as_str() {
+        "/old.md" => Reply::new(301, b"").with_header("Location", "/a.md"),
+        "/a.md" => Reply::new(201, b""),
+        _ => Reply::new(307, b"").with_header("Location", &location),
+    });
+    let client = make_client(&server);
+
+    let moved = client.clone().set_request(RequestType::PUT, "/old.md").basic_auth("user", "password").send_bytes(b"content").unwrap();
+    let elsewhere = client.clone().set_request(RequestType::GET, "/b.md").basic_auth("user", "password").send_bytes(&[]).unwrap();
+
+    assert_eq!(moved.response_code, 201);
+    let redirected = &server.received()[1];
+    assert_eq!((redirected.method.as_str(), redirected.path.as_str()), ("PUT", "/a.md"));
+    assert_eq!(redirected.body, b"content");
+    assert_eq!(redirected.header("authorization"), Some("Basic dXNlcjpwYXNzd29yZA=="));
+    assert_eq!(elsewhere.response_msg, "moved here");
+    let target_host = format!("127.0.0.1:{}", target.port());
+    assert_eq!(target.received()[0].header("host"), Some(target_host.as_str()));
+    assert_eq!(target.received()[0].header("authorization"), None);
+}
+
+pub(crate) fn should_only_replay_redirected_uploads_with_known_length<T: HttpRequest + Clone>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|request| match request.path.as_str() {
+        "/old.md" => Reply::new(308, b"").with_header("Location", "/new.md"),
+        _ => Reply::new(201, b""),
+    });
+    let client = make_client(&server);
+
+    let upload = client.clone().set_request(RequestType::PUT, "/old.md").send_stream(Cursor::new(b"new".to_vec()), Some(3)).unwrap();
+    let streamed = client.clone().set_request(RequestType::PUT, "/old.md").send_stream(Cursor::new(b"new".to_vec()), None);
+
+    assert_eq!(upload.response_code, 201);
+    assert_eq!(server.received()[1].path, "/new.md");
+    assert_eq!(server.received()[1].body, b"new");
+    assert_eq!(
+        streamed.err().unwrap().to_string(),
+        "The upload was redirected to /new.md, but a redirected upload can't be replayed as its body was streamed"
+    );
+}
+
+pub(crate) fn should_refuse_redirects_to_http_and_endless_redirects<T: HttpRequest + Clone>(make_client: fn(&TestServer) -> T) {
+    let server = TestServer::start(|request| match request.path.as_str() {
+        "/plain" => Reply::new(302, b"").with_header("Location", "http://localhost/a.md"),
+        _ => Reply::new(308, b"").with_header("Location", "/again"),
+    });
+    let client = make_client(&server);
+
+    let unencrypted = client.clone().set_request(RequestType::GET, "/plain").send_bytes(&[]).err().unwrap();
+    let endless = client.clone().set_request(RequestType::GET, "/").send_bytes(&[]).err().unwrap();
+
+    assert!(unencrypted.to_string().contains("the request would be sent unencrypted"));
+    assert_eq!(endless.to_string(), "Gave up after following 5 redirects, the last one to /again");
+}
+
+pub(crate) fn should_decode_compressed_responses<T: HttpRequest + Clone>(make_client: fn(&TestServer) -> T) {
+    let listing = b"<d:multistatus>a long listing</d:multistatus>".repeat(50);
+    let mut gzipped = Vec::new();
+    compression::gzip(&listing[..]).read_to_end(&mut gzipped).unwrap();
+    let server = TestServer::start(move |request| match request.header("accept-encoding") {
+        Some(encodings) if encodings.contains("gzip") && request.method == "PROPFIND" => {
+            Reply::new(207, &gzipped).with_header("Content-Encoding", "gzip")
+        },
+        Some(encodings) if encodings.contains("gzip") => Reply::new(200, &gzipped).with_header("Content-Encoding", "gzip").chunked(),
+        _ => Reply::new(406, b"send Accept-Encoding"),
+    });
+    let client = make_client(&server);
+
+    let response = client.clone().set_request(RequestType::PROPFIND, "/Notes").send_bytes(&[]).unwrap();
+    let mut download = client.clone().set_request(RequestType::GET, "/note.md").send_stream(std::io::empty(), Some(0)).unwrap();
+    let mut body = Vec::new();
+    download.body.read_to_end(&mut body).unwrap();
+
+    assert_eq!(response.response_code, 207);
+    assert_eq!(response.response_msg.as_bytes(), &listing[..]);
+    assert_eq!(response.header("content-encoding"), None);
+    assert_eq!(download.response_code, 200);
+    assert_eq!(body, listing);
+    assert_eq!(download.header("content-encoding"), None);
+}
+
+/// Runs every conformance check as its own test in module `$name`, with clients made by `$make_client`.
+macro_rules! conformance_tests {
+    ($name:ident, $make_client:expr) => {
+        mod $name {
+            use super::*;
+
+            conformance_tests!(@tests $make_client;
+                should_send_method_path_headers_and_body,
+                should_return_error_codes_as_responses,
+                should_read_chunked_responses,
+                should_stream_upload_with_known_length,
+                should_stream_upload_of_unknown_length_in_chunks,
+                should_stream_binary_responses,
+                should_reuse_connections,
+                should_handle_responses_without_body,
+                should_send_webdav_methods,
+                should_follow_redirects_without_sending_credentials_to_other_hosts,
+                should_only_replay_redirected_uploads_with_known_length,
+                should_refuse_redirects_to_http_and_endless_redirects,
+                should_decode_compressed_responses
+            );
+        }
+    };
+    (@tests $make_client:expr; $($check:ident),*) => {
+        $(
+            #[test]
+            fn $check() {
+                super::super::$check($make_client);
+            }
+        )*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::httprequest::LiteHttpClient;
+    use crate::testserver::{TestServer, HOST};
+
+    conformance_tests!(lite_http_client, |server: &TestServer| {
+        LiteHttpClient::new(HOST.to_string(), server.port()).with_ssl_connector(server.ssl_connector())
+    });
+
+    #[cfg(feature = "backend-ureq")]
+    conformance_tests!(ureq_http_client, |server: &TestServer| {
+        let certificate = native_tls::Certificate::from_der(&server.certificate_der()).unwrap();
+        let connector = native_tls::TlsConnector::builder().add_root_certificate(certificate).build().unwrap();
+        crate::ureqclient::UreqHttpClient::new(HOST.to_string(), server.port()).with_tls_connector(connector)
+    });
+}
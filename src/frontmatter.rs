@@ -0,0 +1,306 @@
+use std::borrow::Cow;
+
+/// Metadata recorded at the top of a note as YAML front matter, between two `---` lines.
+/// Only the subset of YAML that's needed to write and read back these fields is supported:
+/// `key: value` pairs with plain or quoted strings, and lists of tags.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct FrontMatter {
+    pub tags: Vec<String>,
+    pub hostname: Option<String>,
+    /// Directory the note was posted from.
+    pub cwd: Option<String>,
+    /// Command that produced the note's content, when it was piped in.
+    pub command: Option<String>,
+    /// When the note was created, as an ISO 8601 timestamp.
+    pub created: Option<String>,
+}
+
+const DELIMITER: &str = "---";
+
+impl FrontMatter {
+    /// Renders the front matter block, including the `---` lines around it.
+    pub fn to_yaml(&self) -> String {
+        let mut yaml = format!("{}\n", DELIMITER);
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| quote_if_needed(tag)).collect();
+            yaml.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+        }
+        let fields = [
+            ("hostname", &self.hostname),
+            ("cwd", &self.cwd),
+            ("command", &self.command),
+            ("created", &self.created),
+        ];
+        for (key, value) in fields.iter() {
+            if let Some(value) = value {
+                yaml.push_str(&format!("{}: {}\n", key, quote_if_needed(value)));
+            }
+        }
+        yaml.push_str(DELIMITER);
+        yaml.push('\n');
+        yaml
+    }
+
+    /// Whether the note has every one of the given tags (ignoring case).
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|wanted| self.tags.iter().any(|tag| tag.eq_ignore_ascii_case(wanted)))
+    }
+
+    fn set(&mut self, key: &str, value: YamlValue) {
+        match (key, value) {
+            ("tags", YamlValue::List(tags)) => self.tags = tags,
+            ("tags", YamlValue::Text(tag)) => self.tags = vec![tag],
+            ("hostname", YamlValue::Text(value)) => self.hostname = Some(value),
+            ("cwd", YamlValue::Text(value)) => self.cwd = Some(value),
+            ("command", YamlValue::Text(value)) => self.command = Some(value),
+            ("created", YamlValue::Text(value)) => self.created = Some(value),
+            // anything else was added by someone else, and isn't ours to interpret
+            _ => {}
+        }
+    }
+}
+
+/// Puts the front matter block at the start of the note, or just after its `# Title` heading if it
+/// starts with one, as the Notes app takes the title from the first line.
+pub fn insert(front_matter: &FrontMatter, content: &str) -> String {
+    let (heading, body) = split_heading(content);
+    format!("{}{}{}", heading, front_matter.to_yaml(), body)
+}
+
+/// Splits a note into its front matter and the rest of its content, which keeps the note's heading.
+/// Notes without a front matter block (or with one that isn't closed) are returned unchanged with no front matter.
+pub fn split(content: &str) -> (Option<FrontMatter>, Cow<'_, str>) {
+    let (heading, rest) = split_heading(content);
+    match parse_block(rest) {
+        Some((front_matter, body)) if heading.is_empty() => (Some(front_matter), Cow::Borrowed(body)),
+        Some((front_matter, body)) => (Some(front_matter), Cow::Owned(format!("{}{}", heading, body))),
+        None => (None, Cow::Borrowed(content)),
+    }
+}
+
+/// Splits off the note's `# Title` heading along with the blank lines after it, if it starts with one.
+fn split_heading(content: &str) -> (&str, &str) {
+    if !content.starts_with("# ") {
+        return ("", content);
+    }
+    let mut heading_length = 0;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        if index > 0 && !line.trim().is_empty() || !line.ends_with('\n') {
+            break;
+        }
+        heading_length += line.len();
+    }
+    content.split_at(heading_length)
+}
+
+/// Parses the front matter block at the very start of the content, returning it with the content after it.
+fn parse_block(content: &str) -> Option<(FrontMatter, &str)> {
+    let mut lines = content.split_inclusive('\n');
+    match lines.next() {
+        Some(first_line) if first_line.trim_end() == DELIMITER => {},
+        _ => return None,
+    }
+
+    let mut front_matter = FrontMatter::default();
+    let mut offset = DELIMITER.len();
+    offset += content[offset..].find('\n').map_or(0, |index| index + 1);
+    let mut list_key: Option<(String, Vec<String>)> = None;
+    for line in lines {
+        offset += line.len();
+        let trimmed = line.trim_end();
+        if trimmed == DELIMITER {
+            if let Some((key, items)) = list_key.take() {
+                front_matter.set(&key, YamlValue::List(items));
+            }
+            return Some((front_matter, &content[offset..]));
+        }
+
+        // items of a block list, ie "  - work"
+        if let Some(item) = trimmed.trim_start().strip_prefix("- ") {
+            if let Some((_, items)) = list_key.as_mut() {
+                items.push(unquote(item.trim()));
+            }
+            continue;
+        }
+        if let Some((key, items)) = list_key.take() {
+            front_matter.set(&key, YamlValue::List(items));
+        }
+
+        if let Some((key, value)) = trimmed.split_once(':') {
+            let key = key.trim();
+            let value = value.trim();
+            if value.is_empty() {
+                list_key = Some((key.to_string(), vec![]));
+            } else {
+                front_matter.set(key, parse_value(value));
+            }
+        }
+    }
+    None
+}
+
+enum YamlValue {
+    Text(String),
+    List(Vec<String>),
+}
+
+fn parse_value(value: &str) -> YamlValue {
+    if let Some(items) = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+        let items = split_flow_list(items)
+            .iter()
+            .map(|item| unquote(item.trim()))
+            .filter(|item| !item.is_empty())
+            .collect();
+        YamlValue::List(items)
+    } else {
+        YamlValue::Text(unquote(value))
+    }
+}
+
+/// Splits the items of a flow list on commas, other than commas inside quotes.
+fn split_flow_list(items: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for c in items.chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+                current.push(c);
+            },
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.push(c);
+            },
+            None if c == ',' => split.push(std::mem::take(&mut current)),
+            None => current.push(c),
+        }
+    }
+    split.push(current);
+    split
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut unquoted = String::new();
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unquoted.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => unquoted.push('\n'),
+                Some('t') => unquoted.push('\t'),
+                Some(other) => unquoted.push(other),
+                None => unquoted.push('\\'),
+            }
+        }
+        unquoted
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].replace("''", "'")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Double quotes the value unless it's plain text that YAML would read back as the same string.
+fn quote_if_needed(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value.chars().all(|c| c.is_alphanumeric() || " -_./+@".contains(c))
+        && !value.starts_with(['-', ' '])
+        && !value.ends_with(' ')
+        && !["true", "false", "yes", "no", "null", "~"].contains(&value.to_lowercase().as_str());
+    if is_plain {
+        return value.to_string();
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn front_matter() -> FrontMatter {
+        FrontMatter {
+            tags: vec!["work".to_string(), "deploy, prod".to_string()],
+            hostname: Some("build-box".to_string()),
+            cwd: Some("/home/user/my project".to_string()),
+            command: Some("grep -r \"TODO\" src".to_string()),
+            created: Some("2021-06-15T12:00:00+01:00".to_string()),
+        }
+    }
+
+    #[test]
+    fn should_render_front_matter() {
+        assert_eq!(front_matter().to_yaml(), "---
+tags: [work, \"deploy, prod\"]
+hostname: build-box
+cwd: /home/user/my project
+command: \"grep -r \\\"TODO\\\" src\"
+created: \"2021-06-15T12:00:00+01:00\"
+---
+");
+    }
+
+    #[test]
+    fn should_read_back_rendered_front_matter() {
+        let note = insert(&front_matter(), "the note\n");
+
+        let (parsed, body) = split(&note);
+
+        assert_eq!(parsed, Some(front_matter()));
+        assert_eq!(body, "the note\n");
+    }
+
+    #[test]
+    fn should_keep_the_title_heading_on_the_first_line() {
+        let front_matter = FrontMatter { tags: vec!["work".to_string()], ..FrontMatter::default() };
+
+        let note = insert(&front_matter, "# Meeting\n\nthe note\n");
+
+        assert_eq!(note, "# Meeting\n\n---\ntags: [work]\n---\nthe note\n");
+        let (parsed, body) = split(&note);
+        assert_eq!(parsed, Some(front_matter));
+        assert_eq!(body, "# Meeting\n\nthe note\n");
+    }
+
+    #[test]
+    fn should_parse_block_lists_and_ignore_unknown_keys() {
+        let note = "---\r\ntitle: Something\r\ntags:\r\n  - one\r\n  - 'it''s two'\r\nhostname: box\r\n---\r\nbody";
+
+        let (parsed, body) = split(note);
+
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.tags, vec!["one", "it's two"]);
+        assert_eq!(parsed.hostname, Some("box".to_string()));
+        assert_eq!(body, "body");
+    }
+
+    #[test]
+    fn should_leave_notes_without_front_matter_alone() {
+        assert_eq!(split("just a note"), (None, Cow::Borrowed("just a note")));
+        assert_eq!(split("---\nnot closed"), (None, Cow::Borrowed("---\nnot closed")));
+        assert_eq!(split("# Heading\n\nbody"), (None, Cow::Borrowed("# Heading\n\nbody")));
+    }
+
+    #[test]
+    fn should_match_tags_ignoring_case() {
+        let front_matter = front_matter();
+        assert!(front_matter.has_tags(&["WORK".to_string()]));
+        assert!(front_matter.has_tags(&[]));
+        assert!(!front_matter.has_tags(&["work".to_string(), "home".to_string()]));
+    }
+}
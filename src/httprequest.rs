@@ -1,72 +1,939 @@
 use std::io::BufReader;
 use std::error::Error;
 use openssl::ssl::SslStream;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::net::{TcpStream};
-use std::io::{Read, Write};
+use std::io::{self, Cursor, Read, Write};
 use openssl::ssl::{SslMethod, SslConnector};
 use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use crate::compression;
+use crate::proxy::Proxy;
+use crate::resolver::{self, ResolveOverride};
 
 /// Request types supported by the LiteHttpClient
 /// For now, only the ones used by the nxcloudnotes application are supported
 pub enum RequestType {
     GET,
-    PUT
+    PUT,
+    MKCOL,
+    MOVE,
+    PROPFIND,
+    DELETE,
 }
 
+impl RequestType {
+    /// The http method sent on the request line.
+    pub fn method(&self) -> &'static str {
+        match self {
+            RequestType::GET => "GET",
+            RequestType::PUT => "PUT",
+            RequestType::MKCOL => "MKCOL",
+            RequestType::MOVE => "MOVE",
+            RequestType::PROPFIND => "PROPFIND",
+            RequestType::DELETE => "DELETE",
+        }
+    }
+}
+
+type PooledStream = BufReader<SslStream<TcpStream>>;
+
+/// How many idle connections are kept for each host, so requests sent at once (ie by the bulk
+/// operations) can each reuse one. Connections returned once there are this many are closed.
+pub const MAX_IDLE_CONNECTIONS_PER_HOST: usize = 16;
+
+/// Idle keep-alive connections, keyed by `host:port`.
+/// The pool is shared between clones of a `LiteHttpClient`, so every request built from
+/// the same client reuses a socket (and TLS session) left behind by a previous one.
+#[derive(Clone, Default)]
+pub struct ConnectionPool {
+    idle: Arc<Mutex<HashMap<String, Vec<PooledStream>>>>,
+}
+
+impl ConnectionPool {
+    /// Takes the most recently used idle connection for the key.
+    fn take(&self, key: &str) -> Option<PooledStream> {
+        self.idle.lock().ok()?.get_mut(key)?.pop()
+    }
+
+    /// Takes an idle connection for the key, discarding any the server has closed in the meantime.
+    fn take_live(&self, key: &str) -> Option<PooledStream> {
+        while let Some(stream) = self.take(key) {
+            if is_connection_alive(&stream) {
+                return Some(stream);
+            }
+        }
+        None
+    }
+
+    fn put(&self, key: String, stream: PooledStream) {
+        if let Ok(mut idle) = self.idle.lock() {
+            let streams = idle.entry(key).or_default();
+            if streams.len() < MAX_IDLE_CONNECTIONS_PER_HOST {
+                streams.push(stream);
+            }
+        }
+    }
+
+    /// Number of connections currently parked in the pool waiting to be reused.
+    pub fn idle_connections(&self) -> usize {
+        self.idle.lock().map(|idle| idle.values().map(Vec::len).sum()).unwrap_or(0)
+    }
+}
+
+/// Checks an idle connection without blocking. A live idle connection has nothing to read, whereas
+/// one closed by the server reads as EOF (or has a TLS close notification waiting).
+fn is_connection_alive(stream: &PooledStream) -> bool {
+    if !stream.buffer().is_empty() {
+        return false;
+    }
+    let socket = stream.get_ref().get_ref();
+    if socket.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let mut probe = [0; 1];
+    let would_block = matches!(socket.peek(&mut probe), Err(ref e) if e.kind() == io::ErrorKind::WouldBlock);
+    socket.set_nonblocking(false).is_ok() && would_block
+}
+
+/// How many redirects are followed for a request, unless configured otherwise.
+pub const DEFAULT_REDIRECT_LIMIT: u32 = 5;
+/// Streamed request bodies of up to this many bytes are read into memory before they're sent, so they
+/// can be sent again if the request is redirected.
+const REPLAYABLE_BODY_LIMIT: u64 = 1024 * 1024;
+
+/// How a client connects to servers, follows redirects and compresses uploads. It's shared by
+/// `LiteHttpClient` and its async counterpart, so that both behave the same way.
+#[derive(Clone)]
+pub(crate) struct ConnectionSettings {
+    connector: Option<SslConnector>,
+    redirect_limit: u32,
+    proxy: Option<Proxy>,
+    resolve_overrides: Vec<ResolveOverride>,
+    upload_compression: Option<u64>,
+    /// The `host:port` of servers that have said they accept gzipped request bodies, shared between clones.
+    gzip_accepted: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        ConnectionSettings {
+            connector: None,
+            redirect_limit: DEFAULT_REDIRECT_LIMIT,
+            proxy: None,
+            resolve_overrides: Vec::new(),
+            upload_compression: None,
+            gzip_accepted: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+}
+
+impl ConnectionSettings {
+    pub(crate) fn set_ssl_connector(&mut self, connector: SslConnector) {
+        self.connector = Some(connector);
+    }
+
+    pub(crate) fn set_redirect_limit(&mut self, redirect_limit: u32) {
+        self.redirect_limit = redirect_limit;
+    }
+
+    pub(crate) fn set_proxy(&mut self, proxy: Proxy) {
+        self.proxy = Some(proxy);
+    }
+
+    pub(crate) fn set_resolve(&mut self, resolve_overrides: Vec<ResolveOverride>) {
+        self.resolve_overrides = resolve_overrides;
+    }
+
+    pub(crate) fn set_upload_compression(&mut self, min_length: u64) {
+        self.upload_compression = Some(min_length);
+    }
+
+    /// The connector for the TLS handshake, which is the system default unless one was given.
+    pub(crate) fn ssl_connector(&self) -> Result<SslConnector, Box<dyn Error>> {
+        match &self.connector {
+            Some(connector) => Ok(connector.clone()),
+            None => Ok(SslConnector::builder(SslMethod::tls())?.build()),
+        }
+    }
+
+    /// Opens a connection to the host, through the proxy unless it's bypassed for the host,
+    /// which is ready for the TLS handshake.
+    pub(crate) fn open_socket(&self, host: &str, port: u32) -> Result<TcpStream, Box<dyn Error>> {
+        let stream = match self.proxy.as_ref().filter(|proxy| !proxy.is_bypassed_for(host)) {
+            Some(proxy) => proxy.connect(host, port, &self.resolve_overrides)?,
+            None => {
+                let connected = resolver::resolve(host, port, &self.resolve_overrides)
+                    .and_then(|addresses| resolver::connect(&addresses, resolver::CONNECTION_ATTEMPT_DELAY));
+                match connected {
+                    Ok(stream) => stream,
+                    Err(_) => Err("Unable to connect to given host")?,
+                }
+            },
+        };
+        // the request head and body are written separately, so without this a kept-alive
+        // connection stalls on Nagle's algorithm waiting for the server's delayed ACK
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+
+    /// Remembers whether the server at `pool_key` accepts gzipped request bodies, from the headers of its response.
+    pub(crate) fn note_accepted_encodings(&self, pool_key: &str, headers: &HashMap<String, String>) {
+        if self.upload_compression.is_none() || !headers.contains_key("accept-encoding") {
+            return;
+        }
+        if let Ok(mut gzip_accepted) = self.gzip_accepted.lock() {
+            if compression::accepts_gzip(headers) {
+                gzip_accepted.insert(pool_key.to_string());
+            } else {
+                gzip_accepted.remove(pool_key);
+            }
+        }
+    }
+
+    /// Whether a body of the given length sent to the server at `pool_key` is gzipped, setting the
+    /// `Content-Encoding` header to match.
+    pub(crate) fn compress_upload(&self, method: &str, pool_key: &str, length: Option<u64>, headers: &mut HashMap<String, String>) -> bool {
+        let min_length = match self.upload_compression {
+            Some(min_length) => min_length,
+            None => return false,
+        };
+        let compress = method == "PUT"
+            && length.is_none_or(|length| length >= min_length)
+            && self.gzip_accepted.lock().is_ok_and(|gzip_accepted| gzip_accepted.contains(pool_key));
+        // the decision is made again after a redirect, possibly to a server that doesn't take gzip
+        if compress {
+            headers.insert("Content-Encoding".to_string(), "gzip".to_string());
+        } else {
+            headers.remove("Content-Encoding");
+        }
+        compress
+    }
+
+    pub(crate) fn is_followed_redirect(&self, response_code: u16) -> bool {
+        is_followed_redirect(response_code, self.redirect_limit)
+    }
+
+    pub(crate) fn count_redirect(&self, redirects: &mut u32, location: &str) -> Result<(), String> {
+        count_redirect(redirects, self.redirect_limit, location)
+    }
+}
+
+pub(crate) fn is_followed_redirect(response_code: u16, redirect_limit: u32) -> bool {
+    matches!(response_code, 301 | 302 | 307 | 308) && redirect_limit > 0
+}
+
+/// Counts a redirect that's about to be followed, failing once the limit has been reached.
+/// `redirects` counts the redirects followed so far.
+pub(crate) fn count_redirect(redirects: &mut u32, redirect_limit: u32, location: &str) -> Result<(), String> {
+    if *redirects >= redirect_limit {
+        return Err(format!("Gave up after following {} redirects, the last one to {}", redirects, location));
+    }
+    *redirects += 1;
+    Ok(())
+}
+
+/// Reads in a streamed request body that's small enough to be sent again after a redirect,
+/// returning `None` for bodies that have to be streamed.
+pub(crate) fn read_replayable_body<R: Read>(body: &mut R, length: Option<u64>) -> io::Result<Option<Vec<u8>>> {
+    match length {
+        Some(length) if length <= REPLAYABLE_BODY_LIMIT => {
+            let mut replayable = Vec::new();
+            body.take(length).read_to_end(&mut replayable)?;
+            Ok(Some(replayable))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// The error for a redirected request whose body was streamed, so it can't be sent again.
+pub(crate) fn unreplayable_redirect(location: &str) -> String {
+    format!("The upload was redirected to {}, but a redirected upload can't be replayed as its body was streamed", location)
+}
+
+#[derive(Clone)]
 pub struct LiteHttpClient {
     base_address: String,
     port: u32,
-    request_line: String,
-    headers: HashMap<String, String>, 
+    method: &'static str,
+    path: String,
+    headers: HashMap<String, String>,
+    pool: ConnectionPool,
+    settings: ConnectionSettings,
 }
 
 /// A lite http client that is built on top of openssl for connecting to hosts via ssl.
 /// This is purely done as a learning exercise, and Rust has plenty of good generic
 /// http client crates that you could use.
+///
+/// Cloning the client is cheap and the clones share one connection pool, so the intended usage
+/// is to keep a 'template' client around and clone it for each request that is sent.
+///
+/// Redirects (301, 302, 307 and 308) are followed with the same method and body, as long as they
+/// stay on https. The `Authorization` header is only sent on to the same host and port. A body sent
+/// with `send_stream` is only held on to if it's no bigger than `REPLAYABLE_BODY_LIMIT`, so a redirect
+/// of a larger (or unknown length) upload fails, as its body can't be sent again.
+///
+/// Responses are asked for with gzip or deflate compression, and decoded as they're read, so callers
+/// always see the uncompressed body.
+///
+/// The host can be a name or an IP address, where an IPv6 one can be given with or without brackets.
+/// When a name resolves to several addresses they're raced against each other (Happy Eyeballs), so
+/// a broken IPv6 or IPv4 route only delays the connection rather than failing it.
 impl LiteHttpClient {
     pub fn new(base_address: String, port: u32) -> Self {
         LiteHttpClient {
-            base_address,
+            base_address: base_address.trim_start_matches('[').trim_end_matches(']').to_string(),
             port,
-            request_line: "/".to_string(),
-            headers: HashMap::new()
+            method: "GET",
+            path: "/".to_string(),
+            headers: HashMap::new(),
+            pool: ConnectionPool::default(),
+            settings: ConnectionSettings::default(),
         }
     }
-    fn connect_to_server(&self) -> Result<SslStream<TcpStream>,Box<dyn Error>> {
-        let connector = SslConnector::builder(SslMethod::tls())?.build();
-        let address_with_port = format!("{}:{}", self.base_address, self.port);
 
-        if let Ok(stream) = TcpStream::connect(address_with_port) {
-            let ssl_stream = connector.connect(&self.base_address, stream)?;
-            Ok(ssl_stream)
+    /// Uses the given connector for the TLS handshake instead of the system default,
+    /// ie to trust a self-signed certificate.
+    pub fn with_ssl_connector(mut self, connector: SslConnector) -> Self {
+        self.settings.set_ssl_connector(connector);
+        self
+    }
+
+    /// Sets how many redirects are followed before giving up on a request, where 0 returns
+    /// redirect responses rather than following them.
+    pub fn with_redirect_limit(mut self, redirect_limit: u32) -> Self {
+        self.settings.set_redirect_limit(redirect_limit);
+        self
+    }
+
+    /// Connects to hosts through the proxy, apart from the ones it's bypassed for.
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.settings.set_proxy(proxy);
+        self
+    }
+
+    /// Connects to the given addresses for a host and port instead of looking them up, ie to reach
+    /// the server by its internal address on a split-horizon network.
+    pub fn with_resolve(mut self, resolve_overrides: Vec<ResolveOverride>) -> Self {
+        self.settings.set_resolve(resolve_overrides);
+        self
+    }
+
+    /// Gzips the body of PUT requests that are at least `min_length` bytes (or of unknown length),
+    /// once the server has said it accepts gzipped bodies with an `Accept-Encoding` response header.
+    /// The client then owns the `Content-Encoding` header of those requests.
+    ///
+    /// Only waiting for that header (RFC 7694) makes this safe: Nextcloud's WebDAV server (Sabre) doesn't
+    /// decode a `Content-Encoding: gzip` PUT body, and would store the gzipped bytes as the file. Nextcloud
+    /// doesn't send the header itself, so bodies are only gzipped when something in front of it, ie a
+    /// reverse proxy that decodes request bodies, says it will take them.
+    pub fn with_upload_compression(mut self, min_length: u64) -> Self {
+        self.settings.set_upload_compression(min_length);
+        self
+    }
+
+    /// The pool of idle connections shared by this client and all of its clones.
+    pub fn pool(&self) -> &ConnectionPool {
+        &self.pool
+    }
+
+    fn pool_key(&self) -> String {
+        resolver::format_authority(&self.base_address, self.port)
+    }
+
+    fn connect_to_server(&self) -> Result<PooledStream, Box<dyn Error>> {
+        let connector = self.settings.ssl_connector()?;
+        let stream = self.settings.open_socket(&self.base_address, self.port)?;
+        let ssl_stream = connector.connect(&self.base_address, stream)?;
+        Ok(BufReader::new(ssl_stream))
+    }
+
+    /// Whether a body of the given length is gzipped, setting the `Content-Encoding` header to match.
+    fn compress_upload(&mut self, length: Option<u64>) -> bool {
+        let pool_key = self.pool_key();
+        self.settings.compress_upload(self.method, &pool_key, length, &mut self.headers)
+    }
+
+    /// Builds the request line and headers. A body of unknown `length` is sent with chunked encoding.
+    fn request_head(&mut self, length: Option<u64>) -> String {
+        let request_line = format!("{} {} HTTP/1.1\r\n", self.method, self.path);
+        format_request_head(&request_line, &mut self.headers, length)
+    }
+
+    /// Points the request at the `Location` of a redirect response, returning false if the response
+    /// isn't a redirect that should be followed. `redirects` counts the redirects followed so far.
+    fn follow_redirect(&mut self, response_code: u16, location: Option<&str>, redirects: &mut u32) -> Result<bool, Box<dyn Error>> {
+        let location = match location {
+            Some(location) if self.settings.is_followed_redirect(response_code) => location,
+            _ => return Ok(false),
+        };
+        self.settings.count_redirect(redirects, location)?;
+        let (host, port, path) = redirect(&self.base_address, self.port, &self.path, location, &mut self.headers)?;
+        self.base_address = host;
+        self.port = port;
+        self.path = path;
+        Ok(true)
+    }
+
+    /// Writes the request to the stream and reads back the response.
+    /// Returns the response along with whether the connection can be used for another request.
+    fn exchange(&self, stream: &mut PooledStream, request_head: &[u8], mut body: &[u8]) -> Result<(HttpResponse, bool), Box<dyn Error>> {
+        let length = body.len() as u64;
+        if let Err(e) = write_request(stream.get_mut(), request_head, &mut body, Some(length)) {
+            return Err(match e.downcast::<io::Error>() {
+                Ok(io_error) => StaleConnection::from_write_error(*io_error),
+                Err(e) => e,
+            });
+        }
+
+        let (response, keep_alive) = parse_response(stream)?;
+        self.settings.note_accepted_encodings(&self.pool_key(), &response.headers);
+        Ok((response, keep_alive))
+    }
+
+    fn send_bytes_once(&mut self, bytes: &[u8]) -> Result<HttpResponse, Box<dyn Error>> {
+        let mut compressed = Vec::new();
+        let bytes = if self.compress_upload(Some(bytes.len() as u64)) {
+            compression::gzip(bytes).read_to_end(&mut compressed)?;
+            &compressed
         } else {
-            Err("Unable to connect to given host")?
-        }
-    }
-
-    fn parse_response(&self, stream: SslStream<TcpStream>) -> Result<HttpResponse, Box<dyn Error>> {
-        let mut buf = String::new();
-        let mut reader = BufReader::new(stream);
-        let mut top_line = String::new();
-        reader.read_line(&mut top_line)?;
-        let response_code: u16 = top_line[9..12].parse()?;
-        reader.read_to_string(&mut buf)?;
-        let mut splitter = buf.splitn(2, "\r\n\r\n");
-        splitter.next().unwrap();
-        // body of the response is after /r/n/r/n, which is what we want
-        // and this should always be present (I think?) on a http response.
-        let second = splitter.next().unwrap();
-        Ok(HttpResponse {
-            response_code,
-            response_msg: second.to_string()
-        }) 
+            bytes
+        };
+        let request_data = self.request_head(Some(bytes.len() as u64));
+
+        let pool_key = self.pool_key();
+        if let Some(mut stream) = self.pool.take_live(&pool_key) {
+            match self.exchange(&mut stream, request_data.as_bytes(), bytes) {
+                Ok((response, keep_alive)) => {
+                    if keep_alive {
+                        self.pool.put(pool_key, stream);
+                    }
+                    return Ok(response);
+                },
+                // the server has closed the idle connection since it was last used, so reconnect below
+                Err(e) if can_resend(e.as_ref(), self.method) => {},
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut stream = self.connect_to_server()?;
+        let (response, keep_alive) = self.exchange(&mut stream, request_data.as_bytes(), bytes)?;
+        if keep_alive {
+            self.pool.put(pool_key, stream);
+        }
+        Ok(response)
+    }
+
+    fn send_stream_once<R: Read>(&mut self, mut body: R, length: Option<u64>) -> Result<StreamingResponse, Box<dyn Error>> {
+        let mut compressed;
+        let (body, length): (&mut dyn Read, Option<u64>) = if self.compress_upload(length) {
+            // the compressed length isn't known until it's all been sent, so it goes in chunks
+            compressed = compression::gzip(body);
+            (&mut compressed, None)
+        } else {
+            (&mut body, length)
+        };
+        let request_data = self.request_head(length);
+
+        // unlike send_bytes a streamed body can't be replayed, so a pooled connection is only
+        // used if it still looks open before anything is written to it
+        let pool_key = self.pool_key();
+        let mut stream = match self.pool.take_live(&pool_key) {
+            Some(stream) => stream,
+            None => self.connect_to_server()?,
+        };
+        write_request(stream.get_mut(), request_data.as_bytes(), body, length)?;
+        let mut head = parse_response_head(&mut stream)?;
+        self.settings.note_accepted_encodings(&pool_key, &head.headers);
+
+        let has_body = head.framing != BodyFraming::Length(0);
+        let body = ResponseBody {
+            stream: Some(stream),
+            framing: head.framing,
+            keep_alive: head.keep_alive,
+            pool: self.pool.clone(),
+            pool_key,
+        };
+        Ok(StreamingResponse {
+            response_code: head.response_code,
+            body: if has_body { compression::decode_body(&mut head.headers, body) } else { Box::new(body) },
+            headers: head.headers,
+        })
+    }
+}
+
+/// The async client connects with the same settings, and gzips uploads for the servers this one has found
+/// to accept them, but it has a pool of connections of its own.
+#[cfg(feature = "async")]
+impl crate::asynchttp::ToAsync for LiteHttpClient {
+    type Async = crate::asynchttp::AsyncLiteHttpClient;
+
+    fn to_async(&self) -> Self::Async {
+        crate::asynchttp::AsyncLiteHttpClient::with_settings(self.base_address.clone(), self.port, self.settings.clone())
+    }
+}
+
+/// Builds the request line and headers, adding the headers every request is sent with.
+/// A body of unknown `length` is sent with chunked encoding. The headers can be left from sending the
+/// request before, ie before a redirect, so whichever of the two framing headers isn't used is removed.
+pub(crate) fn format_request_head(request_line: &str, headers: &mut HashMap<String, String>, length: Option<u64>) -> String {
+    let mut request_data = String::new();
+    request_data.push_str(request_line);
+
+    // accept all responses for now, as we aren't particulary interested in the response type
+    // as much as the response code to indicate success or failure
+    headers.insert(String::from("Accept"), String::from("*/*"));
+    match length {
+        Some(length) => {
+            headers.remove("Transfer-Encoding");
+            headers.insert(String::from("Content-Length"), length.to_string());
+        },
+        None => {
+            headers.remove("Content-Length");
+            headers.insert(String::from("Transfer-Encoding"), String::from("chunked"));
+        },
+    }
+    headers.entry(String::from("Connection")).or_insert_with(|| String::from("keep-alive"));
+
+    for (key, value) in headers.iter() {
+        let header_formatted = format!("{}: {}\r\n", key, value);
+        request_data.push_str(&header_formatted)
+    }
+    // end of http request headers, indicated by the new-line
+    request_data.push_str("\r\n");
+    request_data
+}
+
+/// Size of the buffer used when streaming request bodies, which is also the size of each chunk
+/// when the body is sent with chunked encoding.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+fn write_request<W: Write, R: Read + ?Sized>(socket: &mut W, request_head: &[u8], body: &mut R, length: Option<u64>) -> Result<(), Box<dyn Error>> {
+    socket.write_all(request_head)?;
+    match length {
+        Some(length) => {
+            let written = io::copy(&mut body.take(length), socket)?;
+            if written != length {
+                Err(format!("Request body ended after {} of {} bytes", written, length))?
+            }
+        },
+        None => {
+            let mut buf = vec![0; STREAM_BUFFER_SIZE];
+            loop {
+                let read = body.read(&mut buf)?;
+                if read == 0 {
+                    break;
+                }
+                write!(socket, "{:X}\r\n", read)?;
+                socket.write_all(&buf[..read])?;
+                socket.write_all(b"\r\n")?;
+            }
+            socket.write_all(b"0\r\n\r\n")?;
+        }
+    }
+    socket.flush()?;
+    Ok(())
+}
+
+/// How the end of a response body is found.
+#[derive(Debug, PartialEq)]
+pub(crate) enum BodyFraming {
+    /// The number of bytes of the body that are left to read.
+    Length(u64),
+    /// Chunked transfer encoding, with the number of bytes left in the current chunk.
+    Chunked { remaining: u64, finished: bool },
+    /// No framing information, so the body runs until the server closes the connection.
+    UntilClose,
+}
+
+impl BodyFraming {
+    /// Reads the next part of the body, returning 0 once the whole body has been read.
+    pub(crate) fn read<R: BufRead>(&mut self, reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self {
+            BodyFraming::Length(remaining) => {
+                if *remaining == 0 {
+                    return Ok(0);
+                }
+                let max = (*remaining).min(buf.len() as u64) as usize;
+                let read = reader.read(&mut buf[..max])?;
+                if read == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed before the whole response body was received"));
+                }
+                *remaining -= read as u64;
+                Ok(read)
+            },
+            BodyFraming::Chunked { remaining, finished } => {
+                if *finished {
+                    return Ok(0);
+                }
+                if *remaining == 0 {
+                    let size = read_chunk_size(reader)?;
+                    if size == 0 {
+                        skip_trailers(reader)?;
+                        *finished = true;
+                        return Ok(0);
+                    }
+                    *remaining = size;
+                }
+                let max = (*remaining).min(buf.len() as u64) as usize;
+                let read = reader.read(&mut buf[..max])?;
+                if read == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed in the middle of a response chunk"));
+                }
+                *remaining -= read as u64;
+                if *remaining == 0 {
+                    // each chunk's data is followed by a CRLF
+                    let mut crlf = [0; 2];
+                    reader.read_exact(&mut crlf)?;
+                }
+                Ok(read)
+            },
+            BodyFraming::UntilClose => reader.read(buf),
+        }
+    }
+}
+
+fn read_chunk_size<R: BufRead>(reader: &mut R) -> io::Result<u64> {
+    let mut size_line = String::new();
+    reader.read_line(&mut size_line)?;
+    parse_chunk_size(&size_line)
+}
+
+pub(crate) fn parse_chunk_size(size_line: &str) -> io::Result<u64> {
+    // chunk extensions (after a ';') aren't used by anything we talk to, so they're ignored
+    let size_str = size_line.split(';').next().unwrap_or("").trim();
+    u64::from_str_radix(size_str, 16)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid chunk size: {}", size_str)))
+}
+
+/// Skips any trailers after the last chunk, up to and including the final empty line.
+fn skip_trailers<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            return Ok(());
+        }
+    }
+}
+
+/// The status line and headers of a response, read before any of the body.
+pub(crate) struct ResponseHead {
+    pub(crate) response_code: u16,
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) keep_alive: bool,
+    pub(crate) framing: BodyFraming,
+}
+
+pub(crate) fn parse_response_head<R: BufRead>(reader: &mut R) -> Result<ResponseHead, Box<dyn Error>> {
+    let mut top_line = String::new();
+    match reader.read_line(&mut top_line) {
+        // the server closed the connection before responding, usually an idle keep-alive timeout
+        Ok(0) => Err(StaleConnection::closed_before_response())?,
+        Err(e) if top_line.is_empty() && e.kind() == io::ErrorKind::ConnectionReset => Err(StaleConnection::reset_before_response(e))?,
+        result => { result?; },
+    }
+    if top_line.len() < 12 || !top_line.starts_with("HTTP/") {
+        Err(format!("Malformed http status line: {}", top_line.trim_end()))?
+    }
+    let response_code: u16 = top_line[9..12].parse()?;
+    let mut keep_alive = top_line.starts_with("HTTP/1.1");
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed while reading response headers"))?
+        }
+        // end of the http response headers, indicated by the empty line
+        if line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    if let Some(connection) = headers.get("connection") {
+        keep_alive = !connection.eq_ignore_ascii_case("close");
+    }
+
+    let has_body = !(response_code < 200 || response_code == 204 || response_code == 304);
+    let is_chunked = headers
+        .get("transfer-encoding")
+        .is_some_and(|encoding| encoding.to_lowercase().contains("chunked"));
+    let framing = if !has_body {
+        BodyFraming::Length(0)
+    } else if is_chunked {
+        BodyFraming::Chunked { remaining: 0, finished: false }
+    } else if let Some(length) = headers.get("content-length") {
+        BodyFraming::Length(length.parse()?)
+    } else {
+        keep_alive = false;
+        BodyFraming::UntilClose
+    };
+
+    Ok(ResponseHead {
+        response_code,
+        headers,
+        keep_alive,
+        framing,
+    })
+}
+
+/// Reads a single http response from the reader, leaving it positioned at the start of the next response.
+/// The returned bool indicates whether the connection can be kept alive after this response.
+fn parse_response<R: BufRead>(reader: &mut R) -> Result<(HttpResponse, bool), Box<dyn Error>> {
+    let mut head = parse_response_head(reader)?;
+
+    let mut body = Vec::new();
+    let mut buf = [0; 8 * 1024];
+    loop {
+        let read = head.framing.read(reader, &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&buf[..read]);
+    }
+    let body = decode_response_body(&mut head.headers, body)?;
+
+    let response = HttpResponse {
+        response_code: head.response_code,
+        response_msg: String::from_utf8_lossy(&body).to_string(),
+        headers: head.headers,
+    };
+    Ok((response, head.keep_alive))
+}
+
+/// Undoes the compression of a response body read in full, removing the `Content-Encoding` header to match.
+pub(crate) fn decode_response_body(headers: &mut HashMap<String, String>, body: Vec<u8>) -> io::Result<Vec<u8>> {
+    if body.is_empty() {
+        return Ok(body);
+    }
+    let mut decoded = Vec::new();
+    compression::decode_body(headers, Cursor::new(body)).read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
+
+/// A reused connection that the server had already closed, found either while the request was being
+/// written or because the connection ended before a single byte of the response arrived.
+#[derive(Debug)]
+pub(crate) struct StaleConnection {
+    error: io::Error,
+    /// Whether the whole request had been written, in which case the server may have acted on it
+    /// before closing the connection.
+    request_sent: bool,
+}
+
+impl StaleConnection {
+    pub(crate) fn closed_before_response() -> Self {
+        let error = io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed before a response was received");
+        StaleConnection { error, request_sent: true }
+    }
+
+    pub(crate) fn reset_before_response(error: io::Error) -> Self {
+        StaleConnection { error, request_sent: true }
+    }
+
+    /// Marks an error writing the request as stale. A timeout isn't, as the server may just be slow
+    /// to read a request it has already started acting on.
+    pub(crate) fn from_write_error(error: io::Error) -> Box<dyn Error + Send + Sync> {
+        match error.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Box::new(error),
+            _ => Box::new(StaleConnection { error, request_sent: false }),
+        }
+    }
+}
+
+impl fmt::Display for StaleConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl Error for StaleConnection {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Whether a request with the given method that failed on a reused connection can be sent again on a
+/// new one. A request the server didn't get all of can always be resent, but once it's all been written
+/// the server may have acted on it, so only requests that can safely be applied twice are resent.
+pub(crate) fn can_resend(error: &(dyn Error + 'static), method: &str) -> bool {
+    match error.downcast_ref::<StaleConnection>() {
+        Some(stale) => !stale.request_sent || is_idempotent(method),
+        None => false,
+    }
+}
+
+/// PUT is left out, even though it's idempotent on its own, as a conditional PUT (ie one that only
+/// creates a note that doesn't exist yet) fails with 412 when it's sent a second time.
+fn is_idempotent(method: &str) -> bool {
+    matches!(method, "GET" | "HEAD" | "PROPFIND" | "DELETE")
+}
+
+/// Works out where a followed redirect to `location` sends a request for `path` on `host` and `port`,
+/// updating the `Host` header to match. The credentials are for the server they were meant for, so
+/// they're removed from `headers` unless the redirect is to the same host and port.
+pub(crate) fn redirect(host: &str, port: u32, path: &str, location: &str, headers: &mut HashMap<String, String>) -> Result<(String, u32, String), Box<dyn Error>> {
+    // 301 and 302 only allow the method to change for POST, which isn't sent here, so every
+    // redirect is sent with the same method and body
+    let (new_host, new_port, new_path) = resolve_location(host, port, path, location)?;
+    if !new_host.eq_ignore_ascii_case(host) || new_port != port {
+        headers.retain(|name, _| !name.eq_ignore_ascii_case("Authorization"));
+    }
+    headers.insert("Host".to_string(), resolver::host_header(&new_host, new_port));
+    Ok((new_host, new_port, new_path))
+}
+
+/// Works out the host, port and path a redirect's `Location` points to, from the host, port and path
+/// of the request that was redirected. Only https locations are followed, as the client only speaks TLS.
+fn resolve_location(host: &str, port: u32, path: &str, location: &str) -> Result<(String, u32, String), Box<dyn Error>> {
+    // the fragment is for the client, it's never sent to the server
+    let location = location.split('#').next().unwrap_or("");
+    let authority_and_path = if let Some(rest) = location.strip_prefix("//") {
+        rest
+    } else if let Some((scheme, rest)) = location.split_once("://") {
+        if !scheme.eq_ignore_ascii_case("https") {
+            Err(format!("Refusing to follow the redirect to {}, as only https is supported and the request would be sent unencrypted", location))?
+        }
+        rest
+    } else if location.starts_with('/') {
+        return Ok((host.to_string(), port, location.to_string()));
+    } else {
+        // relative to the folder the redirected path is in
+        let folder = &path[..path.rfind('/').map_or(0, |index| index + 1)];
+        return Ok((host.to_string(), port, format!("{}{}", folder, location)));
+    };
+
+    let path_start = authority_and_path.find(['/', '?']).unwrap_or(authority_and_path.len());
+    let (authority, new_path) = authority_and_path.split_at(path_start);
+    let new_path = if new_path.starts_with('/') { new_path.to_string() } else { format!("/{}", new_path) };
+    // any user info is dropped, credentials aren't taken from a redirect
+    let authority = authority.rsplit('@').next().unwrap_or("");
+    let (new_host, new_port) = match authority.strip_prefix('[') {
+        Some(ipv6) => {
+            let (address, rest) = ipv6.split_once(']').ok_or_else(|| format!("Invalid host in redirect to {}", location))?;
+            (address, rest.strip_prefix(':'))
+        },
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    if new_host.is_empty() {
+        Err(format!("Invalid host in redirect to {}", location))?
+    }
+    let new_port = match new_port {
+        Some(port) => port.parse().map_err(|_| format!("Invalid port in redirect to {}", location))?,
+        None => 443,
+    };
+    Ok((new_host.to_string(), new_port, new_path))
+}
+
+/// Body of a streamed response, read straight from the connection.
+/// Once the whole body has been read the connection is handed back to the pool, whereas dropping
+/// it part way through closes the connection.
+struct ResponseBody {
+    stream: Option<PooledStream>,
+    framing: BodyFraming,
+    keep_alive: bool,
+    pool: ConnectionPool,
+    pool_key: String,
+}
+
+impl Read for ResponseBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return Ok(0),
+        };
+        let read = self.framing.read(stream, buf)?;
+        if read == 0 && !buf.is_empty() {
+            if let Some(stream) = self.stream.take() {
+                if self.keep_alive {
+                    self.pool.put(self.pool_key.clone(), stream);
+                }
+            }
+        }
+        Ok(read)
     }
 }
 
 pub struct HttpResponse {
     pub response_code: u16,
-    pub response_msg: String
+    pub response_msg: String,
+    /// Response headers, with the header names lowercased.
+    pub headers: HashMap<String, String>,
+}
+
+impl HttpResponse {
+    /// Gets the value of the given (case insensitive) response header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|value| value.as_str())
+    }
+}
+
+/// A response whose body hasn't been read yet, for downloads that shouldn't be held in memory.
+/// The body is the content sent by the server, after undoing any compression the client asked for.
+pub struct StreamingResponse {
+    pub response_code: u16,
+    /// Response headers, with the header names lowercased.
+    pub headers: HashMap<String, String>,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl StreamingResponse {
+    /// Gets the value of the given (case insensitive) response header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|value| value.as_str())
+    }
+
+    /// Reads the rest of the body into a buffered `HttpResponse`.
+    pub fn into_response(mut self) -> Result<HttpResponse, Box<dyn Error>> {
+        let mut body = Vec::new();
+        self.body.read_to_end(&mut body)?;
+        Ok(HttpResponse {
+            response_code: self.response_code,
+            response_msg: String::from_utf8_lossy(&body).to_string(),
+            headers: self.headers,
+        })
+    }
+}
+
+/// Wraps a reader and calls `callback` with the number of bytes read so far, and the total when
+/// it's known, every time data is read through it. Wrap an upload's body or a download's
+/// `StreamingResponse::body` with it to report progress.
+pub struct ProgressReader<R, F> {
+    inner: R,
+    callback: F,
+    transferred: u64,
+    total: Option<u64>,
+}
+
+impl<R: Read, F: FnMut(u64, Option<u64>)> ProgressReader<R, F> {
+    pub fn new(inner: R, total: Option<u64>, callback: F) -> Self {
+        ProgressReader {
+            inner,
+            callback,
+            transferred: 0,
+            total,
+        }
+    }
+}
+
+impl<R: Read, F: FnMut(u64, Option<u64>)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.transferred += read as u64;
+        (self.callback)(self.transferred, self.total);
+        Ok(read)
+    }
 }
 
 pub trait HttpRequest {
@@ -74,20 +941,18 @@ pub trait HttpRequest {
     fn set_header(self, header: String, value: String) -> Self;
     fn basic_auth(self, user: &str, password: &str) -> Self;
     fn send_bytes(self, bytes: &[u8]) -> Result<HttpResponse, Box<dyn Error>>;
+    /// Sends the request with its body read from `body`, without buffering it in memory.
+    /// The body is sent with a `Content-Length` when its `length` is known, or with chunked encoding otherwise.
+    /// The response body is left unread for the caller to stream.
+    fn send_stream<R: Read>(self, body: R, length: Option<u64>) -> Result<StreamingResponse, Box<dyn Error>>;
 }
 
 impl HttpRequest for LiteHttpClient {
     fn set_request(mut self, req_type: RequestType, remote_uri: &str) -> Self {
-        match req_type {
-            RequestType::GET => {
-                self.request_line = format!("GET {} HTTP/1.1\r\n", remote_uri);
-            },
-            RequestType::PUT => {
-                self.request_line = format!("PUT {} HTTP/1.1\r\n", remote_uri);
-            }
-        };
-
-        self.headers.insert("Host".to_string(), String::from(&self.base_address));
+        self.method = req_type.method();
+        self.path = remote_uri.to_string();
+        self.headers.insert("Host".to_string(), resolver::host_header(&self.base_address, self.port));
+        self.headers.insert("Accept-Encoding".to_string(), compression::ACCEPT_ENCODING.to_string());
         self
     }
 
@@ -104,27 +969,496 @@ impl HttpRequest for LiteHttpClient {
     }
 
     fn send_bytes(mut self, bytes: &[u8]) -> Result<HttpResponse, Box<dyn Error>> {
-        let mut stream = self.connect_to_server()?;
+        let mut redirects = 0;
+        loop {
+            let response = self.send_bytes_once(bytes)?;
+            if !self.follow_redirect(response.response_code, response.header("location"), &mut redirects)? {
+                return Ok(response);
+            }
+        }
+    }
 
-        let mut request_data = String::new();
-        request_data.push_str(&self.request_line);
+    fn send_stream<R: Read>(mut self, mut body: R, length: Option<u64>) -> Result<StreamingResponse, Box<dyn Error>> {
+        // a body that's read as it's sent can't be sent again after a redirect, so small ones are read in first
+        let replayable = read_replayable_body(&mut body, length)?;
+        let mut redirects = 0;
+        loop {
+            let mut response = match &replayable {
+                Some(replayable) => self.send_stream_once(&replayable[..], length)?,
+                None => self.send_stream_once(&mut body, length)?,
+            };
+            if replayable.is_none() && self.settings.is_followed_redirect(response.response_code) {
+                if let Some(location) = response.header("location") {
+                    Err(unreplayable_redirect(location))?
+                }
+            }
+            if !self.follow_redirect(response.response_code, response.header("location"), &mut redirects)? {
+                return Ok(response);
+            }
+            // read the rest of the redirect so its connection goes back to the pool
+            io::copy(&mut response.body, &mut io::sink())?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::mocknextcloud::MockNextcloud;
+    use crate::testserver::{Reply, TestServer, HOST, UNRESOLVED_HOST};
+
+    #[test]
+    fn should_parse_response_with_content_length() {
+        let mut input = Cursor::new("HTTP/1.1 201 Created\r\nContent-Length: 5\r\nETag: \"abc\"\r\n\r\nhelloHTTP/1.1".as_bytes());
+
+        let (response, keep_alive) = parse_response(&mut input).unwrap();
+
+        assert_eq!(response.response_code, 201);
+        assert_eq!(response.response_msg, "hello");
+        assert_eq!(response.header("etag"), Some("\"abc\""));
+        assert!(keep_alive);
+        // the reader should be left at the start of whatever comes next on the connection
+        assert_eq!(input.position(), 61);
+    }
+
+    #[test]
+    fn should_parse_chunked_response() {
+        let mut input = Cursor::new("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n6;ext=1\r\npedia \r\n0\r\n\r\n".as_bytes());
+
+        let (response, keep_alive) = parse_response(&mut input).unwrap();
+
+        assert_eq!(response.response_msg, "Wikipedia ");
+        assert!(keep_alive);
+    }
+
+    #[test]
+    fn should_not_keep_alive_when_server_closes_connection() {
+        let mut input = Cursor::new("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok".as_bytes());
+        let (_, keep_alive) = parse_response(&mut input).unwrap();
+        assert!(!keep_alive);
 
-        // accept all responses for now, as we aren't particulary interested in the response type
-        // as much as the response code to indicate success or failure
-        self.headers.insert(String::from("Accept"), String::from("*/*"));
-        self.headers.insert(String::from("Content-Length"), bytes.len().to_string());
+        let mut input = Cursor::new("HTTP/1.1 200 OK\r\n\r\nbody until eof".as_bytes());
+        let (response, keep_alive) = parse_response(&mut input).unwrap();
+        assert_eq!(response.response_msg, "body until eof");
+        assert!(!keep_alive);
+    }
+
+    #[test]
+    fn should_resend_any_request_the_server_didnt_get_all_of() {
+        let error = StaleConnection::from_write_error(io::Error::from(io::ErrorKind::BrokenPipe));
+
+        assert!(can_resend(error.as_ref(), "GET"));
+        assert!(can_resend(error.as_ref(), "MOVE"));
+        assert!(can_resend(error.as_ref(), "PUT"));
+    }
+
+    #[test]
+    fn should_only_resend_idempotent_request_when_closed_before_response() {
+        let mut input = Cursor::new("".as_bytes());
+        let error = parse_response(&mut input).err().unwrap();
+
+        assert!(can_resend(error.as_ref(), "GET"));
+        assert!(can_resend(error.as_ref(), "PROPFIND"));
+        assert!(can_resend(error.as_ref(), "DELETE"));
+        assert!(!can_resend(error.as_ref(), "MOVE"));
+        assert!(!can_resend(error.as_ref(), "PUT"));
+        assert!(!can_resend(error.as_ref(), "MKCOL"));
+    }
+
+    #[test]
+    fn should_not_resend_after_timeout_or_partial_response() {
+        let mut input = io::BufReader::new(FailingReader(io::ErrorKind::TimedOut));
+        let error = parse_response_head(&mut input).err().unwrap();
+        assert!(!can_resend(error.as_ref(), "GET"));
+
+        let error = StaleConnection::from_write_error(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(!can_resend(error.as_ref(), "GET"));
+
+        let mut input = Cursor::new("HTTP/1.1 200 OK\r\nContent-Le".as_bytes());
+        let error = parse_response(&mut input).err().unwrap();
+        assert!(!can_resend(error.as_ref(), "GET"));
+    }
 
-        for (key, value) in self.headers.iter_mut() {
-            let header_formatted = format!("{}: {}\r\n", key, value);
-            request_data.push_str(&header_formatted)
+    #[test]
+    fn should_treat_reset_before_response_as_closed_before_response() {
+        let mut input = io::BufReader::new(FailingReader(io::ErrorKind::ConnectionReset));
+        let error = parse_response_head(&mut input).err().unwrap();
+        assert!(can_resend(error.as_ref(), "GET"));
+        assert!(!can_resend(error.as_ref(), "MOVE"));
+
+        let mut input = io::BufReader::new(Cursor::new("HTTP/1.1 2".as_bytes()).chain(FailingReader(io::ErrorKind::ConnectionReset)));
+        let error = parse_response_head(&mut input).err().unwrap();
+        assert!(!can_resend(error.as_ref(), "GET"));
+    }
+
+    /// Fails every read with an error of the given kind.
+    struct FailingReader(io::ErrorKind);
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::from(self.0))
+        }
+    }
+
+    #[test]
+    fn should_write_unknown_length_body_with_chunked_encoding() {
+        let mut socket = Vec::new();
+        let mut body = Cursor::new("streamed body".as_bytes());
+
+        write_request(&mut socket, b"PUT / HTTP/1.1\r\n\r\n", &mut body, None).unwrap();
+
+        assert_eq!(String::from_utf8(socket).unwrap(), "PUT / HTTP/1.1\r\n\r\nD\r\nstreamed body\r\n0\r\n\r\n");
+    }
+
+    #[test]
+    fn should_fail_when_body_is_shorter_than_its_length() {
+        let mut socket = Vec::new();
+        let mut body = Cursor::new("short".as_bytes());
+
+        assert!(write_request(&mut socket, b"", &mut body, Some(10)).is_err());
+    }
+
+    #[test]
+    fn should_stream_binary_chunked_body_in_small_reads() {
+        let mut input = Cursor::new(b"3\r\n\x00\xff\x01\r\n2\r\n\xfe\x02\r\n0\r\n\r\nnext".to_vec());
+        let mut framing = BodyFraming::Chunked { remaining: 0, finished: false };
+
+        let mut body = Vec::new();
+        let mut buf = [0; 2];
+        loop {
+            let read = framing.read(&mut input, &mut buf).unwrap();
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&buf[..read]);
+        }
+
+        assert_eq!(body, vec![0x00, 0xff, 0x01, 0xfe, 0x02]);
+        let mut rest = String::new();
+        input.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "next");
+    }
+
+    #[test]
+    fn should_report_progress_while_reading() {
+        let mut reports = Vec::new();
+        let mut reader = ProgressReader::new(Cursor::new(vec![0; 10]), Some(10), |transferred, total| reports.push((transferred, total)));
+
+        let mut buf = [0; 4];
+        while reader.read(&mut buf).unwrap() > 0 {}
+
+        assert_eq!(reports, vec![(4, Some(10)), (8, Some(10)), (10, Some(10)), (10, Some(10))]);
+    }
+
+    #[test]
+    fn should_resend_on_new_connection_when_reused_one_is_dropped() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/a.md", b"content");
+        let client = mock.http_client();
+        let get = |client: &LiteHttpClient| client.clone()
+            .set_request(RequestType::GET, "/remote.php/dav/files/user/a.md")
+            .basic_auth("user", "password")
+            .send_bytes(&[]);
+
+        get(&client).unwrap();
+        mock.drop_next("GET", "/a.md");
+        let response = get(&client).unwrap();
+
+        assert_eq!(response.response_msg, "content");
+        assert_eq!(mock.server().received().len(), 3);
+        assert_eq!(mock.server().connections(), 2);
+    }
+
+    #[test]
+    fn should_not_resend_move_when_reused_connection_is_dropped() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/a.md", b"content");
+        let client = mock.http_client();
+        client.clone()
+            .set_request(RequestType::GET, "/remote.php/dav/files/user/a.md")
+            .basic_auth("user", "password")
+            .send_bytes(&[])
+            .unwrap();
+
+        mock.drop_next("MOVE", "/a.md");
+        let result = client.clone()
+            .set_request(RequestType::MOVE, "/remote.php/dav/files/user/a.md")
+            .basic_auth("user", "password")
+            .set_header("Destination".to_string(), "/remote.php/dav/files/user/b.md".to_string())
+            .send_bytes(&[]);
+
+        // the server may have moved the note before dropping the connection, so it's not moved again
+        assert!(result.is_err());
+        assert_eq!(mock.server().received().len(), 2);
+        assert_eq!(mock.server().connections(), 1);
+    }
+
+    #[test]
+    fn should_fail_when_new_connection_is_dropped() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.drop_next("GET", "/a.md");
+
+        let result = mock.http_client()
+            .set_request(RequestType::GET, "/remote.php/dav/files/user/a.md")
+            .basic_auth("user", "password")
+            .send_bytes(&[]);
+
+        assert!(result.is_err());
+        assert_eq!(mock.server().received().len(), 1);
+    }
+
+    #[test]
+    fn should_pool_connection_once_streamed_response_is_read() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/a.md", b"content");
+        let client = mock.http_client();
+
+        let mut response = client.clone()
+            .set_request(RequestType::GET, "/remote.php/dav/files/user/a.md")
+            .basic_auth("user", "password")
+            .send_stream(io::empty(), Some(0))
+            .unwrap();
+        assert_eq!(client.pool().idle_connections(), 0);
+        let mut body = String::new();
+        response.body.read_to_string(&mut body).unwrap();
+
+        assert_eq!(body, "content");
+        assert_eq!(client.pool().idle_connections(), 1);
+    }
+
+    #[test]
+    fn should_pool_every_connection_of_requests_sent_at_once() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/a.md", b"content");
+        let client = mock.http_client();
+        let get = |client: &LiteHttpClient| client.clone()
+            .set_request(RequestType::GET, "/remote.php/dav/files/user/a.md")
+            .basic_auth("user", "password")
+            .send_stream(io::empty(), Some(0))
+            .unwrap();
+
+        // both responses are still being read when the second request is sent, so it needs its own connection
+        let responses = vec![get(&client), get(&client)];
+        for mut response in responses {
+            io::copy(&mut response.body, &mut io::sink()).unwrap();
+        }
+        assert_eq!(client.pool().idle_connections(), 2);
+        let responses = vec![get(&client), get(&client)];
+        drop(responses);
+
+        assert_eq!(mock.server().received().len(), 4);
+        assert_eq!(mock.server().connections(), 2);
+    }
+
+    fn client(server: &TestServer) -> LiteHttpClient {
+        LiteHttpClient::new(HOST.to_string(), server.port()).with_ssl_connector(server.ssl_connector())
+    }
+
+    #[test]
+    fn should_resolve_redirect_locations() {
+        let resolve = |location| resolve_location("cloud.example.com", 443, "/remote.php/webdav/Notes/a.md", location).unwrap();
+
+        assert_eq!(resolve("/remote.php/dav/a.md"), ("cloud.example.com".to_string(), 443, "/remote.php/dav/a.md".to_string()));
+        assert_eq!(resolve("b.md"), ("cloud.example.com".to_string(), 443, "/remote.php/webdav/Notes/b.md".to_string()));
+        assert_eq!(resolve("https://other.example.com:8443/a.md#top"), ("other.example.com".to_string(), 8443, "/a.md".to_string()));
+        assert_eq!(resolve("//user@other.example.com?x=1"), ("other.example.com".to_string(), 443, "/?x=1".to_string()));
+        assert_eq!(resolve("HTTPS://[::1]:8443/a.md"), ("::1".to_string(), 8443, "/a.md".to_string()));
+        assert!(resolve_location("cloud.example.com", 443, "/", "https://:1/").is_err());
+    }
+
+    #[test]
+    fn should_follow_redirect_with_same_method_and_body() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/remote.php/webdav/a.md" => Reply::new(301, b"").with_header("Location", "/remote.php/dav/files/user/a.md"),
+            _ => Reply::new(201, b""),
+        });
+
+        let response = client(&server)
+            .set_request(RequestType::PUT, "/remote.php/webdav/a.md")
+            .basic_auth("user", "password")
+            .send_bytes(b"content")
+            .unwrap();
+
+        assert_eq!(response.response_code, 201);
+        let redirected = &server.received()[1];
+        assert_eq!(redirected.method, "PUT");
+        assert_eq!(redirected.path, "/remote.php/dav/files/user/a.md");
+        assert_eq!(redirected.body, b"content");
+        assert!(redirected.header("authorization").is_some());
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[test]
+    fn should_not_send_credentials_when_redirected_to_another_host() {
+        let target = TestServer::start(|_| Reply::new(200, b"moved here"));
+        let location = format!("https://127.0.0.1:{}/a.md", target.port());
+        let server = TestServer::start(move |_| Reply::new(307, b"").with_header("Location", &location));
+
+        let response = client(&server)
+            .set_request(RequestType::GET, "/a.md")
+            .basic_auth("user", "password")
+            .send_bytes(&[])
+            .unwrap();
+
+        assert_eq!(response.response_msg, "moved here");
+        let redirected = &target.received()[0];
+        assert_eq!(redirected.header("host"), Some(format!("127.0.0.1:{}", target.port()).as_str()));
+        assert_eq!(redirected.header("authorization"), None);
+    }
+
+    #[test]
+    fn should_refuse_redirect_to_http() {
+        let server = TestServer::start(|_| Reply::new(302, b"").with_header("Location", "http://localhost/a.md"));
+
+        let error = client(&server).set_request(RequestType::GET, "/a.md").send_bytes(&[]).err().unwrap();
+
+        assert!(error.to_string().contains("the request would be sent unencrypted"));
+    }
+
+    #[test]
+    fn should_give_up_after_redirect_limit() {
+        let server = TestServer::start(|_| Reply::new(308, b"").with_header("Location", "/again"));
+
+        let error = client(&server).with_redirect_limit(3).set_request(RequestType::GET, "/").send_bytes(&[]).err().unwrap();
+        let not_followed = client(&server).with_redirect_limit(0).set_request(RequestType::GET, "/").send_bytes(&[]).unwrap();
+
+        assert_eq!(error.to_string(), "Gave up after following 3 redirects, the last one to /again");
+        assert_eq!(not_followed.response_code, 308);
+        assert_eq!(server.received().len(), 5);
+    }
+
+    #[test]
+    fn should_only_follow_redirect_of_streamed_request_with_replayable_body() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/old.md" => Reply::new(308, b"moved").with_header("Location", "/new.md"),
+            _ => Reply::new(200, b"content"),
+        });
+        let client = client(&server);
+
+        let mut download = client.clone().set_request(RequestType::GET, "/old.md").send_stream(io::empty(), Some(0)).unwrap();
+        let mut body = String::new();
+        download.body.read_to_string(&mut body).unwrap();
+        let upload = client.clone().set_request(RequestType::PUT, "/old.md").send_stream(Cursor::new(b"new".to_vec()), Some(3)).unwrap();
+        let streamed = client.clone().set_request(RequestType::PUT, "/old.md").send_stream(Cursor::new(b"new".to_vec()), None);
+
+        assert_eq!(body, "content");
+        assert_eq!(upload.response_code, 200);
+        assert_eq!(server.received()[3].path, "/new.md");
+        assert_eq!(server.received()[3].body, b"new");
+        assert_eq!(
+            streamed.err().unwrap().to_string(),
+            "The upload was redirected to /new.md, but a redirected upload can't be replayed as its body was streamed"
+        );
+    }
+
+    #[test]
+    fn should_connect_to_resolve_override_address() {
+        let server = TestServer::start(|_| Reply::new(200, b"found"));
+        let resolve = format!("{}:{}:[::1],127.0.0.1", UNRESOLVED_HOST, server.port());
+
+        let response = LiteHttpClient::new(UNRESOLVED_HOST.to_string(), server.port())
+            .with_ssl_connector(server.ssl_connector())
+            .with_resolve(vec![resolve.parse().unwrap()])
+            .set_request(RequestType::GET, "/a.md")
+            .send_bytes(&[])
+            .unwrap();
+
+        assert_eq!(response.response_msg, "found");
+        let expected_host = format!("{}:{}", UNRESOLVED_HOST, server.port());
+        assert_eq!(server.received()[0].header("host"), Some(expected_host.as_str()));
+    }
+
+    fn gzipped(content: &[u8]) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        compression::gzip(content).read_to_end(&mut compressed).unwrap();
+        compressed
+    }
+
+    #[test]
+    fn should_decode_compressed_responses() {
+        let listing = b"<d:multistatus>a long listing</d:multistatus>".repeat(50);
+        let (gzipped_listing, content) = (gzipped(&listing), listing.clone());
+        let server = TestServer::start(move |request| match request.path.as_str() {
+            "/listing" => Reply::new(207, &gzipped_listing).with_header("Content-Encoding", "gzip"),
+            _ => {
+                let mut deflated = Vec::new();
+                flate2::read::ZlibEncoder::new(&content[..], flate2::Compression::default()).read_to_end(&mut deflated).unwrap();
+                Reply::new(200, &deflated).with_header("Content-Encoding", "deflate").chunked()
+            },
+        });
+        let client = client(&server);
+
+        let response = client.clone().set_request(RequestType::PROPFIND, "/listing").send_bytes(&[]).unwrap();
+        let mut download = client.clone().set_request(RequestType::GET, "/note.md").send_stream(io::empty(), Some(0)).unwrap();
+        let mut body = Vec::new();
+        download.body.read_to_end(&mut body).unwrap();
+
+        assert_eq!(response.response_msg.as_bytes(), &listing[..]);
+        assert_eq!(response.header("content-encoding"), None);
+        assert_eq!(body, listing);
+        assert_eq!(download.header("content-encoding"), None);
+        assert_eq!(server.received()[0].header("accept-encoding"), Some("gzip, deflate"));
+        assert_eq!(client.pool().idle_connections(), 1);
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[test]
+    fn should_gzip_large_uploads_once_server_accepts_them() {
+        let server = TestServer::start(|_| Reply::new(201, b"").with_header("Accept-Encoding", "gzip"));
+        let client = client(&server).with_upload_compression(100);
+        let content = b"a paragraph of a note. ".repeat(20);
+
+        for body in [&content[..], &content[..], b"short"] {
+            client.clone().set_request(RequestType::PUT, "/a.md").send_bytes(body).unwrap();
         }
-        // end of http request headers, indicated by the new-line
-        request_data.push_str("\r\n");
+        client.clone().set_request(RequestType::PUT, "/b.md").send_stream(Cursor::new(content.clone()), Some(content.len() as u64)).unwrap();
+
+        let received = server.received();
+        // the server hasn't said it accepts gzip before the first response
+        assert_eq!(received[0].header("content-encoding"), None);
+        assert_eq!(received[0].body, content);
+        assert_eq!(received[1].header("content-encoding"), Some("gzip"));
+        assert_eq!(received[1].body, gzipped(&content));
+        assert_eq!(received[2].header("content-encoding"), None);
+        assert_eq!(received[3].header("content-encoding"), Some("gzip"));
+        assert_eq!(received[3].header("transfer-encoding"), Some("chunked"));
+        assert_eq!(received[3].body, gzipped(&content));
+    }
+
+    #[test]
+    fn should_send_one_framing_header_when_redirected_upload_is_gzipped() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/old.md" => Reply::new(307, b"").with_header("Location", "/new.md").with_header("Accept-Encoding", "gzip"),
+            _ => Reply::new(201, b""),
+        });
+        let content = b"a paragraph of a note. ".repeat(20);
 
-        stream.write_all(request_data.as_bytes())?;
-        stream.flush().unwrap();
-        stream.write_all(bytes)?;
+        client(&server)
+            .with_upload_compression(100)
+            .set_request(RequestType::PUT, "/old.md")
+            .send_stream(Cursor::new(content.clone()), Some(content.len() as u64))
+            .unwrap();
 
-        self.parse_response(stream)
+        let received = server.received();
+        assert_eq!(received[0].header("content-length"), Some(content.len().to_string().as_str()));
+        assert_eq!(received[0].header("transfer-encoding"), None);
+        // the server said it takes gzip in the redirect, so the body is gzipped when it's sent again
+        assert_eq!(received[1].header("content-encoding"), Some("gzip"));
+        assert_eq!(received[1].header("transfer-encoding"), Some("chunked"));
+        assert_eq!(received[1].header("content-length"), None);
+        assert_eq!(received[1].body, gzipped(&content));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_replace_framing_header_left_from_previous_request() {
+        let mut headers = HashMap::new();
+
+        format_request_head("PUT / HTTP/1.1\r\n", &mut headers, Some(5));
+        let head = format_request_head("PUT / HTTP/1.1\r\n", &mut headers, None);
+        assert!(head.contains("Transfer-Encoding: chunked\r\n") && !head.contains("Content-Length"));
+
+        let head = format_request_head("PUT / HTTP/1.1\r\n", &mut headers, Some(5));
+        assert!(head.contains("Content-Length: 5\r\n") && !head.contains("Transfer-Encoding"));
+    }
+}
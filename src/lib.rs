@@ -1,6 +1,33 @@
 pub mod httprequest;
+pub mod compression;
+pub mod logging;
+pub mod proxy;
+pub mod resolver;
 pub mod nextcloudclient;
 pub mod configprovider;
 pub mod configcreator;
 pub mod utils;
-pub mod cliarguments;
\ No newline at end of file
+pub mod cliarguments;
+pub mod webdav;
+pub mod attachments;
+pub mod notes;
+pub mod frontmatter;
+pub mod clipboard;
+pub mod completions;
+pub mod manpage;
+pub mod terminal;
+pub mod browser;
+#[cfg(feature = "async")]
+pub mod asynchttp;
+#[cfg(feature = "async")]
+pub mod asyncnextcloudclient;
+#[cfg(feature = "backend-ureq")]
+pub mod ureqclient;
+#[cfg(any(test, feature = "test-server"))]
+pub mod testserver;
+#[cfg(any(test, feature = "test-server"))]
+pub mod mocknextcloud;
+#[cfg(test)]
+mod mockrequest;
+#[cfg(test)]
+mod conformance;
@@ -0,0 +1,651 @@
+//! Leveled logging of what's sent to the server, for `-v` and `-vv`, and a trace file that records every
+//! request and response as HAR-like JSON, to attach to bug reports.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use serde::Serialize;
+#[cfg(feature = "async")]
+use crate::asynchttp::{AsyncError, AsyncHttpRequest, AsyncResponse, ToAsync};
+use crate::httprequest::{HttpRequest, HttpResponse, RequestType, StreamingResponse};
+use crate::utils;
+
+/// How much of each request and response body is logged, the rest is only counted.
+pub const MAX_LOGGED_BODY: usize = 8 * 1024;
+/// Headers whose values are left out of logs and traces, as they hold credentials.
+const REDACTED_HEADERS: [&str; 4] = ["authorization", "proxy-authorization", "cookie", "set-cookie"];
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum LogLevel {
+    /// Nothing is logged.
+    #[default]
+    Quiet,
+    /// The method and url of each request with its response code and how long it took, along with
+    /// anything noteworthy the client does, like resuming an upload.
+    Verbose,
+    /// Everything `Verbose` logs, along with the headers and bodies of requests and responses.
+    Debug,
+}
+
+impl LogLevel {
+    /// The level for the number of times `-v` was given.
+    pub fn from_verbosity(verbosity: usize) -> Self {
+        match verbosity {
+            0 => LogLevel::Quiet,
+            1 => LogLevel::Verbose,
+            _ => LogLevel::Debug,
+        }
+    }
+}
+
+/// Where log messages and trace entries go, which is stderr unless given another output.
+/// Cloning the logger is cheap, and the clones write to the same output and trace file.
+#[derive(Clone, Default)]
+pub struct Logger {
+    level: LogLevel,
+    output: Option<Arc<Mutex<dyn Write + Send>>>,
+    trace: Option<Arc<Mutex<TraceFile>>>,
+}
+
+impl Logger {
+    pub fn new(level: LogLevel) -> Self {
+        Logger { level, ..Logger::default() }
+    }
+
+    /// Writes log messages to `output` instead of stderr.
+    pub fn with_output(mut self, output: Arc<Mutex<dyn Write + Send>>) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Also records every exchange in a trace file at `path`, replacing what's there. The file is
+    /// only readable by its owner, and is kept complete after each exchange, so it can be read even
+    /// if the program doesn't finish.
+    pub fn with_trace_file(mut self, path: &Path) -> io::Result<Self> {
+        self.trace = Some(Arc::new(Mutex::new(TraceFile::create(path)?)));
+        Ok(self)
+    }
+
+    pub fn level(&self) -> LogLevel {
+        self.level
+    }
+
+    /// Whether anything is logged or traced at all.
+    pub fn is_enabled(&self) -> bool {
+        self.level > LogLevel::Quiet || self.trace.is_some()
+    }
+
+    /// Writes the message, a line at a time, if the logger's level includes `level`.
+    pub fn log(&self, level: LogLevel, message: &str) {
+        if level == LogLevel::Quiet || self.level < level {
+            return;
+        }
+        let mut lines = String::new();
+        for line in message.lines() {
+            lines.push_str(line);
+            lines.push('\n');
+        }
+        // a log that can't be written isn't worth failing what's being logged over
+        let _ = match &self.output {
+            Some(output) => output.lock().map(|mut output| output.write_all(lines.as_bytes())),
+            None => Ok(io::stderr().write_all(lines.as_bytes())),
+        };
+    }
+
+    /// Logs a note about what the client is doing, shown from `-v`.
+    pub fn verbose(&self, message: &str) {
+        self.log(LogLevel::Verbose, &format!("* {}", message));
+    }
+
+    fn trace(&self, entry: HarEntry) {
+        let trace = match &self.trace {
+            Some(trace) => trace,
+            None => return,
+        };
+        let written = match trace.lock() {
+            Ok(mut trace) => trace.append(&entry),
+            Err(_) => return,
+        };
+        if let Err(e) = written {
+            self.verbose(&format!("Unable to write the trace file: {}", e));
+        }
+    }
+}
+
+/// The end of the trace file after its last entry, which each new entry is written over.
+const TRACE_END: &str = "\n]}}\n";
+
+struct TraceFile {
+    file: File,
+    entries: usize,
+}
+
+impl TraceFile {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // the mode only applies to new files, an old trace may have been readable by others
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        let creator = HarCreator { name: env!("CARGO_PKG_NAME"), version: env!("CARGO_PKG_VERSION") };
+        write!(file, "{{\"log\":{{\"version\":\"1.2\",\"creator\":{},\"entries\":[{}", serde_json::to_string(&creator)?, TRACE_END)?;
+        Ok(TraceFile { file, entries: 0 })
+    }
+
+    /// Adds the entry in place of the end of the file, then ends the file again.
+    fn append(&mut self, entry: &HarEntry) -> io::Result<()> {
+        let mut written = if self.entries == 0 { "\n".to_string() } else { ",\n".to_string() };
+        written.push_str(&serde_json::to_string(entry)?);
+        written.push_str(TRACE_END);
+        self.file.seek(SeekFrom::End(-(TRACE_END.len() as i64)))?;
+        self.file.write_all(written.as_bytes())?;
+        self.entries += 1;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct HarCreator {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: String,
+    /// Milliseconds from sending the request to receiving the whole response.
+    time: u64,
+    request: HarRequest,
+    response: HarResponse,
+    /// Why no response was received, for requests that failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: &'static str,
+    url: String,
+    http_version: &'static str,
+    headers: Vec<HarHeader>,
+    body_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_data: Option<HarContent>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarResponse {
+    /// 0 when no response was received.
+    status: u16,
+    http_version: &'static str,
+    headers: Vec<HarHeader>,
+    content: HarContent,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarContent {
+    size: u64,
+    mime_type: String,
+    /// The start of the body, see `BodyCapture::text`.
+    text: String,
+}
+
+#[derive(Serialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+/// Headers sorted by name, with credentials redacted.
+fn loggable_headers<'a, I: IntoIterator<Item = (&'a String, &'a String)>>(headers: I) -> Vec<HarHeader> {
+    let mut loggable: Vec<HarHeader> = headers
+        .into_iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.to_lowercase().as_str()) { "[redacted]".to_string() } else { value.clone() };
+            HarHeader { name: name.clone(), value }
+        })
+        .collect();
+    loggable.sort_by_key(|header| header.name.to_lowercase());
+    loggable
+}
+
+/// The start of a body, up to `MAX_LOGGED_BODY`, along with its full size.
+#[derive(Default)]
+struct BodyCapture {
+    start: Vec<u8>,
+    size: u64,
+}
+
+impl BodyCapture {
+    fn of(body: &[u8]) -> Self {
+        let mut capture = BodyCapture::default();
+        capture.add(body);
+        capture
+    }
+
+    fn add(&mut self, data: &[u8]) {
+        let room = MAX_LOGGED_BODY - self.start.len();
+        self.start.extend_from_slice(&data[..room.min(data.len())]);
+        self.size += data.len() as u64;
+    }
+
+    /// The body as text, cut off after `MAX_LOGGED_BODY` bytes, or a description of it if it's binary.
+    fn text(&self) -> String {
+        let text = match std::str::from_utf8(&self.start) {
+            Ok(text) => text,
+            // the cut off can be part way through a character
+            Err(e) if e.error_len().is_none() => std::str::from_utf8(&self.start[..e.valid_up_to()]).unwrap_or_default(),
+            Err(_) => return format!("({} bytes of binary data)", self.size),
+        };
+        if (self.start.len() as u64) < self.size {
+            format!("{}... ({} bytes in total)", text, self.size)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn har_content(&self, mime_type: Option<&str>) -> HarContent {
+        HarContent { size: self.size, mime_type: mime_type.unwrap_or_default().to_string(), text: self.text() }
+    }
+}
+
+/// Prefixes every line of the text, ie `> ` for what was sent and `< ` for what was received.
+fn prefix_lines(prefix: &str, text: &str) -> String {
+    text.lines().map(|line| format!("{}{}\n", prefix, line)).collect()
+}
+
+/// A request that's been sent, which is logged as it goes and traced once its response has been read.
+struct Exchange {
+    logger: Logger,
+    started_at: SystemTime,
+    started: Instant,
+    method: &'static str,
+    url: String,
+    request_headers: Vec<HarHeader>,
+    request_body: BodyCapture,
+}
+
+impl Exchange {
+    fn start(logger: &Logger, method: &'static str, url: String, request_headers: Vec<HarHeader>) -> Self {
+        let mut head = format!("> {} {}\n", method, url);
+        for header in &request_headers {
+            head.push_str(&format!("> {}: {}\n", header.name, header.value));
+        }
+        logger.log(LogLevel::Debug, &head);
+        Exchange { logger: logger.clone(), started_at: SystemTime::now(), started: Instant::now(), method, url, request_headers, request_body: BodyCapture::default() }
+    }
+
+    fn elapsed_millis(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn sent_body(&mut self, body: BodyCapture) {
+        if body.size > 0 {
+            self.logger.log(LogLevel::Debug, &format!(">\n{}", prefix_lines("> ", &body.text())));
+        }
+        self.request_body = body;
+    }
+
+    fn received_head(&self, status: u16, headers: &HashMap<String, String>) {
+        self.logger.log(LogLevel::Verbose, &format!("{} {} -> {} ({} ms)", self.method, self.url, status, self.elapsed_millis()));
+        let mut head = format!("< {}\n", status);
+        for header in loggable_headers(headers) {
+            head.push_str(&format!("< {}: {}\n", header.name, header.value));
+        }
+        self.logger.log(LogLevel::Debug, &head);
+    }
+
+    fn received_body(self, status: u16, headers: &HashMap<String, String>, body: &BodyCapture) {
+        if body.size > 0 {
+            self.logger.log(LogLevel::Debug, &format!("<\n{}", prefix_lines("< ", &body.text())));
+        }
+        let response = HarResponse {
+            status,
+            http_version: "HTTP/1.1",
+            headers: loggable_headers(headers),
+            content: body.har_content(headers.get("content-type").map(String::as_str)),
+        };
+        self.finish(response, None);
+    }
+
+    fn failed(self, error: &dyn Error) {
+        self.logger.log(LogLevel::Verbose, &format!("{} {} failed after {} ms: {}", self.method, self.url, self.elapsed_millis(), error));
+        let response = HarResponse { status: 0, http_version: "", headers: Vec::new(), content: BodyCapture::default().har_content(None) };
+        self.finish(response, Some(error.to_string()));
+    }
+
+    fn finish(self, response: HarResponse, comment: Option<String>) {
+        let mime_type = self.request_headers.iter().find(|header| header.name.eq_ignore_ascii_case("Content-Type")).map(|header| header.value.as_str());
+        let post_data = Some(self.request_body.har_content(mime_type)).filter(|body| body.size > 0);
+        let entry = HarEntry {
+            started_date_time: utils::format_iso8601_local_time(self.started_at),
+            time: self.elapsed_millis(),
+            request: HarRequest {
+                method: self.method,
+                url: self.url,
+                http_version: "HTTP/1.1",
+                body_size: self.request_body.size,
+                headers: self.request_headers,
+                post_data,
+            },
+            response,
+            comment,
+        };
+        self.logger.trace(entry);
+    }
+}
+
+/// Captures the start of a request body as it's read to be sent.
+struct CapturingReader<R> {
+    inner: R,
+    capture: Arc<Mutex<BodyCapture>>,
+}
+
+impl<R: Read> Read for CapturingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Ok(mut capture) = self.capture.lock() {
+            capture.add(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+/// A streamed response body, which is logged and traced once the caller is done with it.
+struct LoggedBody {
+    inner: Box<dyn Read + Send>,
+    capture: BodyCapture,
+    exchange: Option<Exchange>,
+    status: u16,
+    headers: HashMap<String, String>,
+}
+
+impl Read for LoggedBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.capture.add(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        if let Some(exchange) = self.exchange.take() {
+            exchange.received_body(self.status, &self.headers, &self.capture);
+        }
+    }
+}
+
+/// An `HttpRequest` that logs the requests sent with another one, and their responses, to a `Logger`.
+/// Streamed response bodies are logged once they've been read (or dropped), with however much was read.
+#[derive(Clone)]
+pub struct LoggingHttpClient<T> {
+    inner: T,
+    logger: Logger,
+    base_url: String,
+    method: &'static str,
+    path: String,
+    headers: HashMap<String, String>,
+}
+
+impl<T> LoggingHttpClient<T> {
+    /// `base_url` is what request paths are logged relative to, ie `https://cloud.example.com`.
+    pub fn new(inner: T, logger: Logger, base_url: String) -> Self {
+        LoggingHttpClient { inner, logger, base_url, method: "GET", path: "/".to_string(), headers: HashMap::new() }
+    }
+
+    fn start_exchange(&self) -> Exchange {
+        Exchange::start(&self.logger, self.method, format!("{}{}", self.base_url, self.path), loggable_headers(&self.headers))
+    }
+}
+
+impl<T: HttpRequest> HttpRequest for LoggingHttpClient<T> {
+    fn set_request(mut self, req_type: RequestType, remote_uri: &str) -> Self {
+        self.method = req_type.method();
+        self.path = remote_uri.to_string();
+        self.inner = self.inner.set_request(req_type, remote_uri);
+        self
+    }
+
+    fn set_header(mut self, header: String, value: String) -> Self {
+        self.headers.insert(header.clone(), value.clone());
+        self.inner = self.inner.set_header(header, value);
+        self
+    }
+
+    fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        // only ever logged redacted, so the credentials themselves aren't kept
+        self.headers.insert("Authorization".to_string(), String::new());
+        self.inner = self.inner.basic_auth(user, password);
+        self
+    }
+
+    fn send_bytes(self, bytes: &[u8]) -> Result<HttpResponse, Box<dyn Error>> {
+        if !self.logger.is_enabled() {
+            return self.inner.send_bytes(bytes);
+        }
+        let mut exchange = self.start_exchange();
+        exchange.sent_body(BodyCapture::of(bytes));
+        match self.inner.send_bytes(bytes) {
+            Ok(response) => {
+                exchange.received_head(response.response_code, &response.headers);
+                exchange.received_body(response.response_code, &response.headers, &BodyCapture::of(response.response_msg.as_bytes()));
+                Ok(response)
+            },
+            Err(e) => {
+                exchange.failed(e.as_ref());
+                Err(e)
+            },
+        }
+    }
+
+    fn send_stream<R: Read>(self, body: R, length: Option<u64>) -> Result<StreamingResponse, Box<dyn Error>> {
+        if !self.logger.is_enabled() {
+            return self.inner.send_stream(body, length);
+        }
+        let mut exchange = self.start_exchange();
+        let capture = Arc::new(Mutex::new(BodyCapture::default()));
+        let result = self.inner.send_stream(CapturingReader { inner: body, capture: Arc::clone(&capture) }, length);
+        if let Ok(mut capture) = capture.lock() {
+            exchange.sent_body(std::mem::take(&mut *capture));
+        }
+        match result {
+            Ok(mut response) => {
+                exchange.received_head(response.response_code, &response.headers);
+                let inner = std::mem::replace(&mut response.body, Box::new(io::empty()));
+                response.body = Box::new(LoggedBody {
+                    inner,
+                    capture: BodyCapture::default(),
+                    exchange: Some(exchange),
+                    status: response.response_code,
+                    headers: response.headers.clone(),
+                });
+                Ok(response)
+            },
+            Err(e) => {
+                exchange.failed(e.as_ref());
+                Err(e)
+            },
+        }
+    }
+}
+
+/// Logs the requests of the async counterpart of the transport to the same logger.
+#[cfg(feature = "async")]
+impl<T: ToAsync> ToAsync for LoggingHttpClient<T> {
+    type Async = LoggingHttpClient<T::Async>;
+
+    fn to_async(&self) -> Self::Async {
+        LoggingHttpClient::new(self.inner.to_async(), self.logger.clone(), self.base_url.clone())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: AsyncHttpRequest + Send> AsyncHttpRequest for LoggingHttpClient<T> {
+    fn set_request(mut self, req_type: RequestType, remote_uri: &str) -> Self {
+        self.method = req_type.method();
+        self.path = remote_uri.to_string();
+        self.inner = self.inner.set_request(req_type, remote_uri);
+        self
+    }
+
+    fn set_header(mut self, header: String, value: String) -> Self {
+        self.headers.insert(header.clone(), value.clone());
+        self.inner = self.inner.set_header(header, value);
+        self
+    }
+
+    fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        self.headers.insert("Authorization".to_string(), String::new());
+        self.inner = self.inner.basic_auth(user, password);
+        self
+    }
+
+    async fn send_bytes(self, body: Vec<u8>) -> Result<AsyncResponse, AsyncError> {
+        if !self.logger.is_enabled() {
+            return self.inner.send_bytes(body).await;
+        }
+        let mut exchange = self.start_exchange();
+        exchange.sent_body(BodyCapture::of(&body));
+        match self.inner.send_bytes(body).await {
+            Ok(response) => {
+                exchange.received_head(response.response_code, &response.headers);
+                exchange.received_body(response.response_code, &response.headers, &BodyCapture::of(&response.body));
+                Ok(response)
+            },
+            Err(e) => {
+                exchange.failed(e.as_ref());
+                Err(e)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use crate::testserver::{Reply, TestServer, HOST};
+    use crate::httprequest::LiteHttpClient;
+
+    fn logging_client(server: &TestServer, logger: &Logger) -> LoggingHttpClient<LiteHttpClient> {
+        let inner = LiteHttpClient::new(HOST.to_string(), server.port()).with_ssl_connector(server.ssl_connector());
+        LoggingHttpClient::new(inner, logger.clone(), "https://cloud.example.com".to_string())
+    }
+
+    fn logged(output: &Arc<Mutex<Vec<u8>>>) -> String {
+        String::from_utf8(output.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn should_log_method_url_and_status_when_verbose() {
+        let server = TestServer::start(|_| Reply::new(201, b"created").with_header("X-Request-Id", "abc"));
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let logger = Logger::new(LogLevel::Verbose).with_output(output.clone());
+
+        logging_client(&server, &logger).set_request(RequestType::PUT, "/a.md").basic_auth("user", "password").send_bytes(b"body").unwrap();
+
+        let logged = logged(&output);
+        assert!(logged.starts_with("PUT https://cloud.example.com/a.md -> 201 ("), "{}", logged);
+        assert!(logged.ends_with(" ms)\n"));
+        assert_eq!(logged.lines().count(), 1);
+    }
+
+    #[test]
+    fn should_log_redacted_headers_and_truncated_bodies_when_debugging() {
+        let large_body = "x".repeat(MAX_LOGGED_BODY + 10);
+        let reply_body = large_body.clone();
+        let server = TestServer::start(move |_| Reply::new(500, reply_body.as_bytes()).with_header("Set-Cookie", "session=secret"));
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let logger = Logger::new(LogLevel::Debug).with_output(output.clone());
+
+        let response = logging_client(&server, &logger)
+            .set_request(RequestType::PUT, "/a.md")
+            .basic_auth("user", "password")
+            .set_header("Content-Type".to_string(), "text/markdown".to_string())
+            .send_stream(Cursor::new(b"# Title".to_vec()), None)
+            .unwrap();
+        let mut body = String::new();
+        response.body.take(10).read_to_string(&mut body).unwrap();
+
+        let logged = logged(&output);
+        assert!(logged.contains("> PUT https://cloud.example.com/a.md\n> Authorization: [redacted]\n> Content-Type: text/markdown\n>\n> # Title\n"), "{}", logged);
+        assert!(logged.contains("< set-cookie: [redacted]\n"));
+        assert!(!logged.contains("password") && !logged.contains("secret"));
+        // only what was read of a streamed body is logged
+        assert!(logged.ends_with("<\n< xxxxxxxxxx\n"), "{}", logged);
+
+        let full = BodyCapture::of(large_body.as_bytes()).text();
+        assert!(full.ends_with(&format!("x... ({} bytes in total)", MAX_LOGGED_BODY + 10)));
+        assert_eq!(BodyCapture::of(&[0xff, 0xfe, 0]).text(), "(3 bytes of binary data)");
+    }
+
+    #[test]
+    fn should_write_every_exchange_to_trace_file() {
+        let server = TestServer::start(|request| match request.path.as_str() {
+            "/missing.md" => Reply::new(404, b"not found").with_header("Content-Type", "text/plain"),
+            _ => Reply::new(201, b""),
+        });
+        let trace_path = std::env::temp_dir().join(format!("nxcloudnotes-trace-{}.har", server.port()));
+        fs::write(&trace_path, "an older trace, longer than the new one is at first").unwrap();
+        let logger = Logger::new(LogLevel::Quiet).with_trace_file(&trace_path).unwrap();
+        let client = logging_client(&server, &logger);
+
+        let trace: serde_json::Value = serde_json::from_slice(&fs::read(&trace_path).unwrap()).unwrap();
+        assert_eq!(trace["log"]["entries"].as_array().unwrap().len(), 0);
+        client.clone().set_request(RequestType::PUT, "/a.md").basic_auth("user", "password").send_bytes(b"content").unwrap();
+        let trace: serde_json::Value = serde_json::from_slice(&fs::read(&trace_path).unwrap()).unwrap();
+        assert_eq!(trace["log"]["entries"].as_array().unwrap().len(), 1);
+        client.clone().set_request(RequestType::GET, "/missing.md").send_bytes(&[]).unwrap();
+        let unreachable = LoggingHttpClient::new(LiteHttpClient::new("127.0.0.1".to_string(), 1), logger.clone(), "https://127.0.0.1:1".to_string());
+        assert!(unreachable.set_request(RequestType::GET, "/").send_bytes(&[]).is_err());
+
+        let trace: serde_json::Value = serde_json::from_slice(&fs::read(&trace_path).unwrap()).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&trace_path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        fs::remove_file(&trace_path).unwrap();
+        assert_eq!(trace["log"]["creator"]["name"], env!("CARGO_PKG_NAME"));
+        let entries = trace["log"]["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0]["request"]["method"], "PUT");
+        assert_eq!(entries[0]["request"]["url"], "https://cloud.example.com/a.md");
+        assert_eq!(entries[0]["request"]["headers"][0]["value"], "[redacted]");
+        assert_eq!(entries[0]["request"]["postData"]["text"], "content");
+        assert_eq!(entries[0]["response"]["status"], 201);
+        assert_eq!(entries[1]["response"]["status"], 404);
+        assert_eq!(entries[1]["response"]["content"]["text"], "not found");
+        assert_eq!(entries[1]["response"]["content"]["mimeType"], "text/plain");
+        assert_eq!(entries[2]["response"]["status"], 0);
+        assert_eq!(entries[2]["comment"], "Unable to connect to given host");
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn should_log_requests_sent_with_async_counterpart() {
+        let server = TestServer::start(|_| Reply::new(207, b"listing"));
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let logger = Logger::new(LogLevel::Verbose).with_output(output.clone());
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        let request = logging_client(&server, &logger).to_async().set_request(RequestType::PROPFIND, "/Notes/");
+        let response = runtime.block_on(request.send_bytes(vec![])).unwrap();
+
+        assert_eq!(response.body, b"listing");
+        let logged = logged(&output);
+        assert!(logged.starts_with("PROPFIND https://cloud.example.com/Notes/ -> 207 ("), "{}", logged);
+    }
+}
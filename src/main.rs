@@ -1,24 +1,76 @@
-use nxcloudnotes::cliarguments::PostNoteCLIConfig;
+use nxcloudnotes::cliarguments::{ConnectionCLIConfig, ListNotesCLIConfig, LoggingCLIConfig, NoteVersionsCLIConfig, PasswordSource, PostNoteCLIConfig, ReadNoteCLIConfig, RestoreVersionCLIConfig, TrashAction, TrashCLIConfig};
 use directories_next::ProjectDirs;
 use nxcloudnotes::configcreator::ask_user_for_config;
 use nxcloudnotes::configprovider::NxCloudConfigRetriever;
 use nxcloudnotes::configprovider::FileSystemNxCloudConfig;
-use nxcloudnotes::httprequest::LiteHttpClient;
-use nxcloudnotes::nextcloudclient::NextCloudClient;
+use nxcloudnotes::configprovider::{NxCloudNotesConfigData, PASSWORD_ENV_VAR};
+use nxcloudnotes::httprequest::ProgressReader;
+#[cfg(not(feature = "backend-ureq"))]
+use nxcloudnotes::httprequest::LiteHttpClient as HttpClient;
+#[cfg(feature = "backend-ureq")]
+use nxcloudnotes::ureqclient::UreqHttpClient as HttpClient;
+use nxcloudnotes::logging::{LogLevel, Logger, LoggingHttpClient};
+use nxcloudnotes::nextcloudclient::{self, CollisionPolicy, NextCloudClient};
+use nxcloudnotes::proxy::Proxy;
+use nxcloudnotes::resolver::{self, ResolveOverride};
 use nxcloudnotes::cliarguments;
-use std::io::{self};
-use std::time::{SystemTime, UNIX_EPOCH};
+use nxcloudnotes::browser::{Action, Browser};
+use nxcloudnotes::clipboard::{self, Clipboard, CommandClipboard};
+use nxcloudnotes::completions;
+use nxcloudnotes::frontmatter::{self, FrontMatter};
+use nxcloudnotes::terminal::{self, RawTerminal};
+use nxcloudnotes::{attachments, manpage, notes, utils};
+use nxcloudnotes::attachments::LocalAttachment;
+#[cfg(feature = "async")]
+use nxcloudnotes::asynchttp::ToAsync;
+#[cfg(feature = "async")]
+use nxcloudnotes::asyncnextcloudclient::{self, AsyncNextCloudClient};
+use std::error::Error;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::process::Command;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// What every command talks to the server with, which logs the requests it sends when asked to.
+type Client = LoggingHttpClient<HttpClient>;
 
 fn main() {
     let pattern: Vec<String> = std::env::args().collect();
-    let command = cliarguments::parse_args(pattern).unwrap();
+    let command = match cliarguments::parse_args(pattern) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
     let config_project_dir = ProjectDirs::from("com", "", "NxCloudNotes")
     .expect("No valid home directory set for the system. Config cannot be saved. App exiting...");
     let config_dir_path = config_project_dir.config_dir().to_owned();
     let config_provider = FileSystemNxCloudConfig::new(&config_dir_path, "app-config.toml");
 
-    if !config_provider.has_config().expect("An error occurred retrieving user config") {
+    let has_config = config_provider.has_config().expect("An error occurred retrieving user config");
+    match &command {
+        cliarguments::ProgramCommands::Help(help_text) => {
+            println!("{}", help_text);
+            return;
+        },
+        cliarguments::ProgramCommands::Completions(shell) => {
+            print!("{}", completions::completion_script(*shell));
+            return;
+        },
+        cliarguments::ProgramCommands::Man => {
+            print!("{}", manpage::man_page());
+            return;
+        },
+        // shell completion runs this in the background, where there's nobody to create the config
+        cliarguments::ProgramCommands::List(cli_config) if cli_config.names && !has_config => std::process::exit(1),
+        _ => {},
+    }
+
+    if !has_config {
         let stdio = io::stdin();
         let mut input = stdio.lock();
         let mut output = io::stdout(); 
@@ -27,10 +79,6 @@ fn main() {
     }
 
     match command {
-        cliarguments::ProgramCommands::Help(help_text) => {
-            println!("{}", help_text)
-        },
-
         cliarguments::ProgramCommands::ConfigPath => {
             let config_path_str = config_provider.config_path.into_os_string().into_string()
             .expect("Error getting config path");
@@ -39,36 +87,530 @@ fn main() {
         },
         cliarguments::ProgramCommands::PostNote(cli_config) => {
             handle_post_note_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::Categories(cli_config) => {
+            handle_categories_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::List(cli_config) => {
+            handle_list_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::Read(cli_config) => {
+            handle_read_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::Versions(cli_config) => {
+            handle_versions_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::Restore(cli_config) => {
+            handle_restore_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::Trash(cli_config) => {
+            handle_trash_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::Browse(cli_config) => {
+            handle_browse_command(config_provider, cli_config)
+        },
+        cliarguments::ProgramCommands::Help(_)
+        | cliarguments::ProgramCommands::Completions(_)
+        | cliarguments::ProgramCommands::Man => {}
+    }
+}
+
+/// The password to connect with: the one saved in the config file, otherwise the one from the command line,
+/// stdin or the environment, otherwise the user is asked for it if `can_prompt`. Exits if there's none.
+fn resolve_password(config_data: &NxCloudNotesConfigData, cli_password: PasswordSource, can_prompt: bool) -> String {
+    if let Some(password) = &config_data.password {
+        return password.clone();
+    }
+    let result = match cli_password {
+        PasswordSource::Argument(password) => {
+            eprintln!(
+                "Warning: --password is deprecated, as it leaves the password in shell history and the process list. \
+                Use --password-stdin, set {}, or type it in when asked instead.",
+                PASSWORD_ENV_VAR
+            );
+            Ok(password)
+        },
+        PasswordSource::Stdin => terminal::read_password_line(&mut io::stdin().lock()).map_err(Into::into),
+        PasswordSource::Unset => match std::env::var(PASSWORD_ENV_VAR) {
+            Ok(password) if !password.is_empty() => Ok(password),
+            _ if can_prompt => {
+                let prompt = format!("Password for {} on {}: ", config_data.user_name, config_data.server_address);
+                terminal::read_password(&prompt).map_err(Into::into)
+            },
+            _ => Err(Box::<dyn Error>::from("none is saved in the config file")),
+        },
+    };
+    match result {
+        Ok(password) => password,
+        Err(e) => {
+            eprintln!("Unable to get the NextCloud password: {}", e);
+            eprintln!("Give it with --password-stdin or in the {} environment variable.", PASSWORD_ENV_VAR);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn create_logger(logging: &LoggingCLIConfig) -> Logger {
+    let logger = Logger::new(LogLevel::from_verbosity(logging.verbosity));
+    match &logging.trace_file {
+        Some(trace_file) => match logger.with_trace_file(Path::new(trace_file)) {
+            Ok(logger) => logger,
+            Err(e) => {
+                eprintln!("Unable to create the trace file {}: {}", trace_file, e);
+                std::process::exit(1);
+            }
+        },
+        None => logger,
+    }
+}
+
+fn create_nextcloud_client(config_data: &NxCloudNotesConfigData, password: String, logging: &LoggingCLIConfig) -> NextCloudClient<Client> {
+    let mut http_client = HttpClient::new(config_data.server_address.clone(), config_data.port);
+    match Proxy::from_config_or_env(config_data.proxy.as_deref()) {
+        Ok(Some(proxy)) => http_client = http_client.with_proxy(proxy),
+        Ok(None) => {},
+        Err(e) => {
+            eprintln!("The proxy can't be used: {}", e);
+            std::process::exit(1);
+        }
+    }
+    let resolve_overrides = config_data.resolve.iter().flatten().map(|entry| entry.parse()).collect::<Result<Vec<ResolveOverride>, String>>();
+    match resolve_overrides {
+        Ok(resolve_overrides) => http_client = http_client.with_resolve(resolve_overrides),
+        Err(e) => {
+            eprintln!("The resolve setting in the config file can't be used: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(min_length) = config_data.upload_compression_threshold {
+        #[cfg(not(feature = "backend-ureq"))]
+        {
+            http_client = http_client.with_upload_compression(min_length);
+        }
+        #[cfg(feature = "backend-ureq")]
+        eprintln!("Uploads of {} bytes or more aren't compressed, as the ureq backend doesn't support upload_compression_threshold", min_length);
+    }
+    let logger = create_logger(logging);
+    let base_url = format!("https://{}", resolver::host_header(&config_data.server_address, config_data.port));
+    let http_client = LoggingHttpClient::new(http_client, logger.clone(), base_url);
+    let chunked_upload_threshold = config_data.chunked_upload_threshold
+        .unwrap_or(nextcloudclient::DEFAULT_CHUNKED_UPLOAD_THRESHOLD);
+    NextCloudClient::new(http_client, config_data.user_name.clone(), password)
+        .with_chunked_uploads(chunked_upload_threshold, nextcloudclient::DEFAULT_CHUNK_SIZE)
+        .with_logger(logger)
+}
+
+fn handle_categories_command(config_provider: FileSystemNxCloudConfig, cli_config: ConnectionCLIConfig) {
+    let config_data = config_provider.load_config().unwrap();
+    let password = resolve_password(&config_data, cli_config.password, true);
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+
+    match nextcloud_client.list_categories(&format!("/{}", config_data.base_notes_directory)) {
+        Ok(categories) => {
+            for category in categories {
+                println!("{}", category);
+            }
+        },
+        Err(e) => {
+            println!("an error occured! {}", e);
+        }
+    }
+}
+
+fn handle_list_command(config_provider: FileSystemNxCloudConfig, cli_config: ListNotesCLIConfig) {
+    let config_data = config_provider.load_config().unwrap();
+    // completion runs this in the background, where there's nobody to type in the password
+    let password = resolve_password(&config_data, cli_config.password, !cli_config.names);
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+    let notes_directory = format!("/{}", config_data.base_notes_directory);
+    let tags = &cli_config.tags;
+
+    let result = nextcloud_client.list_notes(&notes_directory).and_then(|note_paths| {
+        if tags.is_empty() {
+            return Ok(note_paths);
+        }
+        // tags are only recorded in the notes themselves, so each one has to be read to filter by them
+        let full_paths = note_paths.iter().map(|note_path| format!("{}/{}", notes_directory, note_path)).collect();
+        let contents = read_notes(&nextcloud_client, full_paths, config_data.parallelism)?;
+        let mut tagged = Vec::new();
+        for (note_path, content) in note_paths.into_iter().zip(contents) {
+            if let (Some(front_matter), _) = frontmatter::split(&content) {
+                if front_matter.has_tags(tags) {
+                    tagged.push(note_path);
+                }
+            }
+        }
+        Ok(tagged)
+    });
+
+    match result {
+        Ok(note_paths) => {
+            for note_path in note_paths {
+                if cli_config.names {
+                    println!("{}", notes::note_title_from_path(&note_path));
+                } else {
+                    println!("{}", note_path);
+                }
+            }
+        },
+        Err(e) if cli_config.names => {
+            // anything on stdout would be offered as a completion
+            eprintln!("an error occured! {}", e);
+        },
+        Err(e) => {
+            println!("an error occured! {}", e);
+        }
+    }
+}
+
+fn handle_read_command(config_provider: FileSystemNxCloudConfig, cli_config: ReadNoteCLIConfig) {
+    let config_data = config_provider.load_config().unwrap();
+    let password = resolve_password(&config_data, cli_config.password, true);
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+    let notes_directory = format!("/{}", config_data.base_notes_directory);
+    let title = title_in_category(cli_config.title, cli_config.category.as_deref());
+
+    let result = find_note_path(&nextcloud_client, &notes_directory, &title)
+        .and_then(|note_path| read_note(&nextcloud_client, &note_path));
+
+    let copy = cli_config.copy;
+    let no_meta = cli_config.no_meta;
+    let result = result.map(|content| {
+        if no_meta {
+            frontmatter::split(&content).1.to_string()
+        } else {
+            content
+        }
+    }).and_then(|content| {
+        if copy {
+            CommandClipboard::detect()?.copy(&content)?;
+        }
+        Ok(content)
+    });
+
+    match result {
+        Ok(content) => {
+            if copy {
+                println!("copied {} to the clipboard", title);
+            } else {
+                print!("{}", content);
+            }
+        },
+        Err(e) => {
+            println!("an error occured! {}", e);
+        }
+    }
+}
+
+fn handle_versions_command(config_provider: FileSystemNxCloudConfig, cli_config: NoteVersionsCLIConfig) {
+    let config_data = config_provider.load_config().unwrap();
+    let password = resolve_password(&config_data, cli_config.password, true);
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+    let notes_directory = format!("/{}", config_data.base_notes_directory);
+    let title = title_in_category(cli_config.title, cli_config.category.as_deref());
+
+    let result = find_note_path(&nextcloud_client, &notes_directory, &title)
+        .and_then(|note_path| nextcloud_client.list_versions(&note_path));
+
+    match result {
+        Ok(versions) if versions.is_empty() => {
+            println!("{} has no previous versions", title);
+        },
+        Ok(versions) => {
+            for version in versions {
+                let modified = version.modified.unwrap_or_default();
+                let size = version.size.map_or(String::new(), |size| format!("{} bytes", size));
+                println!("{}  {}  {}", version.id, modified, size);
+            }
+        },
+        Err(e) => {
+            println!("an error occured! {}", e);
+        }
+    }
+}
+
+fn handle_restore_command(config_provider: FileSystemNxCloudConfig, cli_config: RestoreVersionCLIConfig) {
+    let config_data = config_provider.load_config().unwrap();
+    let password = resolve_password(&config_data, cli_config.password, true);
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+    let notes_directory = format!("/{}", config_data.base_notes_directory);
+    let title = title_in_category(cli_config.title, cli_config.category.as_deref());
+    let version = cli_config.version;
+
+    let result = find_note_path(&nextcloud_client, &notes_directory, &title)
+        .and_then(|note_path| nextcloud_client.restore_version(&note_path, &version));
+
+    match result {
+        Ok(()) => {
+            println!("restored {} to version {}", title, version);
+        },
+        Err(e) => {
+            println!("an error occured! {}", e);
         }
     }
 }
 
-fn handle_post_note_command(config_provider: FileSystemNxCloudConfig, cli_config: PostNoteCLIConfig) {
+fn handle_trash_command(config_provider: FileSystemNxCloudConfig, cli_config: TrashCLIConfig) {
     let config_data = config_provider.load_config().unwrap();
+    let password = resolve_password(&config_data, cli_config.password, true);
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+    let notes_directory = format!("/{}", config_data.base_notes_directory);
+    let yes = cli_config.yes;
 
-    let password = match config_data.password {
-        Some(password_string) => password_string,
-        None => cli_config.password.expect("You must provide a password with the -p argument if none is present in the applications config file"),
+    let result = match cli_config.action {
+        TrashAction::List => nextcloud_client.list_trash(&notes_directory).map(|items| {
+            if items.is_empty() {
+                println!("there are no deleted notes in the trash bin");
+            }
+            for item in items {
+                let deleted = item.deleted.map_or(String::new(), |deleted| utils::format_local_time(deleted, "%Y-%m-%d %H:%M"));
+                println!("{}  {}  {}", item.name, item.original_location, deleted);
+            }
+        }),
+        TrashAction::Restore(name) => nextcloud_client.restore_from_trash(&name).map(|_| {
+            println!("restored {}", name);
+        }),
+        TrashAction::Empty => nextcloud_client.list_trash(&notes_directory).and_then(|items| {
+            if items.is_empty() {
+                println!("there are no deleted notes in the trash bin");
+                return Ok(());
+            }
+            let prompt = format!("Permanently delete the {} item(s) in the trash bin? [y/N] ", items.len());
+            if !yes && !terminal::confirm(&prompt).map_err(|e| format!("{}, give --yes to empty the trash bin without asking", e))? {
+                println!("left the trash bin as it was");
+                return Ok(());
+            }
+            nextcloud_client.delete_from_trash(&items)?;
+            println!("permanently deleted {} item(s) from the trash bin", items.len());
+            Ok(())
+        }),
     };
- 
-    let title = match cli_config.title {
-        Some(title_string) => title_string,
-        None => {
-            let start = SystemTime::now();
-            let unix_time_since_epoch = start
-                .duration_since(UNIX_EPOCH)
-                .expect("Unable to generate unix timestamp for note title");
-            unix_time_since_epoch.as_millis().to_string()
+
+    if let Err(e) = result {
+        println!("an error occured! {}", e);
+    }
+}
+
+fn handle_browse_command(config_provider: FileSystemNxCloudConfig, cli_config: ConnectionCLIConfig) {
+    let config_data = config_provider.load_config().unwrap();
+    let password = resolve_password(&config_data, cli_config.password, true);
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+    let extension = config_data.note_extension.as_deref()
+        .map(notes::normalise_extension)
+        .unwrap_or_else(|| notes::DEFAULT_NOTE_EXTENSION.to_string());
+
+    let mut browser = Browser::new(nextcloud_client, &format!("/{}", config_data.base_notes_directory), &extension);
+    if let Err(e) = browser.refresh() {
+        println!("an error occured! {}", e);
+        return;
+    }
+    let result = RawTerminal::enter()
+        .map_err(|e| format!("Unable to start the browser: {}", e).into())
+        .and_then(|mut raw_terminal| run_browser(&mut browser, &mut raw_terminal));
+    if let Err(e) = result {
+        println!("an error occured! {}", e);
+    }
+}
+
+fn run_browser(browser: &mut Browser<Client>, raw_terminal: &mut RawTerminal) -> Result<(), Box<dyn Error>> {
+    let mut stdout = io::stdout();
+    loop {
+        browser.load_preview();
+        let (width, height) = raw_terminal.size();
+        terminal::draw(&mut stdout, &browser.render(width, height))?;
+        for key in raw_terminal.read_keys()? {
+            match browser.handle_key(key) {
+                Action::Continue => {},
+                Action::Quit => return Ok(()),
+                Action::Edit(note) => {
+                    let result = edit_note(browser, raw_terminal, &note);
+                    if let Err(e) = result {
+                        browser.set_status(format!("an error occured! {}", e));
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Lets the user edit the note in their editor, through a temporary file, then saves it if it was changed.
+fn edit_note(browser: &mut Browser<Client>, raw_terminal: &mut RawTerminal, note: &str) -> Result<(), Box<dyn Error>> {
+    let original = if browser.has_note(note) { browser.read_note(note)? } else { String::new() };
+    // the file keeps the note's name, so the editor can pick its syntax highlighting from the extension
+    let file_name = note.rsplit('/').next().unwrap_or(note);
+    let temp_dir = utils::PrivateTempDir::new("nxcloudnotes")?;
+    let (temp_path, mut temp_file) = temp_dir.create_file(file_name)?;
+    temp_file.write_all(original.as_bytes())?;
+    drop(temp_file);
+
+    let edited = raw_terminal.suspend(|| {
+        run_editor(&temp_path)?;
+        Ok::<String, Box<dyn Error>>(std::fs::read_to_string(&temp_path)?)
+    })?;
+    browser.save_note(note, &original, &edited?).map_err(|e| {
+        // the note wasn't saved, so the edits are left where the user can get them back
+        temp_dir.keep();
+        format!("{}, the edited note is in {}", e, temp_path.display()).into()
+    })
+}
+
+/// Runs the editor from `$VISUAL` or `$EDITOR` (which can include arguments, ie `code --wait`), or vi if neither is set.
+fn run_editor(path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+    let editor = std::env::var("VISUAL").ok().filter(|editor| !editor.trim().is_empty())
+        .or_else(|| std::env::var("EDITOR").ok().filter(|editor| !editor.trim().is_empty()))
+        .unwrap_or_else(|| String::from("vi"));
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("Unable to run the editor {}: {}", program, e))?;
+    if !status.success() {
+        Err(format!("The editor exited with {}, so the note wasn't saved", status))?
+    }
+    Ok(())
+}
+
+/// The title to look a note up by, with the category given with --category in front of it.
+fn title_in_category(title: String, category: Option<&str>) -> String {
+    match category.and_then(notes::category_path) {
+        Some(category) => format!("{}/{}", category, title),
+        None => title,
+    }
+}
+
+/// Finds the note with the given title, returning its full path on the server.
+fn find_note_path(nextcloud_client: &NextCloudClient<Client>, notes_directory: &str, title: &str) -> Result<String, Box<dyn Error>> {
+    let note_paths = nextcloud_client.list_notes(notes_directory)?;
+    let note_path = notes::find_note(&note_paths, title)
+        .ok_or(format!("No note called {} was found", title))?;
+    Ok(format!("{}/{}", notes_directory, note_path))
+}
+
+fn read_note(nextcloud_client: &NextCloudClient<Client>, note_path: &str) -> Result<String, Box<dyn Error>> {
+    let mut response = nextcloud_client.download_file(note_path)?;
+    let mut content = Vec::new();
+    response.body.read_to_end(&mut content)?;
+    Ok(String::from_utf8_lossy(&content).to_string())
+}
+
+/// Reads every note, up to `parallelism` at once.
+#[cfg(feature = "async")]
+fn read_notes(nextcloud_client: &NextCloudClient<Client>, note_paths: Vec<String>, parallelism: Option<usize>) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut notes = Vec::new();
+    for content in run_bulk(nextcloud_client, parallelism, |bulk_client| async move { bulk_client.download_files(note_paths).await })? {
+        let content = content.map_err(|e| e.to_string())?;
+        notes.push(String::from_utf8_lossy(&content).to_string());
+    }
+    Ok(notes)
+}
+
+/// Reads every note, one after another, as requests can only be sent at once with the async feature.
+#[cfg(not(feature = "async"))]
+fn read_notes(nextcloud_client: &NextCloudClient<Client>, note_paths: Vec<String>, _parallelism: Option<usize>) -> Result<Vec<String>, Box<dyn Error>> {
+    note_paths.iter().map(|note_path| read_note(nextcloud_client, note_path)).collect()
+}
+
+/// Front matter recording the note's tags and where it came from. The hostname and working directory
+/// say more about the machine than the note, so they're only recorded if the config asks for them.
+fn note_front_matter(config_data: &NxCloudNotesConfigData, tags: Vec<String>, content_from_stdin: bool) -> FrontMatter {
+    let hostname = if config_data.front_matter_hostname.unwrap_or(false) { utils::hostname() } else { String::new() };
+    let cwd = if config_data.front_matter_cwd.unwrap_or(false) { std::env::current_dir().ok() } else { None };
+    FrontMatter {
+        tags,
+        hostname: if hostname.is_empty() { None } else { Some(hostname) },
+        cwd: cwd.map(|cwd| cwd.to_string_lossy().to_string()),
+        command: if content_from_stdin { utils::piped_command() } else { None },
+        created: Some(utils::format_iso8601_local_time(SystemTime::now())),
+    }
+}
+
+fn handle_post_note_command(config_provider: FileSystemNxCloudConfig, mut cli_config: PostNoteCLIConfig) {
+    let config_data = config_provider.load_config().unwrap();
+
+    let password = resolve_password(&config_data, cli_config.password, true);
+
+    let content_from_stdin = cli_config.content == "-";
+    if content_from_stdin {
+        let mut content = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut content) {
+            eprintln!("Unable to read the note from stdin: {}", e);
+            std::process::exit(1);
+        }
+        cli_config.content = content;
+    }
+    if cli_config.from_clipboard {
+        match CommandClipboard::detect().and_then(|clipboard| clipboard::note_from_clipboard(&clipboard)) {
+            Ok(content) => cli_config.content = content,
+            Err(e) => {
+                eprintln!("Unable to read the note from the clipboard: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let title_template = config_data.title_template.as_deref().unwrap_or(notes::DEFAULT_TITLE_TEMPLATE);
+    let title_context = notes::TitleContext::for_note(&cli_config.content);
+    let title = notes::note_title(cli_config.title.as_deref(), title_template, &title_context);
+    let extension = cli_config.extension
+        .or_else(|| config_data.note_extension.clone())
+        .map(|extension| notes::normalise_extension(&extension))
+        .unwrap_or_else(|| notes::DEFAULT_NOTE_EXTENSION.to_string());
+    let notes_directory = format!("/{}", config_data.base_notes_directory);
+    let category = cli_config.category
+        .or_else(|| config_data.default_category.clone())
+        .and_then(|category| notes::category_path(&category));
+    let note_directory = match &category {
+        Some(category) => format!("{}/{}", notes_directory, category),
+        None => notes_directory.clone(),
+    };
+    let note_path = format!("{}/{}.{}", note_directory, title, extension);
+    let note_content = if config_data.prepend_title_heading.unwrap_or(false) {
+        notes::with_title_heading(&title, &cli_config.content)
+    } else {
+        cli_config.content
+    };
+    let note_content = if cli_config.no_meta || !config_data.front_matter.unwrap_or(false) {
+        note_content
+    } else {
+        frontmatter::insert(&note_front_matter(&config_data, cli_config.tags, content_from_stdin), &note_content)
+    };
+    let nextcloud_client = create_nextcloud_client(&config_data, password, &cli_config.logging);
+
+    let attachments = match attachments::open_attachments(&cli_config.attachments) {
+        Ok(attachments) => attachments,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
     };
-    let note_path = format!("/{}/{}.txt", config_data.base_notes_directory, title).to_string();
-    let http_client = LiteHttpClient::new(config_data.server_address, config_data.port);
-    let nextcloud_client = NextCloudClient::new(http_client, config_data.user_name, password);
-    let result = nextcloud_client.create_or_replace_file(&note_path, cli_config.content.as_bytes());
+
+    let collision_policy = cli_config.collision_policy
+        .or(config_data.collision_policy)
+        .unwrap_or(CollisionPolicy::Overwrite);
+
+    let result = category
+        .map_or(Ok(()), |category| nextcloud_client.create_folders(&notes_directory, &category))
+        .and_then(|_| nextcloud_client.create_note(&note_path, note_content.as_bytes(), collision_policy))
+        .and_then(|note| {
+            let references = upload_attachments(&nextcloud_client, &note_directory, &note.path, attachments, config_data.parallelism)?;
+            if !references.is_empty() {
+                // the references are only added to the note as it was saved, so a note that was changed on
+                // the server in the meantime (or replaced despite the collision policy) is left alone
+                let content = attachments::append_references(&String::from_utf8_lossy(&note.content), &references);
+                let etag = note.etag.as_deref()
+                    .ok_or_else(|| format!("The server didn't send an etag for {}, so the attachment references couldn't be added safely", note.path))?;
+                if !nextcloud_client.replace_file_if_unchanged(&note.path, content.as_bytes(), etag)? {
+                    Err(format!("{} was changed on the server while the attachments were uploaded, so the references to them weren't added", note.path))?
+                }
+            }
+            Ok(note)
+        });
 
     match result {
-        Ok(_) => {
-            println!("successfully uploaded note");
+        Ok(note) => {
+            println!("successfully uploaded note to {}", note.path);
         },
         Err(e) => {
             // Would be better to return a customized error with a friendly message here.
@@ -76,3 +618,94 @@ fn handle_post_note_command(config_provider: FileSystemNxCloudConfig, cli_config
         }
     }
 }
+
+/// Uploads the files into the note's attachments folder, returning the Markdown references to add to the note body.
+/// The attachments folder is named after the note's file id, so the note must already exist.
+fn upload_attachments(
+    nextcloud_client: &NextCloudClient<Client>,
+    note_directory: &str,
+    note_path: &str,
+    attachments: Vec<LocalAttachment>,
+    parallelism: Option<usize>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if attachments.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let file_id = nextcloud_client.file_id(note_path)?;
+    let attachments_folder = attachments::attachments_folder_name(&file_id);
+    let folder_path = format!("{}/{}", note_directory, attachments_folder);
+    nextcloud_client.create_folder(&folder_path)?;
+
+    let references = attachments.iter()
+        .map(|attachment| attachments::markdown_reference(&attachment.file_name, &attachments_folder))
+        .collect();
+    let attachments = upload_small_attachments(nextcloud_client, &folder_path, attachments, parallelism)?;
+    for attachment in attachments {
+        let file_name = attachment.file_name;
+        let length = attachment.length;
+
+        println!("uploading {}", file_name);
+        let mut stderr = io::stderr();
+        let progress = ProgressReader::new(attachment.file, Some(length), |transferred, total| {
+            let _ = utils::render_progress_bar(&mut stderr, transferred, total);
+        });
+        let upload_path = format!("{}/{}", folder_path, file_name);
+        let result = nextcloud_client.upload_file(&upload_path, progress, Some(length));
+        eprintln!();
+        result?;
+    }
+    Ok(references)
+}
+
+/// Uploads the attachments that are small enough to hold in memory, up to `parallelism` at once,
+/// returning the rest to be streamed from their files one after another.
+#[cfg(feature = "async")]
+fn upload_small_attachments(
+    nextcloud_client: &NextCloudClient<Client>,
+    folder_path: &str,
+    attachments: Vec<LocalAttachment>,
+    parallelism: Option<usize>,
+) -> Result<Vec<LocalAttachment>, Box<dyn Error>> {
+    let (small, large): (Vec<LocalAttachment>, Vec<LocalAttachment>) = attachments
+        .into_iter()
+        .partition(|attachment| attachment.length <= nextcloud_client.chunked_upload_threshold());
+    let mut files = Vec::new();
+    for mut attachment in small {
+        println!("uploading {}", attachment.file_name);
+        let mut content = Vec::new();
+        attachment.file.read_to_end(&mut content)?;
+        files.push((format!("{}/{}", folder_path, attachment.file_name), content));
+    }
+    if !files.is_empty() {
+        for uploaded in run_bulk(nextcloud_client, parallelism, |bulk_client| async move { bulk_client.upload_files(files).await })? {
+            uploaded.map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(large)
+}
+
+/// Leaves every attachment to be streamed from its file, as requests can only be sent at once with the async feature.
+#[cfg(not(feature = "async"))]
+fn upload_small_attachments(
+    _nextcloud_client: &NextCloudClient<Client>,
+    _folder_path: &str,
+    attachments: Vec<LocalAttachment>,
+    _parallelism: Option<usize>,
+) -> Result<Vec<LocalAttachment>, Box<dyn Error>> {
+    Ok(attachments)
+}
+
+/// Runs one of the bulk operations of the client's async counterpart, which sends up to `parallelism`
+/// requests at once with the async counterpart of its transport.
+#[cfg(feature = "async")]
+fn run_bulk<F, Fut>(nextcloud_client: &NextCloudClient<Client>, parallelism: Option<usize>, operation: F) -> Result<Fut::Output, Box<dyn Error>>
+where
+    F: FnOnce(AsyncNextCloudClient<<Client as ToAsync>::Async>) -> Fut,
+    Fut: Future,
+{
+    let parallelism = parallelism.unwrap_or(asyncnextcloudclient::DEFAULT_PARALLELISM);
+    let bulk_client = nextcloud_client.to_async().with_parallelism(parallelism);
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    Ok(runtime.block_on(operation(bulk_client)))
+}
@@ -0,0 +1,149 @@
+use crate::cliarguments::{CommandSpec, COMMANDS, FLAGS};
+use crate::configprovider::PASSWORD_ENV_VAR;
+use crate::proxy::PROXY_ENV_VARS;
+
+/// Escapes text for roff: backslashes and hyphens are special anywhere, and a `.` or `'` at the start of a line
+/// would be read as a request.
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// The command's arguments, in italics and with optional ones in brackets, ie `\fINOTES TITLE\fR`.
+fn command_arguments(command: &CommandSpec) -> Vec<String> {
+    command
+        .arguments
+        .iter()
+        .map(|argument| {
+            if argument.required {
+                format!("\\fI{}\\fR", escape(argument.name))
+            } else {
+                format!("[\\fI{}\\fR]", escape(argument.name))
+            }
+        })
+        .collect()
+}
+
+fn command_heading(command: &CommandSpec) -> String {
+    let mut heading = format!(".B {}", escape(command.name));
+    let arguments = command_arguments(command);
+    if !arguments.is_empty() {
+        heading.push('\n');
+        heading.push_str(&arguments.join(" "));
+    }
+    heading
+}
+
+/// The man page for the program in roff format, built from the command and flag tables so it matches the help.
+pub fn man_page() -> String {
+    let mut page = format!(
+        ".TH NXCLOUDNOTES 1 \"\" \"nxcloudnotes {}\" \"User Commands\"\n",
+        escape(env!("CARGO_PKG_VERSION"))
+    );
+    page.push_str(".SH NAME\nnxcloudnotes \\- a CLI text dumping tool for NextCloud\n");
+
+    page.push_str(".SH SYNOPSIS\n");
+    let synopses: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| {
+            let mut synopsis = String::from(".B nxcloudnotes\n");
+            if !command.name.is_empty() {
+                synopsis.push_str(&format!(".B {}\n", escape(command.name)));
+            }
+            synopsis.push_str("[\\fIflags\\fR]");
+            for argument in command_arguments(command) {
+                synopsis.push(' ');
+                synopsis.push_str(&argument);
+            }
+            synopsis
+        })
+        .collect();
+    page.push_str(&synopses.join("\n.br\n"));
+    page.push('\n');
+
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str(&escape(COMMANDS[0].description));
+    page.push('\n');
+
+    page.push_str(".SH COMMANDS\n");
+    for command in COMMANDS.iter().filter(|command| !command.name.is_empty()) {
+        page.push_str(&format!(".TP\n{}\n{}\n", command_heading(command), escape(command.description)));
+        let flags: Vec<String> = command
+            .visible_flags()
+            .filter(|flag| flag.long != "help")
+            .map(|flag| format!("\\fB\\-\\-{}\\fR", escape(flag.long)))
+            .collect();
+        if !flags.is_empty() {
+            page.push_str(&format!("Accepts {}.\n", flags.join(", ")));
+        }
+    }
+
+    page.push_str(".SH OPTIONS\n");
+    for flag in FLAGS.iter().filter(|flag| !flag.hidden) {
+        let long = match flag.value_name {
+            Some(value_name) => format!("\\fB\\-\\-{}\\fR=\\fI{}\\fR", escape(flag.long), escape(value_name)),
+            None => format!("\\fB\\-\\-{}\\fR", escape(flag.long)),
+        };
+        let forms = match flag.short {
+            Some(short) => format!("\\fB\\-{}\\fR, {}", short, long),
+            None => long,
+        };
+        page.push_str(&format!(".TP\n{}\n{}\n", forms, escape(flag.help)));
+    }
+
+    page.push_str(".SH EXAMPLES\n.nf\n");
+    for command in COMMANDS.iter().skip(1).chain(COMMANDS.iter().take(1)) {
+        for example in command.examples {
+            page.push_str(&format!("nxcloudnotes {}\n", escape(example)));
+        }
+    }
+    page.push_str(".fi\n");
+
+    page.push_str(".SH ENVIRONMENT\n");
+    page.push_str(&format!(
+        ".TP\n.B {}\nNextCloud password, used if none is saved in the config file. If it isn't set either, the password is asked for.\n",
+        escape(PASSWORD_ENV_VAR)
+    ));
+    page.push_str(".TP\n.BR VISUAL \", \" EDITOR\nEditor notes are edited in by the browse command, vi if neither is set.\n");
+    page.push_str(&format!(
+        ".TP\n.BR {}\nProxy to connect through when there's none in the config file, ie http://proxy:3128 or socks5://proxy:1080.\n",
+        PROXY_ENV_VARS.iter().map(|name| escape(name)).collect::<Vec<_>>().join(" \", \" ")
+    ));
+    page.push_str(".TP\n.B NO_PROXY\nComma separated hosts (and their subdomains) that are connected to directly rather than through the proxy.\n");
+
+    page.push_str(".SH FILES\n");
+    page.push_str("The config file, app\\-config.toml, is kept in the platform's config directory for NxCloudNotes. ");
+    page.push_str("Run \\fBnxcloudnotes config\\-path\\fR to see where.\n");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_escape_roff() {
+        assert_eq!(escape("--no-meta"), "\\-\\-no\\-meta");
+        assert_eq!(escape("C:\\notes"), "C:\\enotes");
+        assert_eq!(escape(".hidden"), "\\&.hidden");
+    }
+
+    #[test]
+    fn should_render_every_command_and_flag() {
+        let page = man_page();
+        assert!(page.starts_with(".TH NXCLOUDNOTES 1"));
+        for section in &["NAME", "SYNOPSIS", "DESCRIPTION", "COMMANDS", "OPTIONS", "EXAMPLES", "ENVIRONMENT", "FILES"] {
+            assert!(page.contains(&format!(".SH {}\n", section)), "{}", section);
+        }
+        assert!(page.contains(".TP\n.B read\n\\fINOTES TITLE\\fR\n"));
+        assert!(page.contains(".TP\n\\fB\\-t\\fR, \\fB\\-\\-title\\fR=\\fITITLE\\fR\n"));
+        assert!(page.contains(".TP\n.B NXCLOUDNOTES_PASSWORD\n"));
+        assert!(page.contains(".TP\n.BR HTTPS_PROXY \", \" https_proxy \", \" ALL_PROXY \", \" all_proxy\n"));
+        // hidden flags are for the program's own use
+        assert!(!page.contains("\\-\\-names"));
+    }
+}
@@ -0,0 +1,94 @@
+//! A fake `HttpRequest` for testing code built on `NextCloudClient` without a server.
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::io::{Cursor, Read};
+use std::sync::{Arc, Mutex};
+use crate::httprequest::{HttpRequest, HttpResponse, RequestType, StreamingResponse};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SentRequest {
+    pub(crate) method: String,
+    pub(crate) uri: String,
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: Vec<u8>,
+}
+
+/// Response code, body and headers of a response the mock will send.
+type CannedResponse = (u16, String, HashMap<String, String>);
+
+/// Records every request sent through it and answers with canned responses, in order.
+#[derive(Clone, Default)]
+pub(crate) struct MockRequest {
+    current: SentRequest,
+    sent: Arc<Mutex<Vec<SentRequest>>>,
+    responses: Arc<Mutex<VecDeque<CannedResponse>>>,
+}
+
+impl MockRequest {
+    pub(crate) fn with_responses(responses: &[(u16, &str)]) -> Self {
+        let mock = MockRequest::default();
+        mock.responses.lock().unwrap().extend(responses.iter().map(|(code, body)| (*code, body.to_string(), HashMap::new())));
+        mock
+    }
+
+    /// Sets the headers of the response at `index`, ie `("etag", "\"1\"")`.
+    pub(crate) fn with_headers(self, index: usize, headers: &[(&str, &str)]) -> Self {
+        let headers = headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        self.responses.lock().unwrap()[index].2 = headers;
+        self
+    }
+
+    pub(crate) fn sent(&self) -> Vec<SentRequest> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    fn respond(mut self, body: Vec<u8>) -> HttpResponse {
+        self.current.body = body;
+        self.sent.lock().unwrap().push(self.current);
+        let (response_code, response_msg, headers) = self.responses.lock().unwrap().pop_front().unwrap_or((200, String::new(), HashMap::new()));
+        HttpResponse { response_code, response_msg, headers }
+    }
+}
+
+impl HttpRequest for MockRequest {
+    fn set_request(mut self, req_type: RequestType, remote_uri: &str) -> Self {
+        self.current.method = req_type.method().to_string();
+        self.current.uri = remote_uri.to_string();
+        self
+    }
+
+    fn set_header(mut self, header: String, value: String) -> Self {
+        self.current.headers.insert(header, value);
+        self
+    }
+
+    fn basic_auth(self, _user: &str, _password: &str) -> Self {
+        self
+    }
+
+    fn send_bytes(self, bytes: &[u8]) -> Result<HttpResponse, Box<dyn Error>> {
+        Ok(self.respond(bytes.to_vec()))
+    }
+
+    fn send_stream<R: Read>(self, mut body: R, _length: Option<u64>) -> Result<StreamingResponse, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        body.read_to_end(&mut bytes)?;
+        let response = self.respond(bytes);
+        Ok(StreamingResponse {
+            response_code: response.response_code,
+            headers: response.headers,
+            body: Box::new(Cursor::new(response.response_msg.into_bytes())),
+        })
+    }
+}
+
+/// A multistatus response listing the folder and the given (name, is_folder) entries, as a PROPFIND would.
+pub(crate) fn folder_listing(folder: &str, entries: &[(&str, bool)]) -> String {
+    let mut listing = format!(r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:"><d:response><d:href>/remote.php/dav/files/user{}/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"#, folder);
+    for (name, is_folder) in entries {
+        let resource_type = if *is_folder { "<d:resourcetype><d:collection/></d:resourcetype>" } else { "<d:resourcetype/>" };
+        listing.push_str(&format!(r#"<d:response><d:href>/remote.php/dav/files/user{}/{}</d:href><d:propstat><d:prop>{}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"#, folder, name, resource_type));
+    }
+    listing.push_str("</d:multistatus>");
+    listing
+}
@@ -1,41 +1,1162 @@
 use std::error::Error;
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "async")]
+use crate::asynchttp::ToAsync;
+#[cfg(feature = "async")]
+use crate::asyncnextcloudclient::AsyncNextCloudClient;
+use crate::frontmatter;
 use crate::httprequest::HttpRequest;
+use crate::httprequest::HttpResponse;
 use crate::httprequest::RequestType;
+use crate::httprequest::StreamingResponse;
+use crate::logging::Logger;
+use crate::utils;
+use crate::webdav;
 
+/// Files larger than this are uploaded in chunks, unless configured otherwise.
+pub const DEFAULT_CHUNKED_UPLOAD_THRESHOLD: u64 = 10 * 1024 * 1024;
+/// Size of each chunk of a chunked upload, unless configured otherwise.
+pub const DEFAULT_CHUNK_SIZE: u64 = 10 * 1024 * 1024;
+/// Nextcloud only accepts chunks numbered from 1 to 10000, so bigger files need bigger chunks.
+const MAX_CHUNKS: u64 = 10000;
+/// How much of the start of a file is hashed to identify its chunked upload, see `chunked_upload_id`.
+const UPLOAD_ID_SAMPLE_SIZE: u64 = 64 * 1024;
 
-pub struct NextCloudClient<T> 
-where 
-    T: HttpRequest 
+/// How many numbered titles are tried when suffixing a note's title to avoid a collision.
+const MAX_TITLE_SUFFIX: u32 = 100;
+/// How many times an append is retried when the note changes between reading and writing it.
+const MAX_APPEND_ATTEMPTS: u32 = 3;
+
+/// What to do when creating a note whose file already exists.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Replace the existing note.
+    Overwrite,
+    /// Leave the existing note alone and fail.
+    Fail,
+    /// Save the note under the first free numbered title, ie `Title (2)`.
+    Suffix,
+    /// Add the new content to the end of the existing note.
+    Append,
+}
+
+impl FromStr for CollisionPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "overwrite" => Ok(CollisionPolicy::Overwrite),
+            "fail" => Ok(CollisionPolicy::Fail),
+            "suffix" => Ok(CollisionPolicy::Suffix),
+            "append" => Ok(CollisionPolicy::Append),
+            _ => Err(format!("Unknown collision policy '{}', expected one of overwrite, fail, suffix or append", value)),
+        }
+    }
+}
+
+impl fmt::Display for CollisionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CollisionPolicy::Overwrite => "overwrite",
+            CollisionPolicy::Fail => "fail",
+            CollisionPolicy::Suffix => "suffix",
+            CollisionPolicy::Append => "append",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A note as it was saved by `NextCloudClient::create_note`.
+#[derive(Debug, PartialEq)]
+pub struct CreatedNote {
+    /// Where the note ended up, which differs from the requested path if it was suffixed.
+    pub path: String,
+    /// The full content of the note on the server, which includes the earlier content if it was appended to.
+    pub content: Vec<u8>,
+    /// The etag the server gave the note when it was saved, to replace it with `replace_file_if_unchanged`
+    /// only if nobody has changed it since. `None` if the server didn't send one.
+    pub etag: Option<String>,
+}
+
+/// A previous version of a file, which Nextcloud keeps when the file is changed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FileVersion {
+    /// What the version is restored by, which Nextcloud makes the unix time it was last modified at.
+    pub id: String,
+    /// When the version was last modified, as an http date.
+    pub modified: Option<String>,
+    /// Size of the version in bytes.
+    pub size: Option<u64>,
+}
+
+/// A file or folder in the trash bin, where Nextcloud keeps what's deleted until it's restored or purged.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TrashItem {
+    /// What the item is called in the trash bin, which is its file name with the deletion time added,
+    /// ie `Title.md.d1697712345`. Restoring and purging the item is done by this name.
+    pub name: String,
+    /// Where the item was deleted from, relative to the user's files, ie `Notes/Title.md`.
+    pub original_location: String,
+    /// When the item was deleted.
+    pub deleted: Option<SystemTime>,
+    pub is_folder: bool,
+}
+
+pub struct NextCloudClient<T>
+where
+    T: HttpRequest + Clone
 {
     request_builder: T,
     username: String,
     password: String,
+    chunked_upload_threshold: u64,
+    chunk_size: u64,
+    logger: Logger,
 }
 
-impl<T: HttpRequest> NextCloudClient<T> {
+impl<T: HttpRequest + Clone> NextCloudClient<T> {
+    /// `t` is used as a template for every request the client sends, so an http client that
+    /// shares its connections between clones (like `LiteHttpClient`) will reuse them across calls.
     pub fn new (t: T, username: String, password: String ) -> Self {
         NextCloudClient {
             request_builder: t,
             username,
-            password
+            password,
+            chunked_upload_threshold: DEFAULT_CHUNKED_UPLOAD_THRESHOLD,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            logger: Logger::default(),
         }
     }
 
-    pub fn create_or_replace_file<'a>(self, filepath: &str, content: &[u8]) -> Result<&'a str, Box<dyn Error>> {
-        let call_result = self.request_builder
-        .set_request(RequestType::PUT, &format!("/remote.php/dav/files/{}{}", &self.username, filepath))
+    /// Sets the size above which `upload_file` switches to a chunked upload, and the size of each chunk.
+    pub fn with_chunked_uploads(mut self, threshold: u64, chunk_size: u64) -> Self {
+        self.chunked_upload_threshold = threshold;
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Logs what the client does that isn't obvious from the requests it sends, like resuming an upload.
+    pub fn with_logger(mut self, logger: Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// An async client sending the same requests as this one with the async counterpart of its transport,
+    /// for operations on many files at once. Its bulk operations have to be run on a tokio runtime.
+    #[cfg(feature = "async")]
+    pub fn to_async(&self) -> AsyncNextCloudClient<T::Async>
+    where
+        T: ToAsync
+    {
+        AsyncNextCloudClient::new(self.request_builder.to_async(), self.username.clone(), self.password.clone())
+    }
+
+    /// Files above this size are uploaded in chunks by `upload_file`.
+    pub fn chunked_upload_threshold(&self) -> u64 {
+        self.chunked_upload_threshold
+    }
+
+    /// The WebDAV path of a file, percent-encoded so any characters in the username or file path
+    /// that have a meaning in urls (ie spaces, `#` or `?`) are sent as part of the path.
+    fn files_path(&self, filepath: &str) -> String {
+        webdav::percent_encode_path(&format!("/remote.php/dav/files/{}{}", &self.username, filepath))
+    }
+
+    /// Builds an authenticated request for the given path, relative to the server root.
+    fn dav_request(&self, req_type: RequestType, dav_path: &str) -> T {
+        self.request_builder.clone()
+        .set_request(req_type, dav_path)
         .basic_auth(&self.username, &self.password)
         .set_header("OCS-APIRequest".to_string(), "true".to_string())
-        .set_header("Connection".to_string(), "closed".to_string())
+    }
+
+    fn request(&self, req_type: RequestType, filepath: &str) -> T {
+        self.dav_request(req_type, &self.files_path(filepath))
+    }
+
+    pub fn create_or_replace_file<'a>(&self, filepath: &str, content: &[u8]) -> Result<&'a str, Box<dyn Error>> {
+        self.put_file(filepath, content)?;
+        Ok("File uploaded successfully")
+    }
+
+    /// PUTs the file whether or not there's one at the path already, returning the etag it was saved with.
+    fn put_file(&self, filepath: &str, content: &[u8]) -> Result<Option<String>, Box<dyn Error>> {
+        let call_result = self.request(RequestType::PUT, filepath)
+        .set_header("Content-Type".to_string(), utils::content_type_for_path(filepath).to_string())
         .send_bytes(content)?;
 
-        // assume all these response codes are a success, this should probably be more verbose
-        // but for a learning exercise this should be fine.
-        if (199..300).contains(&call_result.response_code) {
-            Ok("File uploaded successfully")
+        let etag = call_result.header("etag").map(|etag| etag.to_string());
+        check_response(call_result, "uploading file")?;
+        Ok(etag)
+    }
+
+    /// Replaces the file only if it still has the given etag, returning false without replacing it if
+    /// it was changed (or removed) since the etag was read.
+    pub fn replace_file_if_unchanged(&self, filepath: &str, content: &[u8], etag: &str) -> Result<bool, Box<dyn Error>> {
+        let call_result = self.request(RequestType::PUT, filepath)
+        .set_header("Content-Type".to_string(), utils::content_type_for_path(filepath).to_string())
+        .set_header("If-Match".to_string(), etag.to_string())
+        .send_bytes(content)?;
+
+        if call_result.response_code == 412 {
+            return Ok(false);
+        }
+        check_response(call_result, "uploading file")?;
+        Ok(true)
+    }
+
+    /// Creates the note at `filepath`, handling an existing note at that path with the collision policy.
+    /// Unless the policy is to overwrite, the note is PUT with `If-None-Match: *` so the server only
+    /// creates it if nothing is there, and a 412 (Precondition Failed) response triggers the policy.
+    pub fn create_note(&self, filepath: &str, content: &[u8], policy: CollisionPolicy) -> Result<CreatedNote, Box<dyn Error>> {
+        if policy == CollisionPolicy::Overwrite {
+            let etag = self.put_file(filepath, content)?;
+            return Ok(CreatedNote { path: filepath.to_string(), content: content.to_vec(), etag });
+        }
+
+        if let Some(note) = self.create_file_if_missing(filepath, content)? {
+            return Ok(note);
+        }
+        self.logger.verbose(&format!("A note already exists at {}, so the {} collision policy applies", filepath, policy));
+
+        match policy {
+            CollisionPolicy::Overwrite | CollisionPolicy::Fail => {
+                Err(format!("A note already exists at {}", filepath))?
+            },
+            CollisionPolicy::Suffix => {
+                for suffix in 2..=MAX_TITLE_SUFFIX {
+                    let suffixed_path = path_with_suffix(filepath, suffix);
+                    if let Some(note) = self.create_file_if_missing(&suffixed_path, content)? {
+                        return Ok(note);
+                    }
+                }
+                Err(format!("Notes already exist for every title from {} to {}", filepath, path_with_suffix(filepath, MAX_TITLE_SUFFIX)))?
+            },
+            CollisionPolicy::Append => {
+                // the existing note keeps its own front matter, a second block further down wouldn't be read as one
+                let split = std::str::from_utf8(content).map(frontmatter::split);
+                let appended = match &split {
+                    Ok((_, body)) => body.as_bytes(),
+                    Err(_) => content,
+                };
+                let (content, etag) = self.append_to_file(filepath, appended)?;
+                Ok(CreatedNote { path: filepath.to_string(), content, etag })
+            }
+        }
+    }
+
+    /// PUTs the file only if there isn't one at the path already, returning the created note,
+    /// or `None` if there was one.
+    fn create_file_if_missing(&self, filepath: &str, content: &[u8]) -> Result<Option<CreatedNote>, Box<dyn Error>> {
+        let call_result = self.request(RequestType::PUT, filepath)
+        .set_header("Content-Type".to_string(), utils::content_type_for_path(filepath).to_string())
+        .set_header("If-None-Match".to_string(), "*".to_string())
+        .send_bytes(content)?;
+
+        if call_result.response_code == 412 {
+            return Ok(None);
+        }
+        let etag = call_result.header("etag").map(|etag| etag.to_string());
+        check_response(call_result, "uploading file")?;
+        Ok(Some(CreatedNote { path: filepath.to_string(), content: content.to_vec(), etag }))
+    }
+
+    /// Adds the content to the end of the existing file, returning the combined content and its etag.
+    /// The write is conditional on the file's etag, so a change made by someone else between reading
+    /// and writing the file isn't lost, and the append is retried on top of it instead.
+    fn append_to_file(&self, filepath: &str, content: &[u8]) -> Result<(Vec<u8>, Option<String>), Box<dyn Error>> {
+        for _ in 0..MAX_APPEND_ATTEMPTS {
+            let mut existing = self.download_file(filepath)?;
+            let etag = existing.header("etag").map(|etag| etag.to_string());
+            let mut combined = Vec::new();
+            existing.body.read_to_end(&mut combined)?;
+            while combined.last().is_some_and(|byte| byte.is_ascii_whitespace()) {
+                combined.pop();
+            }
+            if !combined.is_empty() {
+                combined.extend_from_slice(b"\n\n");
+            }
+            combined.extend_from_slice(content);
+
+            let mut request = self.request(RequestType::PUT, filepath)
+            .set_header("Content-Type".to_string(), utils::content_type_for_path(filepath).to_string());
+            if let Some(etag) = etag {
+                request = request.set_header("If-Match".to_string(), etag);
+            }
+            let call_result = request.send_bytes(&combined)?;
+            if call_result.response_code != 412 {
+                let etag = call_result.header("etag").map(|etag| etag.to_string());
+                check_response(call_result, "appending to file")?;
+                return Ok((combined, etag));
+            }
+            self.logger.verbose(&format!("{} changed while it was being appended to, so the append is tried again", filepath));
+        }
+        Err(format!("{} kept changing while appending to it", filepath))?
+    }
+
+    /// Same as `create_or_replace_file`, but streams the file content from `content` rather than
+    /// needing it all in memory. If the `length` of the content isn't known it's uploaded with chunked
+    /// encoding, and if it's above the chunked upload threshold it's uploaded with `upload_file_chunked`.
+    pub fn upload_file<'a, R: Read>(&self, filepath: &str, content: R, length: Option<u64>) -> Result<&'a str, Box<dyn Error>> {
+        if let Some(length) = length {
+            if length > self.chunked_upload_threshold {
+                return self.upload_file_chunked(filepath, content, length);
+            }
+        }
+
+        let call_result = self.request(RequestType::PUT, filepath)
+        .set_header("Content-Type".to_string(), utils::content_type_for_path(filepath).to_string())
+        .send_stream(content, length)?
+        .into_response()?;
+
+        check_response(call_result, "uploading file")?;
+        Ok("File uploaded successfully")
+    }
+
+    /// Uploads the file using Nextcloud's chunked upload (v2) API, for files too large to send
+    /// in a single request. The chunks are PUT into an upload folder, then assembled by MOVEing
+    /// the folder's `.file` to the destination.
+    ///
+    /// If an earlier upload of the same file was interrupted, the chunks the server already has are
+    /// skipped and the upload carries on from the last confirmed chunk.
+    pub fn upload_file_chunked<'a, R: Read>(&self, filepath: &str, mut content: R, length: u64) -> Result<&'a str, Box<dyn Error>> {
+        let chunk_size = self.chunk_size.max(length.div_ceil(MAX_CHUNKS));
+        let chunk_count = length.div_ceil(chunk_size);
+
+        // read the start of the file to identify the upload, then put it back in front of the rest
+        let mut sample = Vec::new();
+        (&mut content).take(UPLOAD_ID_SAMPLE_SIZE).read_to_end(&mut sample)?;
+        let upload_id = self.chunked_upload_id(filepath, length, chunk_size, &sample);
+        let mut content = Cursor::new(sample).chain(content);
+
+        let upload_path = webdav::percent_encode_path(&format!("/remote.php/dav/uploads/{}/{}", &self.username, upload_id));
+        let destination = self.files_path(filepath);
+
+        let confirmed_chunks = match self.confirmed_chunks(&upload_path, length, chunk_size)? {
+            Some(confirmed_chunks) => confirmed_chunks,
+            None => {
+                let call_result = self.dav_request(RequestType::MKCOL, &upload_path)
+                .set_header("Destination".to_string(), destination.clone())
+                .send_bytes(&[])?;
+                check_response(call_result, "creating upload folder")?;
+                0
+            }
+        };
+
+        if confirmed_chunks > 0 {
+            self.logger.verbose(&format!("Resuming the upload of {} after the {} of {} chunks the server already has", filepath, confirmed_chunks, chunk_count));
         } else {
-            // so if response_code is in the 300 -> 500 range we can assume the upload failed
-            Err(format!("Reponse code {} indicates failure uploading file:\r\n{}", call_result.response_code, call_result.response_msg))?
+            self.logger.verbose(&format!("Uploading {} in {} chunks of up to {} bytes", filepath, chunk_count, chunk_size));
         }
+        let already_uploaded = confirmed_chunks * chunk_size;
+        let skipped = io::copy(&mut (&mut content).take(already_uploaded), &mut io::sink())?;
+        if skipped != already_uploaded {
+            Err(format!("File ended after {} of {} bytes", skipped, length))?
+        }
+
+        for chunk_number in (confirmed_chunks + 1)..=chunk_count {
+            let chunk_length = chunk_size.min(length - (chunk_number - 1) * chunk_size);
+            let call_result = self.dav_request(RequestType::PUT, &format!("{}/{}", upload_path, chunk_number))
+            .set_header("Destination".to_string(), destination.clone())
+            .set_header("OC-Total-Length".to_string(), length.to_string())
+            .send_stream((&mut content).take(chunk_length), Some(chunk_length))?
+            .into_response()?;
+            check_response(call_result, &format!("uploading chunk {} of {}", chunk_number, chunk_count))?;
+        }
+
+        // the chunks are plain byte ranges, it's the assembled file that gets the note's content type
+        let call_result = self.dav_request(RequestType::MOVE, &format!("{}/.file", upload_path))
+        .set_header("Destination".to_string(), destination)
+        .set_header("Content-Type".to_string(), utils::content_type_for_path(filepath).to_string())
+        .set_header("OC-Total-Length".to_string(), length.to_string())
+        .send_bytes(&[])?;
+        check_response(call_result, "assembling uploaded chunks")?;
+
+        Ok("File uploaded successfully")
+    }
+
+    /// Upload ids are derived from the destination, size and start of the file, so that retrying
+    /// the upload of the same file finds the chunks left behind by the interrupted attempt.
+    fn chunked_upload_id(&self, filepath: &str, length: u64, chunk_size: u64, sample: &[u8]) -> String {
+        let mut hasher = openssl::sha::Sha256::new();
+        hasher.update(self.username.as_bytes());
+        hasher.update(filepath.as_bytes());
+        hasher.update(&length.to_be_bytes());
+        hasher.update(&chunk_size.to_be_bytes());
+        hasher.update(sample);
+        let hash: String = hasher.finish().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("nxcloudnotes-{}", &hash[..32])
+    }
+
+    /// Finds how many chunks of an upload the server already has, counting up from the first chunk
+    /// until one is missing or the wrong size. Returns None if the upload folder doesn't exist.
+    fn confirmed_chunks(&self, upload_path: &str, length: u64, chunk_size: u64) -> Result<Option<u64>, Box<dyn Error>> {
+        let call_result = self.dav_request(RequestType::PROPFIND, upload_path)
+        .set_header("Depth".to_string(), "1".to_string())
+        .set_header("Content-Type".to_string(), "application/xml; charset=utf-8".to_string())
+        .send_bytes(webdav::propfind_body(&["d:getcontentlength"]).as_bytes())?;
+
+        if call_result.response_code == 404 {
+            return Ok(None);
+        }
+        let call_result = check_response(call_result, "checking for an interrupted upload")?;
+
+        let uploaded_chunks: Vec<(u64, u64)> = webdav::parse_multistatus(&call_result.response_msg)?
+            .iter()
+            .filter(|response| !response.is_collection())
+            .filter_map(|response| {
+                let chunk_number = response.name().parse().ok()?;
+                let size = response.property("getcontentlength")?.parse().ok()?;
+                Some((chunk_number, size))
+            })
+            .collect();
+
+        let mut confirmed = 0;
+        loop {
+            let chunk_number = confirmed + 1;
+            let expected_size = chunk_size.min(length.saturating_sub(confirmed * chunk_size));
+            if expected_size == 0 || !uploaded_chunks.contains(&(chunk_number, expected_size)) {
+                return Ok(Some(confirmed));
+            }
+            confirmed = chunk_number;
+        }
+    }
+
+    /// Creates the folder at `folderpath`, succeeding if it already exists.
+    pub fn create_folder(&self, folderpath: &str) -> Result<(), Box<dyn Error>> {
+        let call_result = self.request(RequestType::MKCOL, folderpath)
+        .send_bytes(&[])?;
+
+        // MKCOL on a folder that already exists is rejected with 405 Method Not Allowed
+        if call_result.response_code == 405 {
+            return Ok(());
+        }
+        check_response(call_result, "creating folder")?;
+        Ok(())
+    }
+
+    /// Deletes the file or folder at `filepath`. Nextcloud keeps deleted files in its trash bin, where
+    /// `restore_from_trash` can bring them back.
+    pub fn delete_file(&self, filepath: &str) -> Result<(), Box<dyn Error>> {
+        let call_result = self.request(RequestType::DELETE, filepath)
+        .send_bytes(&[])?;
+        check_response(call_result, "deleting file")?;
+        Ok(())
+    }
+
+    /// Moves (or renames) the file at `filepath` to `destination`, failing rather than replacing a file
+    /// that's already there.
+    pub fn move_file(&self, filepath: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+        let call_result = self.request(RequestType::MOVE, filepath)
+        .set_header("Destination".to_string(), self.files_path(destination))
+        .set_header("Overwrite".to_string(), "F".to_string())
+        .send_bytes(&[])?;
+        if call_result.response_code == 412 {
+            Err(format!("{} already exists", destination))?
+        }
+        check_response(call_result, "moving file")?;
+        Ok(())
+    }
+
+    /// Creates `subfolders` (ie `Work/Meetings`) inside the existing folder at `folderpath`, one level
+    /// at a time as MKCOL can't create intermediate folders. Levels that already exist are left as they are.
+    pub fn create_folders(&self, folderpath: &str, subfolders: &str) -> Result<(), Box<dyn Error>> {
+        let mut path = folderpath.trim_end_matches('/').to_string();
+        for level in subfolders.split('/').filter(|level| !level.is_empty()) {
+            path = format!("{}/{}", path, level);
+            self.create_folder(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Lists the files and folders directly inside the folder at `folderpath`, with their
+    /// type, size, modification time and etag.
+    pub fn list_folder(&self, folderpath: &str) -> Result<Vec<webdav::DavResponse>, Box<dyn Error>> {
+        let properties = ["d:resourcetype", "d:getcontentlength", "d:getlastmodified", "d:getetag"];
+        self.list_collection(&self.files_path(folderpath.trim_end_matches('/')), &properties, "listing folder")
+    }
+
+    /// Sends a PROPFIND for the properties of the resource at `dav_path`, and with a `depth` of 1 of
+    /// everything directly inside it, returning the responses parsed from the multistatus.
+    fn propfind(&self, dav_path: &str, depth: u32, properties: &[&str], action: &str) -> Result<Vec<webdav::DavResponse>, Box<dyn Error>> {
+        let call_result = self.dav_request(RequestType::PROPFIND, dav_path)
+        .set_header("Depth".to_string(), depth.to_string())
+        .set_header("Content-Type".to_string(), "application/xml; charset=utf-8".to_string())
+        .send_bytes(webdav::propfind_body(properties).as_bytes())?;
+        let call_result = check_response(call_result, action)?;
+        webdav::parse_multistatus(&call_result.response_msg)
+    }
+
+    /// The properties of everything directly inside the collection at `dav_path`.
+    fn list_collection(&self, dav_path: &str, properties: &[&str], action: &str) -> Result<Vec<webdav::DavResponse>, Box<dyn Error>> {
+        let responses = self.propfind(&format!("{}/", dav_path), 1, properties, action)?;
+        // the collection itself is included in the response, alongside its contents
+        let collection_path = webdav::percent_decode(dav_path);
+        let contents = responses
+            .into_iter()
+            .filter(|response| webdav::percent_decode(&response.href).trim_end_matches('/') != collection_path)
+            .collect();
+        Ok(contents)
+    }
+
+    /// Finds every category in the notes folder at `notes_directory`, which the Notes app stores as
+    /// subfolders. Nested categories are returned as paths, ie `Work/Meetings`. Hidden folders, like
+    /// the ones attachments are kept in, aren't categories.
+    pub fn list_categories(&self, notes_directory: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let (categories, _) = self.walk_notes_directory(notes_directory)?;
+        Ok(categories)
+    }
+
+    /// Lists the paths of all notes in the notes directory and its categories, relative to the notes
+    /// directory (ie `Work/Meeting.md`), sorted. Files in hidden folders such as attachments are skipped.
+    pub fn list_notes(&self, notes_directory: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let (_, notes) = self.walk_notes_directory(notes_directory)?;
+        Ok(notes)
+    }
+
+    /// Walks the notes directory, returning the sorted relative paths of its categories and of its notes.
+    fn walk_notes_directory(&self, notes_directory: &str) -> Result<(Vec<String>, Vec<String>), Box<dyn Error>> {
+        let mut categories = Vec::new();
+        let mut notes = Vec::new();
+        let mut to_visit = vec![String::new()];
+        while let Some(category) = to_visit.pop() {
+            let folder = format!("{}/{}", notes_directory.trim_end_matches('/'), category);
+            for entry in self.list_folder(&folder)? {
+                let name = entry.name();
+                if name.starts_with('.') {
+                    continue;
+                }
+                let path = if category.is_empty() { name } else { format!("{}/{}", category, name) };
+                if entry.is_collection() {
+                    categories.push(path.clone());
+                    to_visit.push(path);
+                } else {
+                    notes.push(path);
+                }
+            }
+        }
+        categories.sort();
+        notes.sort();
+        Ok((categories, notes))
+    }
+
+    /// Looks up the Nextcloud file id of the file at `filepath`, which is what other apps
+    /// (like Notes) use to refer to a file.
+    pub fn file_id(&self, filepath: &str) -> Result<String, Box<dyn Error>> {
+        let responses = self.propfind(&self.files_path(filepath), 0, &["oc:fileid"], "looking up file id")?;
+        match responses.first().and_then(|response| response.property("fileid")) {
+            Some(file_id) => Ok(file_id.to_string()),
+            None => Err(format!("No file id was returned for {}", filepath))?,
+        }
+    }
+
+    /// The WebDAV path of the user's file versions, percent-encoded like `files_path`.
+    fn versions_path(&self) -> String {
+        webdav::percent_encode_path(&format!("/remote.php/dav/versions/{}", &self.username))
     }
-}
\ No newline at end of file
+
+    /// Lists the previous versions of the file at `filepath` that Nextcloud has kept, newest first.
+    /// The current content of the file isn't one of them.
+    pub fn list_versions(&self, filepath: &str) -> Result<Vec<FileVersion>, Box<dyn Error>> {
+        let versions_path = format!("{}/versions/{}", self.versions_path(), self.file_id(filepath)?);
+        let mut versions: Vec<FileVersion> = self
+            .list_collection(&versions_path, &["d:getcontentlength", "d:getlastmodified"], "listing versions")?
+            .into_iter()
+            .map(|response| FileVersion {
+                id: response.name(),
+                modified: response.property("getlastmodified").map(String::from),
+                size: response.property("getcontentlength").and_then(|size| size.parse().ok()),
+            })
+            .collect();
+        versions.sort_by_key(|version| std::cmp::Reverse(version.id.parse::<u64>().ok()));
+        Ok(versions)
+    }
+
+    /// Restores the version of the file at `filepath` with the given id, as listed by `list_versions`.
+    /// Nextcloud keeps the content it replaces as another version, so restoring can be undone.
+    pub fn restore_version(&self, filepath: &str, version: &str) -> Result<(), Box<dyn Error>> {
+        let versions_path = self.versions_path();
+        let version_path = format!("{}/versions/{}/{}", versions_path, self.file_id(filepath)?, webdav::percent_encode_path(version));
+        let call_result = self.dav_request(RequestType::MOVE, &version_path)
+        .set_header("Destination".to_string(), format!("{}/restore/target", versions_path))
+        .send_bytes(&[])?;
+        if call_result.response_code == 404 {
+            Err(format!("No version {} of {} was found", version, filepath))?
+        }
+        check_response(call_result, "restoring version")?;
+        Ok(())
+    }
+
+    /// The WebDAV path of the user's trash bin, percent-encoded like `files_path`.
+    fn trashbin_path(&self) -> String {
+        webdav::percent_encode_path(&format!("/remote.php/dav/trashbin/{}", &self.username))
+    }
+
+    /// Lists what's in the trash bin that was deleted from the folder at `folderpath` or a folder
+    /// inside it, most recently deleted first.
+    pub fn list_trash(&self, folderpath: &str) -> Result<Vec<TrashItem>, Box<dyn Error>> {
+        let properties = ["d:resourcetype", "nc:trashbin-filename", "nc:trashbin-original-location", "nc:trashbin-deletion-time"];
+        let trash = self.list_collection(&format!("{}/trash", self.trashbin_path()), &properties, "listing the trash bin")?;
+
+        let folder = folderpath.trim_matches('/');
+        let mut items: Vec<TrashItem> = trash
+            .iter()
+            .map(|response| TrashItem {
+                name: response.name(),
+                original_location: response.property("trashbin-original-location").unwrap_or("").trim_start_matches('/').to_string(),
+                deleted: response
+                    .property("trashbin-deletion-time")
+                    .and_then(|time| time.parse().ok())
+                    .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds)),
+                is_folder: response.is_collection(),
+            })
+            .filter(|item| folder.is_empty() || item.original_location.starts_with(&format!("{}/", folder)))
+            .collect();
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted));
+        Ok(items)
+    }
+
+    /// Puts the item with the given name back where it was deleted from. Nextcloud restores it to the
+    /// root of the user's files if the folder it was in no longer exists.
+    pub fn restore_from_trash(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        let trashbin_path = self.trashbin_path();
+        let encoded_name = webdav::percent_encode_path(name);
+        let call_result = self.dav_request(RequestType::MOVE, &format!("{}/trash/{}", trashbin_path, encoded_name))
+        .set_header("Destination".to_string(), format!("{}/restore/{}", trashbin_path, encoded_name))
+        .send_bytes(&[])?;
+        if call_result.response_code == 404 {
+            Err(format!("Nothing called {} is in the trash bin", name))?
+        }
+        check_response(call_result, "restoring from the trash bin")?;
+        Ok(())
+    }
+
+    /// Permanently deletes everything in the trash bin that was deleted from the folder at `folderpath`
+    /// or a folder inside it, leaving anything else in the trash bin alone. Returns how many items were deleted.
+    pub fn empty_trash(&self, folderpath: &str) -> Result<usize, Box<dyn Error>> {
+        let items = self.list_trash(folderpath)?;
+        self.delete_from_trash(&items)?;
+        Ok(items.len())
+    }
+
+    /// Permanently deletes the given items, as listed by `list_trash`, from the trash bin.
+    pub fn delete_from_trash(&self, items: &[TrashItem]) -> Result<(), Box<dyn Error>> {
+        for item in items {
+            let item_path = format!("{}/trash/{}", self.trashbin_path(), webdav::percent_encode_path(&item.name));
+            let call_result = self.dav_request(RequestType::DELETE, &item_path)
+            .send_bytes(&[])?;
+            check_response(call_result, &format!("deleting {} from the trash bin", item.name))?;
+        }
+        Ok(())
+    }
+
+    /// Requests the file at `filepath`, returning the response with its body left to be streamed by the caller.
+    pub fn download_file(&self, filepath: &str) -> Result<StreamingResponse, Box<dyn Error>> {
+        let response = self.request(RequestType::GET, filepath)
+        .send_stream(&[][..], Some(0))?;
+
+        if (199..300).contains(&response.response_code) {
+            Ok(response)
+        } else {
+            Err(failure_message(&response.into_response()?, "downloading file"))?
+        }
+    }
+}
+
+/// Adds a number to the end of the file name, before its extension, ie `/Notes/Title (2).md`.
+fn path_with_suffix(filepath: &str, suffix: u32) -> String {
+    let name_start = filepath.rfind('/').map_or(0, |index| index + 1);
+    match filepath[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = name_start + dot;
+            format!("{} ({}){}", &filepath[..dot], suffix, &filepath[dot..])
+        },
+        _ => format!("{} ({})", filepath, suffix),
+    }
+}
+
+/// Turns an unsuccessful response into an error describing what was being attempted.
+fn check_response(call_result: HttpResponse, action: &str) -> Result<HttpResponse, Box<dyn Error>> {
+    // assume all these response codes are a success, this should probably be more verbose
+    // but for a learning exercise this should be fine.
+    if (199..300).contains(&call_result.response_code) {
+        Ok(call_result)
+    } else {
+        // so if response_code is in the 300 -> 500 range we can assume the request failed
+        Err(failure_message(&call_result, action))?
+    }
+}
+
+pub(crate) fn failure_message(call_result: &HttpResponse, action: &str) -> String {
+    format!("Reponse code {} indicates failure {}:\r\n{}", call_result.response_code, action, call_result.response_msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mockrequest::{folder_listing, MockRequest, SentRequest};
+
+    fn client(mock: &MockRequest) -> NextCloudClient<MockRequest> {
+        NextCloudClient::new(mock.clone(), "user".to_string(), "password".to_string()).with_chunked_uploads(8, 4)
+    }
+
+    fn summary(sent: &[SentRequest]) -> Vec<(String, String, String)> {
+        sent.iter()
+            .map(|request| {
+                // the upload id is a hash, so swap it for something readable
+                let uri = request.uri.split('/').map(|segment| if segment.starts_with("nxcloudnotes-") { "ID" } else { segment }).collect::<Vec<_>>().join("/");
+                (request.method.clone(), uri, String::from_utf8_lossy(&request.body).to_string())
+            })
+            .collect()
+    }
+
+    fn expected(requests: &[(&str, &str, &str)]) -> Vec<(String, String, String)> {
+        requests.iter().map(|(method, uri, body)| (method.to_string(), uri.to_string(), body.to_string())).collect()
+    }
+
+    #[test]
+    fn should_upload_small_files_in_a_single_request() {
+        let mock = MockRequest::with_responses(&[(201, "")]);
+
+        client(&mock).upload_file("/Notes/small.txt", "12345678".as_bytes(), Some(8)).unwrap();
+
+        let sent = mock.sent();
+        assert_eq!(summary(&sent), expected(&[("PUT", "/remote.php/dav/files/user/Notes/small.txt", "12345678")]));
+        assert_eq!(sent[0].headers.get("Content-Type").unwrap(), "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn should_percent_encode_file_paths() {
+        let mock = MockRequest::with_responses(&[(201, "")]);
+
+        client(&mock).create_or_replace_file("/Notes/Q&A #2?.md", b"").unwrap();
+
+        assert_eq!(mock.sent()[0].uri, "/remote.php/dav/files/user/Notes/Q%26A%20%232%3F.md");
+    }
+
+    #[test]
+    fn should_upload_large_files_in_chunks() {
+        let mock = MockRequest::with_responses(&[(404, ""), (201, ""), (201, ""), (201, ""), (201, ""), (201, "")]);
+
+        client(&mock).upload_file("/Notes/large.txt", "0123456789".as_bytes(), Some(10)).unwrap();
+
+        let sent = mock.sent();
+        assert_eq!(summary(&sent)[1..], expected(&[
+            ("MKCOL", "/remote.php/dav/uploads/user/ID", ""),
+            ("PUT", "/remote.php/dav/uploads/user/ID/1", "0123"),
+            ("PUT", "/remote.php/dav/uploads/user/ID/2", "4567"),
+            ("PUT", "/remote.php/dav/uploads/user/ID/3", "89"),
+            ("MOVE", "/remote.php/dav/uploads/user/ID/.file", ""),
+        ])[..]);
+        assert_eq!(sent[0].method, "PROPFIND");
+        assert_eq!(sent[5].headers.get("Destination").unwrap(), "/remote.php/dav/files/user/Notes/large.txt");
+        assert_eq!(sent[5].headers.get("OC-Total-Length").unwrap(), "10");
+        assert_eq!(sent[5].headers.get("Content-Type").unwrap(), "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn should_resume_interrupted_chunked_upload() {
+        // chunk 1 made it to the server, but chunk 2 was cut short
+        let existing_chunks = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response><d:href>/remote.php/dav/uploads/user/id/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+  <d:response><d:href>/remote.php/dav/uploads/user/id/1</d:href><d:propstat><d:prop><d:getcontentlength>4</d:getcontentlength></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+  <d:response><d:href>/remote.php/dav/uploads/user/id/2</d:href><d:propstat><d:prop><d:getcontentlength>1</d:getcontentlength></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+</d:multistatus>"#;
+        let mock = MockRequest::with_responses(&[(207, existing_chunks), (201, ""), (201, ""), (201, "")]);
+
+        client(&mock).upload_file("/Notes/large.txt", "0123456789".as_bytes(), Some(10)).unwrap();
+
+        assert_eq!(summary(&mock.sent())[1..], expected(&[
+            ("PUT", "/remote.php/dav/uploads/user/ID/2", "4567"),
+            ("PUT", "/remote.php/dav/uploads/user/ID/3", "89"),
+            ("MOVE", "/remote.php/dav/uploads/user/ID/.file", ""),
+        ])[..]);
+    }
+
+    #[test]
+    fn should_fail_when_a_chunk_is_rejected() {
+        let mock = MockRequest::with_responses(&[(404, ""), (201, ""), (507, "Insufficient Storage")]);
+
+        let result = client(&mock).upload_file("/Notes/large.txt", "0123456789".as_bytes(), Some(10));
+
+        assert!(result.unwrap_err().to_string().contains("uploading chunk 1 of 3"));
+        assert_eq!(mock.sent().len(), 3);
+    }
+
+    #[test]
+    fn should_fail_on_collision() {
+        let mock = MockRequest::with_responses(&[(412, "")]);
+
+        let result = client(&mock).create_note("/Notes/Title.md", b"new", CollisionPolicy::Fail);
+
+        assert_eq!(result.unwrap_err().to_string(), "A note already exists at /Notes/Title.md");
+        let sent = mock.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].headers.get("If-None-Match").unwrap(), "*");
+    }
+
+    #[test]
+    fn should_suffix_title_on_collision() {
+        let mock = MockRequest::with_responses(&[(412, ""), (412, ""), (201, "")]);
+
+        let note = client(&mock).create_note("/Notes/Title.md", b"new", CollisionPolicy::Suffix).unwrap();
+
+        assert_eq!(note.path, "/Notes/Title (3).md");
+        let uris: Vec<String> = mock.sent().iter().map(|request| request.uri.clone()).collect();
+        assert_eq!(uris, vec![
+            "/remote.php/dav/files/user/Notes/Title.md",
+            "/remote.php/dav/files/user/Notes/Title%20%282%29.md",
+            "/remote.php/dav/files/user/Notes/Title%20%283%29.md",
+        ]);
+    }
+
+    #[test]
+    fn should_append_to_existing_note_on_collision() {
+        let mock = MockRequest::with_responses(&[(412, ""), (200, "old content\n"), (204, "")])
+            .with_headers(1, &[("etag", "\"abc\"")]);
+
+        let note = client(&mock).create_note("/Notes/Title.md", b"new", CollisionPolicy::Append).unwrap();
+
+        assert_eq!(note.content, b"old content\n\nnew".to_vec());
+        let sent = mock.sent();
+        assert_eq!(sent[1].method, "GET");
+        assert_eq!(sent[2].method, "PUT");
+        assert_eq!(sent[2].headers.get("If-Match").unwrap(), "\"abc\"");
+        assert_eq!(sent[2].body, b"old content\n\nnew".to_vec());
+    }
+
+    #[test]
+    fn should_leave_front_matter_out_when_appending() {
+        let mock = MockRequest::with_responses(&[(412, ""), (200, "---\ntags: [old]\n---\nold content\n"), (204, "")]);
+
+        let note = client(&mock).create_note("/Notes/Title.md", b"---\ntags: [new]\n---\nnew", CollisionPolicy::Append).unwrap();
+
+        assert_eq!(note.content, b"---\ntags: [old]\n---\nold content\n\nnew".to_vec());
+    }
+
+    #[test]
+    fn should_overwrite_without_precondition() {
+        let mock = MockRequest::with_responses(&[(204, "")]);
+
+        client(&mock).create_note("/Notes/Title.md", b"new", CollisionPolicy::Overwrite).unwrap();
+
+        assert!(!mock.sent()[0].headers.contains_key("If-None-Match"));
+    }
+
+    #[test]
+    fn should_add_suffix_before_extension() {
+        assert_eq!(path_with_suffix("/Notes/Title.md", 2), "/Notes/Title (2).md");
+        assert_eq!(path_with_suffix("/Notes.d/Title", 2), "/Notes.d/Title (2)");
+        assert_eq!(path_with_suffix("/Notes/.hidden", 3), "/Notes/.hidden (3)");
+    }
+
+    #[test]
+    fn should_list_nested_categories() {
+        let mock = MockRequest::with_responses(&[
+            (207, &folder_listing("/Notes", &[("Work", true), ("Personal", true), (".attachments.12", true), ("note.md", false)])),
+            (207, &folder_listing("/Notes/Personal", &[])),
+            (207, &folder_listing("/Notes/Work", &[("Meetings%20%26%20Calls", true)])),
+            (207, &folder_listing("/Notes/Work/Meetings & Calls", &[])),
+        ]);
+
+        let categories = client(&mock).list_categories("/Notes").unwrap();
+
+        assert_eq!(categories, vec!["Personal", "Work", "Work/Meetings & Calls"]);
+        assert_eq!(mock.sent()[3].uri, "/remote.php/dav/files/user/Notes/Work/Meetings%20%26%20Calls/");
+    }
+
+    #[test]
+    fn should_list_notes_in_categories_but_not_attachments() {
+        let mock = MockRequest::with_responses(&[
+            (207, &folder_listing("/Notes", &[("Work", true), (".attachments.12", true), ("note.md", false)])),
+            (207, &folder_listing("/Notes/Work", &[("Meeting.md", false), ("Agenda.txt", false)])),
+        ]);
+
+        let notes = client(&mock).list_notes("/Notes").unwrap();
+
+        assert_eq!(notes, vec!["Work/Agenda.txt", "Work/Meeting.md", "note.md"]);
+        assert_eq!(mock.sent().len(), 2);
+    }
+
+    #[test]
+    fn should_move_without_overwriting() {
+        let mock = MockRequest::with_responses(&[(201, ""), (412, "")]);
+
+        client(&mock).move_file("/Notes/Old name.md", "/Notes/Work/New.md").unwrap();
+        let error = client(&mock).move_file("/Notes/Old name.md", "/Notes/Taken.md").unwrap_err();
+
+        let sent = mock.sent();
+        assert_eq!(sent[0].method, "MOVE");
+        assert_eq!(sent[0].uri, "/remote.php/dav/files/user/Notes/Old%20name.md");
+        assert_eq!(sent[0].headers.get("Destination").unwrap(), "/remote.php/dav/files/user/Notes/Work/New.md");
+        assert_eq!(sent[0].headers.get("Overwrite").unwrap(), "F");
+        assert_eq!(error.to_string(), "/Notes/Taken.md already exists");
+    }
+
+    #[test]
+    fn should_create_each_level_of_subfolders() {
+        let mock = MockRequest::with_responses(&[(405, ""), (201, "")]);
+
+        client(&mock).create_folders("/Notes", "Work/Meetings").unwrap();
+
+        let uris: Vec<String> = mock.sent().iter().map(|request| format!("{} {}", request.method, request.uri)).collect();
+        assert_eq!(uris, vec!["MKCOL /remote.php/dav/files/user/Notes/Work", "MKCOL /remote.php/dav/files/user/Notes/Work/Meetings"]);
+    }
+
+    #[test]
+    fn should_restore_version_by_moving_it_to_the_restore_target() {
+        let file_id = r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns"><d:response><d:href>/remote.php/dav/files/user/Notes/a.md</d:href><d:propstat><d:prop><oc:fileid>42</oc:fileid></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response></d:multistatus>"#;
+        let mock = MockRequest::with_responses(&[(207, file_id), (204, ""), (207, file_id), (404, "")]);
+
+        client(&mock).restore_version("/Notes/a.md", "1697712345").unwrap();
+        let error = client(&mock).restore_version("/Notes/a.md", "1").unwrap_err();
+
+        let sent = mock.sent();
+        assert_eq!(sent[1].method, "MOVE");
+        assert_eq!(sent[1].uri, "/remote.php/dav/versions/user/versions/42/1697712345");
+        assert_eq!(sent[1].headers.get("Destination").unwrap(), "/remote.php/dav/versions/user/restore/target");
+        assert_eq!(error.to_string(), "No version 1 of /Notes/a.md was found");
+    }
+
+    #[test]
+    fn should_list_trash_from_the_notes_directory_and_restore_by_name() {
+        let trash = r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:" xmlns:nc="http://nextcloud.org/ns">
+            <d:response><d:href>/remote.php/dav/trashbin/user/trash/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+            <d:response><d:href>/remote.php/dav/trashbin/user/trash/a.md.d1697712345</d:href><d:propstat><d:prop><d:resourcetype/><nc:trashbin-original-location>Notes/a.md</nc:trashbin-original-location><nc:trashbin-deletion-time>1697712345</nc:trashbin-deletion-time></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+            <d:response><d:href>/remote.php/dav/trashbin/user/trash/Work.d1697712400</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype><nc:trashbin-original-location>Notes/Work</nc:trashbin-original-location><nc:trashbin-deletion-time>1697712400</nc:trashbin-deletion-time></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+            <d:response><d:href>/remote.php/dav/trashbin/user/trash/Notes%20old.txt.d1697712500</d:href><d:propstat><d:prop><d:resourcetype/><nc:trashbin-original-location>Notes old.txt</nc:trashbin-original-location><nc:trashbin-deletion-time>1697712500</nc:trashbin-deletion-time></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>
+        </d:multistatus>"#;
+        let mock = MockRequest::with_responses(&[(207, trash), (201, ""), (404, "")]);
+
+        let items = client(&mock).list_trash("/Notes").unwrap();
+        client(&mock).restore_from_trash("a.md.d1697712345").unwrap();
+        let error = client(&mock).restore_from_trash("b.md.d1").unwrap_err();
+
+        // the file deleted from next to the notes directory isn't in it, even though its name starts the same
+        let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["Work.d1697712400", "a.md.d1697712345"]);
+        assert!(items[0].is_folder);
+        assert_eq!(items[1].original_location, "Notes/a.md");
+        assert_eq!(items[1].deleted, Some(UNIX_EPOCH + Duration::from_secs(1697712345)));
+        let sent = mock.sent();
+        assert_eq!(sent[0].uri, "/remote.php/dav/trashbin/user/trash/");
+        assert_eq!(sent[1].method, "MOVE");
+        assert_eq!(sent[1].uri, "/remote.php/dav/trashbin/user/trash/a.md.d1697712345");
+        assert_eq!(sent[1].headers.get("Destination").unwrap(), "/remote.php/dav/trashbin/user/restore/a.md.d1697712345");
+        assert_eq!(error.to_string(), "Nothing called b.md.d1 is in the trash bin");
+    }
+}
+
+/// The client against an in-memory Nextcloud, to check the requests it sends are understood by a WebDAV server.
+#[cfg(test)]
+mod end_to_end_tests {
+    use super::*;
+    use crate::mocknextcloud::MockNextcloud;
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn should_send_bulk_operations_with_the_same_transport() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/Notes/existing.md", b"");
+        let bulk_client = mock.nextcloud_client().to_async().with_parallelism(2);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        let files = vec![("/Notes/a.png".to_string(), vec![0x00, 0xff]), ("/Notes/b.md".to_string(), b"b".to_vec())];
+        let uploaded = runtime.block_on(bulk_client.upload_files(files));
+        let downloaded = runtime.block_on(bulk_client.download_files(vec!["/Notes/a.png".to_string(), "/Notes/missing.md".to_string()]));
+
+        assert!(uploaded.iter().all(|result| result.is_ok()));
+        assert_eq!(mock.file("/Notes/b.md").unwrap(), b"b");
+        assert_eq!(downloaded[0].as_ref().unwrap(), &vec![0x00, 0xff]);
+        assert!(downloaded[1].is_err());
+        // the async transport keeps its connections open between the operations
+        assert_eq!(mock.server().connections(), 2);
+    }
+
+    #[test]
+    fn should_suffix_title_when_note_exists() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/Notes/Title.md", b"first");
+
+        let created = mock.nextcloud_client().create_note("/Notes/Title.md", b"second", CollisionPolicy::Suffix).unwrap();
+
+        assert_eq!(created.path, "/Notes/Title (2).md");
+        assert_eq!(created.etag, mock.etag("/Notes/Title (2).md"));
+        assert_eq!(mock.file("/Notes/Title.md").unwrap(), b"first");
+        assert_eq!(mock.file("/Notes/Title (2).md").unwrap(), b"second");
+    }
+
+    #[test]
+    fn should_only_replace_created_note_while_it_has_the_etag_it_was_saved_with() {
+        let mock = MockNextcloud::start("user", "password");
+        let client = mock.nextcloud_client();
+        mock.create_folder("/Notes");
+
+        let created = client.create_note("/Notes/Title.md", b"first", CollisionPolicy::Overwrite).unwrap();
+        let etag = created.etag.unwrap();
+        mock.put_file("/Notes/Title.md", b"changed elsewhere");
+        let replaced_changed = client.replace_file_if_unchanged("/Notes/Title.md", b"first with references", &etag).unwrap();
+        let recreated = client.create_note("/Notes/Other.md", b"second", CollisionPolicy::Fail).unwrap();
+        let replaced = client.replace_file_if_unchanged("/Notes/Other.md", b"second with references", &recreated.etag.unwrap()).unwrap();
+
+        assert!(!replaced_changed);
+        assert_eq!(mock.file("/Notes/Title.md").unwrap(), b"changed elsewhere");
+        assert!(replaced);
+        assert_eq!(mock.file("/Notes/Other.md").unwrap(), b"second with references");
+    }
+
+    #[test]
+    fn should_leave_existing_note_alone_when_failing_on_collision() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/Notes/Title.md", b"first");
+
+        let error = mock.nextcloud_client().create_note("/Notes/Title.md", b"second", CollisionPolicy::Fail).unwrap_err();
+
+        assert_eq!(error.to_string(), "A note already exists at /Notes/Title.md");
+        assert_eq!(mock.file("/Notes/Title.md").unwrap(), b"first");
+    }
+
+    #[test]
+    fn should_retry_append_when_note_changes_while_appending() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/Notes/Title.md", b"first\n");
+        // the first conditional write is rejected as if someone else had saved the note in the meantime
+        mock.fail_next("PUT", "/Notes/Title.md", 412);
+        mock.fail_next("PUT", "/Notes/Title.md", 412);
+
+        let created = mock.nextcloud_client().create_note("/Notes/Title.md", b"second", CollisionPolicy::Append).unwrap();
+
+        assert_eq!(created.content, b"first\n\nsecond");
+        assert_eq!(created.etag, mock.etag("/Notes/Title.md"));
+        assert_eq!(mock.file("/Notes/Title.md").unwrap(), b"first\n\nsecond");
+        assert!(mock.server().received().last().unwrap().header("if-match").is_some());
+    }
+
+    #[test]
+    fn should_resume_chunked_upload_after_a_chunk_fails() {
+        let mock = MockNextcloud::start("user", "password");
+        let client = mock.nextcloud_client().with_chunked_uploads(8, 4);
+        mock.create_folder("/Notes");
+        mock.fail_next("PUT", "/3", 503);
+
+        let error = client.upload_file("/Notes/large.txt", &b"0123456789"[..], Some(10)).unwrap_err();
+        assert!(error.to_string().contains("uploading chunk 3 of 3"));
+        let first_attempt = mock.server().received().len();
+        client.upload_file("/Notes/large.txt", &b"0123456789"[..], Some(10)).unwrap();
+
+        assert_eq!(mock.file("/Notes/large.txt").unwrap(), b"0123456789");
+        let retried: Vec<String> = mock.server().received()[first_attempt..]
+            .iter()
+            .map(|request| {
+                let name = request.path.rsplit('/').next().unwrap();
+                format!("{} {}", request.method, if name.starts_with("nxcloudnotes-") { "ID" } else { name })
+            })
+            .collect();
+        assert_eq!(retried, vec!["PROPFIND ID", "PUT 3", "MOVE .file"]);
+    }
+
+    #[test]
+    fn should_upload_file_of_unknown_length() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.create_folder("/Notes");
+
+        mock.nextcloud_client().upload_file("/Notes/piped.md", &b"from stdin"[..], None).unwrap();
+
+        assert_eq!(mock.file("/Notes/piped.md").unwrap(), b"from stdin");
+    }
+
+    #[test]
+    fn should_list_notes_and_categories() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/Notes/note.md", b"");
+        mock.put_file("/Notes/Work/Meetings & Calls/Standup.md", b"");
+        mock.put_file("/Notes/.attachments.1/image.png", b"");
+        let client = mock.nextcloud_client();
+
+        assert_eq!(client.list_categories("/Notes").unwrap(), vec!["Work", "Work/Meetings & Calls"]);
+        assert_eq!(client.list_notes("/Notes").unwrap(), vec!["Work/Meetings & Calls/Standup.md", "note.md"]);
+    }
+
+    #[test]
+    fn should_create_move_download_and_delete_notes() {
+        let mock = MockNextcloud::start("user", "password");
+        mock.put_file("/Notes/Taken.md", b"");
+        let client = mock.nextcloud_client();
+
+        client.create_folders("/Notes", "Work/Meetings").unwrap();
+        client.create_or_replace_file("/Notes/Old name.md", b"content").unwrap();
+        let error = client.move_file("/Notes/Old name.md", "/Notes/Taken.md").unwrap_err();
+        client.move_file("/Notes/Old name.md", "/Notes/Work/Meetings/New.md").unwrap();
+        let mut download = client.download_file("/Notes/Work/Meetings/New.md").unwrap();
+        let mut content = String::new();
+        download.body.read_to_string(&mut content).unwrap();
+        client.delete_file("/Notes/Taken.md").unwrap();
+
+        assert_eq!(error.to_string(), "/Notes/Taken.md already exists");
+        assert_eq!(content, "content");
+        assert_eq!(mock.paths(), vec!["/Notes", "/Notes/Work", "/Notes/Work/Meetings", "/Notes/Work/Meetings/New.md"]);
+        assert!(client.download_file("/Notes/Taken.md").err().unwrap().to_string().contains("Reponse code 404"));
+    }
+
+    #[test]
+    fn should_list_and_restore_previous_versions() {
+        let mock = MockNextcloud::start("user", "password");
+        let client = mock.nextcloud_client();
+        mock.put_file("/Notes/Title.md", b"first");
+        mock.put_file("/Notes/Title.md", b"second!");
+        mock.put_file("/Notes/Title.md", b"third");
+
+        let versions = client.list_versions("/Notes/Title.md").unwrap();
+        let sizes: Vec<Option<u64>> = versions.iter().map(|version| version.size).collect();
+        assert_eq!(sizes, vec![Some(7), Some(5)]);
+        assert!(versions.iter().all(|version| version.modified.as_deref().is_some_and(|modified| modified.ends_with(" GMT"))));
+
+        client.restore_version("/Notes/Title.md", &versions[1].id).unwrap();
+
+        assert_eq!(mock.file("/Notes/Title.md").unwrap(), b"first");
+        // what was replaced by restoring is kept as the newest version
+        let sizes: Vec<Option<u64>> = client.list_versions("/Notes/Title.md").unwrap().iter().map(|version| version.size).collect();
+        assert_eq!(sizes, vec![Some(5), Some(7)]);
+    }
+
+    #[test]
+    fn should_restore_deleted_notes_and_empty_the_trash() {
+        let mock = MockNextcloud::start("user", "password");
+        let client = mock.nextcloud_client();
+        mock.put_file("/Notes/Work/Meeting.md", b"agenda");
+        mock.put_file("/Notes/Todo.md", b"milk");
+        mock.put_file("/Documents/Report.odt", b"");
+        client.delete_file("/Notes/Work").unwrap();
+        client.delete_file("/Notes/Todo.md").unwrap();
+        client.delete_file("/Documents/Report.odt").unwrap();
+
+        let items = client.list_trash("/Notes").unwrap();
+        let locations: Vec<&str> = items.iter().map(|item| item.original_location.as_str()).collect();
+        assert_eq!(locations.len(), 2);
+        assert!(locations.contains(&"Notes/Work") && locations.contains(&"Notes/Todo.md"));
+        let work = items.iter().find(|item| item.is_folder).unwrap();
+        client.restore_from_trash(&work.name).unwrap();
+
+        assert_eq!(mock.file("/Notes/Work/Meeting.md").unwrap(), b"agenda");
+        assert_eq!(client.empty_trash("/Notes").unwrap(), 1);
+        assert!(client.list_trash("/Notes").unwrap().is_empty());
+        // only what was deleted from the notes directory is purged
+        assert_eq!(client.list_trash("/").unwrap()[0].original_location, "Documents/Report.odt");
+    }
+
+    #[test]
+    fn should_report_wrong_password() {
+        let mock = MockNextcloud::start("user", "password");
+        let client = NextCloudClient::new(mock.http_client(), "user".to_string(), "guess".to_string());
+
+        let error = client.list_notes("/Notes").unwrap_err();
+
+        assert!(error.to_string().starts_with("Reponse code 401 indicates failure listing folder"));
+    }
+}
@@ -0,0 +1,253 @@
+use std::time::SystemTime;
+use crate::utils;
+
+/// Extension notes are saved with when none is configured.
+pub const DEFAULT_NOTE_EXTENSION: &str = "txt";
+
+/// Cleans up a user supplied extension, so `.md`, `MD` and `md` are all treated as `md`.
+/// Falls back to the default extension if nothing usable is left.
+pub fn normalise_extension(extension: &str) -> String {
+    let extension = extension.trim().trim_start_matches('.').to_lowercase();
+    if extension.is_empty() || extension.contains('/') {
+        DEFAULT_NOTE_EXTENSION.to_string()
+    } else {
+        extension
+    }
+}
+
+/// Turns a category like `Work/Meetings` into a safe relative folder path, sanitising each level
+/// like a file name. Returns None if nothing is left, ie for an empty category.
+pub fn category_path(category: &str) -> Option<String> {
+    let levels: Vec<String> = category
+        .split(['/', '\\'])
+        .map(sanitise_file_name)
+        .filter(|level| !level.is_empty())
+        .collect();
+    if levels.is_empty() {
+        None
+    } else {
+        Some(levels.join("/"))
+    }
+}
+
+/// Starts the note with its title as a Markdown heading, unless it already begins with a heading.
+pub fn with_title_heading(title: &str, content: &str) -> String {
+    if content.trim_start().starts_with("# ") {
+        return content.to_string();
+    }
+    format!("# {}\n\n{}", title, content)
+}
+
+/// Template used for the title of a note when none is given.
+pub const DEFAULT_TITLE_TEMPLATE: &str = "{first_line}";
+/// Title used if the template renders to nothing usable, ie for a note with an empty first line.
+const FALLBACK_TITLE_TEMPLATE: &str = "{date:%Y-%m-%d %H.%M.%S}";
+/// Titles longer than this (in characters) are cut short, as they end up as file names.
+const MAX_TITLE_LENGTH: usize = 100;
+/// Characters that can't be used in file names on at least one of the platforms Nextcloud syncs to.
+const FORBIDDEN_FILE_NAME_CHARACTERS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Values that can be used in a title template.
+pub struct TitleContext {
+    pub now: SystemTime,
+    pub first_line: String,
+    pub hostname: String,
+    /// Name of the directory the note was posted from.
+    pub cwd: String,
+}
+
+impl TitleContext {
+    /// Builds the context for a note with the given body, posted from this machine right now.
+    pub fn for_note(content: &str) -> Self {
+        let cwd = std::env::current_dir()
+            .ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+            .unwrap_or_default();
+        TitleContext {
+            now: SystemTime::now(),
+            first_line: first_line(content),
+            hostname: utils::hostname(),
+            cwd,
+        }
+    }
+}
+
+/// The first non-empty line of the note, without any Markdown heading markers.
+pub fn first_line(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Fills in the placeholders of a title template:
+/// - `{first_line}` the first line of the note.
+/// - `{date}` or `{date:FORMAT}` the current date, formatted with a strftime format (`%Y-%m-%d` by default).
+/// - `{hostname}` the name of this machine.
+/// - `{cwd}` the name of the current directory.
+///
+/// Unknown placeholders are left as they are.
+pub fn render_title_template(template: &str, context: &TitleContext) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = match after.find('}') {
+            Some(end) => end,
+            None => {
+                // an unclosed brace isn't a placeholder, so keep the rest as it is
+                rendered.push_str(&rest[start..]);
+                return rendered;
+            }
+        };
+        let placeholder = &after[..end];
+        let (name, argument) = match placeholder.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (placeholder, None),
+        };
+        match name {
+            "first_line" => rendered.push_str(&context.first_line),
+            "date" => rendered.push_str(&utils::format_local_time(context.now, argument.unwrap_or("%Y-%m-%d"))),
+            "hostname" => rendered.push_str(&context.hostname),
+            "cwd" => rendered.push_str(&context.cwd),
+            _ => {
+                rendered.push('{');
+                rendered.push_str(placeholder);
+                rendered.push('}');
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Turns a title into something that's safe to use as a file name: characters that aren't allowed
+/// in file names are replaced with spaces, leading dots are dropped (so the note isn't hidden and
+/// can't be `..`), whitespace is collapsed and the result is cut down to a sensible length.
+pub fn sanitise_file_name(title: &str) -> String {
+    let replaced: String = title
+        .chars()
+        .map(|c| if c.is_control() || FORBIDDEN_FILE_NAME_CHARACTERS.contains(&c) { ' ' } else { c })
+        .collect();
+    let collapsed = replaced.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.trim_start_matches(|c: char| c == '.' || c.is_whitespace());
+    trimmed.chars().take(MAX_TITLE_LENGTH).collect::<String>().trim_end().to_string()
+}
+
+/// Finds the note a user means by `title` among the paths returned by listing the notes directory.
+/// The title can be given with or without the note's extension, ie `Work/Meeting` or `Work/Meeting.md`.
+pub fn find_note<'a>(note_paths: &'a [String], title: &str) -> Option<&'a String> {
+    let title = title.trim_matches('/');
+    note_paths.iter().find(|path| path.as_str() == title).or_else(|| {
+        note_paths.iter().find(|path| match path.rsplit_once('.') {
+            Some((without_extension, _)) => without_extension == title,
+            None => false,
+        })
+    })
+}
+
+/// The title a note can be read by, which is its path relative to the notes directory without the extension.
+pub fn note_title_from_path(note_path: &str) -> &str {
+    let file_name_start = note_path.rfind('/').map_or(0, |index| index + 1);
+    match note_path[file_name_start..].rfind('.') {
+        Some(index) if index > 0 => &note_path[..file_name_start + index],
+        _ => note_path,
+    }
+}
+
+/// The title of a note: the one given by the user if there is one, otherwise rendered from the template.
+/// Either way it's sanitised so it can be used as a file name.
+pub fn note_title(given_title: Option<&str>, template: &str, context: &TitleContext) -> String {
+    let title = match given_title {
+        Some(title) => sanitise_file_name(title),
+        None => sanitise_file_name(&render_title_template(template, context)),
+    };
+    if title.is_empty() {
+        sanitise_file_name(&render_title_template(FALLBACK_TITLE_TEMPLATE, context))
+    } else {
+        title
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_normalise_extensions() {
+        assert_eq!(normalise_extension(".md"), "md");
+        assert_eq!(normalise_extension("MD"), "md");
+        assert_eq!(normalise_extension("org"), "org");
+        assert_eq!(normalise_extension(""), "txt");
+        assert_eq!(normalise_extension("../md"), "txt");
+    }
+
+    fn context() -> TitleContext {
+        TitleContext {
+            // 2021-06-15 12:00 UTC, which is the 15th or 16th in every timezone
+            now: std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_623_758_400),
+            first_line: "Deploy notes".to_string(),
+            hostname: "build-box".to_string(),
+            cwd: "nextcloud-notes-rs".to_string(),
+        }
+    }
+
+    #[test]
+    fn should_render_title_template() {
+        assert_eq!(render_title_template("{date:%Y-%m} {first_line}", &context()), "2021-06 Deploy notes");
+        assert_eq!(render_title_template("{hostname}: {cwd}", &context()), "build-box: nextcloud-notes-rs");
+        assert_eq!(render_title_template("{unknown} {first_line", &context()), "{unknown} {first_line");
+    }
+
+    #[test]
+    fn should_take_first_non_empty_line() {
+        assert_eq!(first_line("\n\n  # Heading  \nbody"), "Heading");
+        assert_eq!(first_line("   \n"), "");
+    }
+
+    #[test]
+    fn should_sanitise_file_names() {
+        assert_eq!(sanitise_file_name("../../etc/passwd"), "etc passwd");
+        assert_eq!(sanitise_file_name("What? A #1 <note>"), "What A #1 note");
+        assert_eq!(sanitise_file_name("  .hidden\tfile  "), "hidden file");
+        assert_eq!(sanitise_file_name(&"a".repeat(150)).len(), 100);
+    }
+
+    #[test]
+    fn should_fall_back_to_date_when_title_is_empty() {
+        let mut context = context();
+        assert_eq!(note_title(Some("a/b"), DEFAULT_TITLE_TEMPLATE, &context), "a b");
+        assert_eq!(note_title(None, DEFAULT_TITLE_TEMPLATE, &context), "Deploy notes");
+
+        context.first_line = String::new();
+        assert!(note_title(None, DEFAULT_TITLE_TEMPLATE, &context).starts_with("2021-06-1"));
+    }
+
+    #[test]
+    fn should_sanitise_category_levels() {
+        assert_eq!(category_path("Work/Meetings"), Some("Work/Meetings".to_string()));
+        assert_eq!(category_path("/Work//../Meet?ings/"), Some("Work/Meet ings".to_string()));
+        assert_eq!(category_path(" / "), None);
+    }
+
+    #[test]
+    fn should_find_notes_with_or_without_extension() {
+        let paths = vec!["Meeting.md".to_string(), "Work/Meeting.txt".to_string(), "Work/Meeting".to_string()];
+        assert_eq!(find_note(&paths, "Meeting"), Some(&paths[0]));
+        assert_eq!(find_note(&paths, "/Work/Meeting.txt"), Some(&paths[1]));
+        assert_eq!(find_note(&paths, "Work/Meeting"), Some(&paths[2]));
+        assert_eq!(find_note(&paths, "Home/Meeting"), None);
+        assert_eq!(note_title_from_path("Work/Meeting.v2.md"), "Work/Meeting.v2");
+        assert_eq!(note_title_from_path("Work.old/Meeting"), "Work.old/Meeting");
+    }
+
+    #[test]
+    fn should_prepend_title_heading() {
+        assert_eq!(with_title_heading("Meeting", "notes here"), "# Meeting\n\nnotes here");
+        assert_eq!(with_title_heading("Meeting", "# Already titled\nnotes"), "# Already titled\nnotes");
+    }
+}
@@ -0,0 +1,228 @@
+use std::convert::TryFrom;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for a connection attempt before racing it with one to the next address, as
+/// recommended by RFC 8305 (Happy Eyeballs version 2).
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+/// How long a single connection attempt is given before it's abandoned.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Addresses to use for a host and port instead of looking them up, like curl's `--resolve`,
+/// ie `cloud.example.com:443:10.0.0.5` to reach the server by its internal address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveOverride {
+    pub host: String,
+    pub port: u32,
+    pub addresses: Vec<IpAddr>,
+}
+
+impl FromStr for ResolveOverride {
+    type Err = String;
+
+    /// Parses `host:port:address`, where there can be more than one comma separated address and
+    /// IPv6 addresses can be in brackets, ie `cloud.example.com:443:[2001:db8::5],10.0.0.5`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid resolve entry '{}', expected host:port:address", value);
+        let (host, rest) = value.split_once(':').ok_or_else(invalid)?;
+        let (port, addresses) = rest.split_once(':').ok_or_else(invalid)?;
+        let port = port.parse().map_err(|_| invalid())?;
+        let addresses = addresses
+            .split(',')
+            .map(|address| {
+                let address = address.trim().trim_start_matches('[').trim_end_matches(']');
+                address.parse().map_err(|_| format!("Invalid address '{}' in resolve entry '{}'", address, value))
+            })
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(ResolveOverride { host: host.to_string(), port, addresses })
+    }
+}
+
+/// Formats the host and port for an http `Host` header or `CONNECT` request, with an IPv6 address in brackets.
+pub fn format_authority(host: &str, port: u32) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// The host as it's sent in the `Host` header, which only has the port when it isn't the default for https.
+pub fn host_header(host: &str, port: u32) -> String {
+    match (port, host.contains(':')) {
+        (443, true) => format!("[{}]", host),
+        (443, false) => host.to_string(),
+        _ => format_authority(host, port),
+    }
+}
+
+/// Finds the addresses to connect to for the host, from the overrides if one matches, otherwise from the
+/// system resolver. The host can be an IP address, with or without brackets around an IPv6 one.
+pub fn resolve(host: &str, port: u32, overrides: &[ResolveOverride]) -> io::Result<Vec<SocketAddr>> {
+    let port = u16::try_from(port).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid port {}", port)))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Some(resolved) = overrides.iter().find(|entry| entry.host.eq_ignore_ascii_case(host) && entry.port == u32::from(port)) {
+        return Ok(resolved.addresses.iter().map(|address| SocketAddr::new(*address, port)).collect());
+    }
+    if let Ok(address) = host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(address, port)]);
+    }
+    let addresses: Vec<SocketAddr> = (host, port).to_socket_addrs()?.collect();
+    if addresses.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("No addresses found for {}", host)));
+    }
+    Ok(addresses)
+}
+
+/// Connects to the first address that answers. Following Happy Eyeballs, the addresses are tried with
+/// IPv6 and IPv4 alternating, starting a new attempt whenever the last one fails or hasn't connected
+/// within `attempt_delay`, so a family that's broken on the network only costs that delay.
+pub fn connect(addresses: &[SocketAddr], attempt_delay: Duration) -> io::Result<TcpStream> {
+    race(&interleave_families(addresses), attempt_delay, |address| TcpStream::connect_timeout(&address, CONNECT_TIMEOUT))
+}
+
+/// Runs `attempt` for the addresses in order, each on its own thread once the previous one has failed
+/// or `attempt_delay` has passed, and returns the first success (or the last error if none succeeds).
+fn race<T, F>(addresses: &[SocketAddr], attempt_delay: Duration, attempt: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: Fn(SocketAddr) -> io::Result<T> + Send + Clone + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let mut started = 0;
+    let mut finished = 0;
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "No addresses to connect to");
+
+    while finished < addresses.len() {
+        if started < addresses.len() {
+            let address = addresses[started];
+            let sender = sender.clone();
+            let attempt = attempt.clone();
+            // attempts that lose the race finish in the background, and their connection is closed
+            thread::spawn(move || {
+                let _ = sender.send(attempt(address));
+            });
+            started += 1;
+        }
+
+        let result = if started < addresses.len() {
+            match receiver.recv_timeout(attempt_delay) {
+                Ok(result) => result,
+                // time to race the attempts so far with one to the next address
+                Err(_) => continue,
+            }
+        } else {
+            match receiver.recv() {
+                Ok(result) => result,
+                Err(_) => break,
+            }
+        };
+        finished += 1;
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// Orders the addresses alternating between families, starting with the family of the first one.
+fn interleave_families(addresses: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addresses.first().is_some_and(SocketAddr::is_ipv6);
+    let (mut preferred, mut other): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses
+        .iter()
+        .partition(|address| address.is_ipv6() == first_is_ipv6);
+    preferred.reverse();
+    other.reverse();
+
+    let mut interleaved = Vec::with_capacity(addresses.len());
+    while !preferred.is_empty() || !other.is_empty() {
+        interleaved.extend(preferred.pop());
+        interleaved.extend(other.pop());
+    }
+    interleaved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    #[test]
+    fn should_parse_resolve_overrides() {
+        let entry: ResolveOverride = "cloud.example.com:443:[2001:db8::5], 10.0.0.5".parse().unwrap();
+
+        assert_eq!(entry.host, "cloud.example.com");
+        assert_eq!(entry.port, 443);
+        assert_eq!(entry.addresses, vec!["2001:db8::5".parse::<IpAddr>().unwrap(), "10.0.0.5".parse().unwrap()]);
+        assert!("cloud.example.com:443".parse::<ResolveOverride>().is_err());
+        assert!("cloud.example.com:https:10.0.0.5".parse::<ResolveOverride>().is_err());
+        assert!("cloud.example.com:443:cloud.internal".parse::<ResolveOverride>().is_err());
+    }
+
+    #[test]
+    fn should_resolve_overrides_and_ip_literals() {
+        let overrides = vec!["Cloud.example.com:443:10.0.0.5".parse().unwrap()];
+
+        assert_eq!(resolve("cloud.example.com", 443, &overrides).unwrap(), vec!["10.0.0.5:443".parse().unwrap()]);
+        assert_eq!(resolve("[::1]", 8443, &overrides).unwrap(), vec!["[::1]:8443".parse().unwrap()]);
+        assert_eq!(resolve("127.0.0.1", 443, &[]).unwrap(), vec!["127.0.0.1:443".parse().unwrap()]);
+    }
+
+    #[test]
+    fn should_format_ipv6_hosts_in_brackets() {
+        assert_eq!(format_authority("::1", 8443), "[::1]:8443");
+        assert_eq!(format_authority("cloud.example.com", 443), "cloud.example.com:443");
+        assert_eq!(host_header("::1", 443), "[::1]");
+        assert_eq!(host_header("cloud.example.com", 8443), "cloud.example.com:8443");
+        assert_eq!(host_header("cloud.example.com", 443), "cloud.example.com");
+    }
+
+    #[test]
+    fn should_alternate_address_families() {
+        let addresses: Vec<SocketAddr> = ["[::1]:1", "[::2]:1", "[::3]:1", "10.0.0.1:1", "10.0.0.2:1"].iter().map(|address| address.parse().unwrap()).collect();
+
+        let order: Vec<String> = interleave_families(&addresses).iter().map(|address| address.ip().to_string()).collect();
+
+        assert_eq!(order, vec!["::1", "10.0.0.1", "::2", "10.0.0.2", "::3"]);
+    }
+
+    #[test]
+    fn should_connect_to_address_that_answers_when_others_fail() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        // a port that was just released refuses connections
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+        let stream = connect(&[closed, closed, open], Duration::from_secs(10)).unwrap();
+
+        assert_eq!(stream.peer_addr().unwrap(), open);
+        assert!(connect(&[closed], Duration::from_secs(10)).is_err());
+    }
+
+    #[test]
+    fn should_race_next_address_when_attempt_is_slow() {
+        let addresses: Vec<SocketAddr> = ["[::1]:443", "127.0.0.1:443"].iter().map(|address| address.parse().unwrap()).collect();
+        // the IPv6 attempt hangs, like it does on a network without a working IPv6 route
+        let attempt = |address: SocketAddr| {
+            if address.is_ipv6() {
+                thread::sleep(Duration::from_secs(10));
+            }
+            Ok(address)
+        };
+
+        let started = Instant::now();
+        let winner = race(&addresses, Duration::from_millis(50), attempt).unwrap();
+
+        assert_eq!(winner, addresses[1]);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
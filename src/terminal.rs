@@ -0,0 +1,385 @@
+use std::io::{self, BufRead, Write};
+
+/// A key press read from the terminal.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Key {
+    Char(char),
+    /// A letter pressed with Ctrl, ie `Ctrl('c')`.
+    Ctrl(char),
+    Enter,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+}
+
+/// Decodes the bytes read from a terminal in raw mode into key presses. Escape sequences that
+/// aren't recognised are dropped, rather than being seen as Esc followed by ordinary keys.
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+    while let Some(&byte) = rest.first() {
+        if byte == 0x1b {
+            let (key, length) = parse_escape_sequence(rest);
+            keys.extend(key);
+            rest = &rest[length..];
+            continue;
+        }
+        let key = match byte {
+            b'\r' | b'\n' => Some(Key::Enter),
+            0x7f | 0x08 => Some(Key::Backspace),
+            0x01..=0x1a => Some(Key::Ctrl((b'a' + byte - 1) as char)),
+            0x00..=0x1f => None,
+            _ => {
+                // the rest of a utf-8 character follows its first byte
+                let length = match byte {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => 1,
+                };
+                let length = length.min(rest.len());
+                let key = std::str::from_utf8(&rest[..length]).ok().and_then(|text| text.chars().next()).map(Key::Char);
+                rest = &rest[length..];
+                keys.extend(key);
+                continue;
+            },
+        };
+        keys.extend(key);
+        rest = &rest[1..];
+    }
+    keys
+}
+
+/// Parses the escape sequence at the start of `bytes`, returning the key (if it's one we know) and
+/// how many bytes it took up.
+fn parse_escape_sequence(bytes: &[u8]) -> (Option<Key>, usize) {
+    match bytes.get(1) {
+        Some(b'[') | Some(b'O') => {},
+        // a lone escape, or escape followed by an ordinary key (which is Alt+key, handled as Esc)
+        _ => return (Some(Key::Esc), 1),
+    }
+    // the sequence ends with its first byte in the @ to ~ range, after any parameters
+    let end = match bytes[2..].iter().position(|byte| (0x40..=0x7e).contains(byte)) {
+        Some(index) => index + 2,
+        None => return (None, bytes.len()),
+    };
+    let key = match &bytes[2..=end] {
+        b"A" => Some(Key::Up),
+        b"B" => Some(Key::Down),
+        b"H" | b"1~" | b"7~" => Some(Key::Home),
+        b"F" | b"4~" | b"8~" => Some(Key::End),
+        b"5~" => Some(Key::PageUp),
+        b"6~" => Some(Key::PageDown),
+        _ => None,
+    };
+    (key, end + 1)
+}
+
+/// Writes a whole frame of lines, replacing what was on the screen. Lines must already fit the width.
+pub fn draw<W: Write>(writer: &mut W, lines: &[String]) -> io::Result<()> {
+    let mut frame = String::from("\x1b[H");
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            frame.push_str("\r\n");
+        }
+        frame.push_str(line);
+        // clear whatever was left on the line by the previous frame
+        frame.push_str("\x1b[K");
+    }
+    frame.push_str("\x1b[J");
+    writer.write_all(frame.as_bytes())?;
+    writer.flush()
+}
+
+/// Puts the terminal into raw mode on the alternate screen, so a full screen UI can be drawn and every
+/// key read as it's pressed. The terminal is put back the way it was when this is dropped.
+#[cfg(unix)]
+pub struct RawTerminal {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawTerminal {
+    pub fn enter() -> io::Result<Self> {
+        // safe as termios is plain data, which tcgetattr fills in
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 || unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::other("stdin isn't a terminal"));
+        }
+        let terminal = RawTerminal { original };
+        terminal.make_raw()?;
+        Ok(terminal)
+    }
+
+    fn make_raw(&self) -> io::Result<()> {
+        let mut raw = self.original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // switch to the alternate screen and hide the cursor
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()
+    }
+
+    fn restore(&self) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[?25h\x1b[?1049l")?;
+        stdout.flush()?;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Width and height of the terminal in characters, falling back to 80x24 if it can't be found.
+    pub fn size(&self) -> (usize, usize) {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if result != 0 || size.ws_col == 0 || size.ws_row == 0 {
+            (80, 24)
+        } else {
+            (size.ws_col as usize, size.ws_row as usize)
+        }
+    }
+
+    /// Waits for the next key presses.
+    pub fn read_keys(&mut self) -> io::Result<Vec<Key>> {
+        let mut buf = [0u8; 64];
+        loop {
+            let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if read < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error);
+            }
+            if read == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the terminal was closed"));
+            }
+            return Ok(parse_keys(&buf[..read as usize]));
+        }
+    }
+
+    /// Gives the terminal back to run `f`, ie to let the user edit a file in their editor, then takes it over again.
+    pub fn suspend<F: FnOnce() -> R, R>(&mut self, f: F) -> io::Result<R> {
+        self.restore()?;
+        let result = f();
+        self.make_raw()?;
+        Ok(result)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+/// Raw mode is only implemented with termios, so isn't available on other platforms.
+#[cfg(not(unix))]
+pub struct RawTerminal;
+
+#[cfg(not(unix))]
+impl RawTerminal {
+    pub fn enter() -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "a full screen terminal UI is only supported on Unix"))
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (80, 24)
+    }
+
+    pub fn read_keys(&mut self) -> io::Result<Vec<Key>> {
+        Ok(vec![])
+    }
+
+    pub fn suspend<F: FnOnce() -> R, R>(&mut self, f: F) -> io::Result<R> {
+        Ok(f())
+    }
+}
+
+/// Stops what's typed on a terminal from being shown, ie while a password is typed, until this is dropped.
+/// Does nothing if the file descriptor isn't a terminal, so input that's piped in works the same.
+#[cfg(unix)]
+pub struct NoEcho {
+    fd: libc::c_int,
+    original: Option<libc::termios>,
+}
+
+#[cfg(unix)]
+impl NoEcho {
+    pub fn new(fd: libc::c_int) -> Self {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::isatty(fd) } != 1 || unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return NoEcho { fd, original: None };
+        }
+        let mut no_echo = original;
+        // the newline is still echoed, so the next output starts on its own line
+        no_echo.c_lflag &= !libc::ECHO;
+        no_echo.c_lflag |= libc::ECHONL;
+        if unsafe { libc::tcsetattr(fd, libc::TCSAFLUSH, &no_echo) } != 0 {
+            return NoEcho { fd, original: None };
+        }
+        NoEcho { fd, original: Some(original) }
+    }
+
+    pub fn stdin() -> Self {
+        Self::new(libc::STDIN_FILENO)
+    }
+
+    /// Whether echoing was turned off, which it isn't if the file descriptor isn't a terminal.
+    pub fn is_active(&self) -> bool {
+        self.original.is_some()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for NoEcho {
+    fn drop(&mut self) {
+        if let Some(original) = &self.original {
+            unsafe { libc::tcsetattr(self.fd, libc::TCSAFLUSH, original) };
+        }
+    }
+}
+
+/// Echoing is only turned off with termios, so what's typed is still shown on other platforms.
+#[cfg(not(unix))]
+pub struct NoEcho;
+
+#[cfg(not(unix))]
+impl NoEcho {
+    pub fn stdin() -> Self {
+        NoEcho
+    }
+
+    pub fn is_active(&self) -> bool {
+        false
+    }
+}
+
+/// Asks for a password on the controlling terminal without showing it as it's typed. The terminal is used
+/// rather than stdin and stdout, so the prompt still works when they're piped, ie when a note is piped in.
+#[cfg(unix)]
+pub fn read_password(prompt: &str) -> io::Result<String> {
+    use std::os::unix::io::AsRawFd;
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|_| io::Error::other("there's no terminal to ask for it on"))?;
+    let _no_echo = NoEcho::new(tty.as_raw_fd());
+    (&tty).write_all(prompt.as_bytes())?;
+    (&tty).flush()?;
+    read_password_line(&mut io::BufReader::new(&tty))
+}
+
+#[cfg(not(unix))]
+pub fn read_password(prompt: &str) -> io::Result<String> {
+    let mut stderr = io::stderr();
+    stderr.write_all(prompt.as_bytes())?;
+    stderr.flush()?;
+    read_password_line(&mut io::stdin().lock())
+}
+
+/// Reads a password from the first line of `reader`, without its line ending.
+pub fn read_password_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut password = String::new();
+    if reader.read_line(&mut password)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no password was given"));
+    }
+    let length = password.trim_end_matches(['\r', '\n']).len();
+    password.truncate(length);
+    Ok(password)
+}
+
+/// Asks a yes or no question on the controlling terminal, like `read_password`, so the answer can't
+/// come from a pipe by accident. Anything but y or yes is taken as no.
+#[cfg(unix)]
+pub fn confirm(prompt: &str) -> io::Result<bool> {
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|_| io::Error::other("there's no terminal to ask on"))?;
+    (&tty).write_all(prompt.as_bytes())?;
+    (&tty).flush()?;
+    read_confirmation(&mut io::BufReader::new(&tty))
+}
+
+#[cfg(not(unix))]
+pub fn confirm(prompt: &str) -> io::Result<bool> {
+    use std::io::IsTerminal;
+    if !io::stdin().is_terminal() {
+        return Err(io::Error::other("there's no terminal to ask on"));
+    }
+    let mut stderr = io::stderr();
+    stderr.write_all(prompt.as_bytes())?;
+    stderr.flush()?;
+    read_confirmation(&mut io::stdin().lock())
+}
+
+/// Reads a yes or no answer from the first line of `reader`, where no answer at all is no.
+pub fn read_confirmation<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    let mut answer = String::new();
+    reader.read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_keys() {
+        assert_eq!(parse_keys(b"a\r\x7f\x03"), vec![Key::Char('a'), Key::Enter, Key::Backspace, Key::Ctrl('c')]);
+        assert_eq!(parse_keys("é".as_bytes()), vec![Key::Char('é')]);
+    }
+
+    #[test]
+    fn should_parse_escape_sequences() {
+        assert_eq!(parse_keys(b"\x1b[A\x1bOB\x1b[5~\x1b[6~"), vec![Key::Up, Key::Down, Key::PageUp, Key::PageDown]);
+        assert_eq!(parse_keys(b"\x1b"), vec![Key::Esc]);
+        // unknown sequences (here F5) are dropped whole
+        assert_eq!(parse_keys(b"\x1b[15~x"), vec![Key::Char('x')]);
+    }
+
+    #[test]
+    fn should_draw_whole_frames() {
+        let mut output = Vec::new();
+        draw(&mut output, &["one".to_string(), "two".to_string()]).unwrap();
+        assert_eq!(output, b"\x1b[Hone\x1b[K\r\ntwo\x1b[K\x1b[J");
+    }
+
+    #[test]
+    fn should_read_password_without_line_ending() {
+        assert_eq!(read_password_line(&mut "s3cret pass\r\nnext line\n".as_bytes()).unwrap(), "s3cret pass");
+        assert_eq!(read_password_line(&mut "".as_bytes()).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn should_only_confirm_on_yes() {
+        assert!(read_confirmation(&mut "y\n".as_bytes()).unwrap());
+        assert!(read_confirmation(&mut " Yes\r\n".as_bytes()).unwrap());
+        assert!(!read_confirmation(&mut "\n".as_bytes()).unwrap());
+        assert!(!read_confirmation(&mut "no\n".as_bytes()).unwrap());
+        assert!(!read_confirmation(&mut "yesterday\n".as_bytes()).unwrap());
+        assert!(!read_confirmation(&mut "".as_bytes()).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_leave_files_that_arent_terminals_alone() {
+        let file = std::fs::File::open("/dev/null").unwrap();
+        assert!(!NoEcho::new(std::os::unix::io::AsRawFd::as_raw_fd(&file)).is_active());
+    }
+}
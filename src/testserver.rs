@@ -0,0 +1,419 @@
+//! A local server to test http clients against, answering requests with a handler and recording them.
+//! It's available to other crates' tests with the `test-server` feature.
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::ssl::{SslAcceptor, SslConnector, SslMethod};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509NameBuilder, X509};
+use crate::httprequest::BodyFraming;
+
+/// Host name the server's certificate is for, which clients should connect to it with.
+pub const HOST: &str = "localhost";
+/// Another host name the certificate is for, which doesn't resolve to any address (`.test` is reserved),
+/// ie to connect to the server through a resolve override.
+pub const UNRESOLVED_HOST: &str = "nextcloud.test";
+
+/// A request as the server received it, with its chunked body (if it was sent with one) decoded.
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: String,
+    pub path: String,
+    /// Request headers, with the header names lowercased.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl ReceivedRequest {
+    /// Gets the value of the given (case insensitive) request header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|value| value.as_str())
+    }
+}
+
+/// The response the handler sends back for a request.
+pub struct Reply {
+    pub code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Whether the body is sent in chunks, rather than with a `Content-Length`.
+    pub chunked: bool,
+    /// Whether the connection is closed instead of replying, like a server that went away mid-request.
+    pub drop_connection: bool,
+}
+
+impl Reply {
+    pub fn new(code: u16, body: &[u8]) -> Self {
+        Reply { code, headers: vec![], body: body.to_vec(), chunked: false, drop_connection: false }
+    }
+
+    /// Closes the connection without sending a response.
+    pub fn drop_connection() -> Self {
+        Reply { drop_connection: true, ..Reply::new(500, b"") }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn chunked(mut self) -> Self {
+        self.chunked = true;
+        self
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} Test\r\n", self.code);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        let has_body = !(self.code < 200 || self.code == 204 || self.code == 304);
+        if self.chunked {
+            head.push_str("Transfer-Encoding: chunked\r\n\r\n");
+            writer.write_all(head.as_bytes())?;
+            // small chunks, so clients have to put the body back together
+            for chunk in self.body.chunks(5) {
+                write!(writer, "{:x}\r\n", chunk.len())?;
+                writer.write_all(chunk)?;
+                writer.write_all(b"\r\n")?;
+            }
+            writer.write_all(b"0\r\n\r\n")?;
+        } else if has_body {
+            head.push_str(&format!("Content-Length: {}\r\n\r\n", self.body.len()));
+            writer.write_all(head.as_bytes())?;
+            writer.write_all(&self.body)?;
+        } else {
+            head.push_str("\r\n");
+            writer.write_all(head.as_bytes())?;
+        }
+        writer.flush()
+    }
+}
+
+/// The certificate is the same for every server, as generating a key is slow.
+fn certificate() -> &'static (X509, PKey<Private>) {
+    static CERTIFICATE: OnceLock<(X509, PKey<Private>)> = OnceLock::new();
+    CERTIFICATE.get_or_init(|| {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", HOST).unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        // the server can also be reached as 127.0.0.1, ie to test talking to more than one host
+        let san = SubjectAlternativeName::new().dns(HOST).dns(UNRESOLVED_HOST).ip("127.0.0.1").build(&builder.x509v3_context(None, None)).unwrap();
+        builder.append_extension(san).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (builder.build(), key)
+    })
+}
+
+type Handler = dyn Fn(&ReceivedRequest) -> Reply + Send + Sync;
+
+/// Runs until the test process exits, answering each connection on its own thread.
+pub struct TestServer {
+    port: u16,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl TestServer {
+    /// Starts a server answering over TLS, with a certificate for `HOST`.
+    pub fn start<H>(handler: H) -> Self
+    where
+        H: Fn(&ReceivedRequest) -> Reply + Send + Sync + 'static,
+    {
+        let (certificate, key) = certificate();
+        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(certificate).unwrap();
+        acceptor.set_private_key(key).unwrap();
+        Self::start_with(Some(acceptor.build()), Arc::new(handler))
+    }
+
+    /// Starts a server answering plain http, for clients that aren't using TLS.
+    pub fn start_plain<H>(handler: H) -> Self
+    where
+        H: Fn(&ReceivedRequest) -> Reply + Send + Sync + 'static,
+    {
+        Self::start_with(None, Arc::new(handler))
+    }
+
+    fn start_with(acceptor: Option<SslAcceptor>, handler: Arc<Handler>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let server = TestServer { port, received: Arc::clone(&received), connections: Arc::clone(&connections) };
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                connections.fetch_add(1, Ordering::SeqCst);
+                let acceptor = acceptor.clone();
+                let handler = Arc::clone(&handler);
+                let received = Arc::clone(&received);
+                thread::spawn(move || match acceptor {
+                    Some(acceptor) => {
+                        if let Ok(stream) = acceptor.accept(stream) {
+                            serve(BufReader::new(stream), handler.as_ref(), &received);
+                        }
+                    },
+                    None => serve(BufReader::new(stream), handler.as_ref(), &received),
+                });
+            }
+        });
+        server
+    }
+
+    pub fn port(&self) -> u32 {
+        u32::from(self.port)
+    }
+
+    /// A connector that trusts the server's self-signed certificate.
+    pub fn ssl_connector(&self) -> SslConnector {
+        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+        connector.cert_store_mut().add_cert(certificate().0.clone()).unwrap();
+        connector.build()
+    }
+
+    /// The server's certificate, DER encoded, for clients that aren't configured with openssl.
+    pub fn certificate_der(&self) -> Vec<u8> {
+        certificate().0.to_der().unwrap()
+    }
+
+    /// Every request received so far, in the order they were answered.
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// How many connections have been made to the server.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+/// Answers requests on the connection until the client closes it.
+fn serve<S: Read + Write>(mut reader: BufReader<S>, handler: &Handler, received: &Mutex<Vec<ReceivedRequest>>) {
+    while let Ok(Some(request)) = read_request(&mut reader) {
+        let reply = handler(&request);
+        received.lock().unwrap().push(request);
+        if reply.drop_connection || reply.write_to(reader.get_mut()).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Option<ReceivedRequest>> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line == "\r\n" || line == "\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let is_chunked = headers.get("transfer-encoding").is_some_and(|encoding| encoding.contains("chunked"));
+    let mut framing = if is_chunked {
+        BodyFraming::Chunked { remaining: 0, finished: false }
+    } else {
+        let length = headers.get("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+        BodyFraming::Length(length)
+    };
+    let mut body = Vec::new();
+    let mut buf = [0; 8 * 1024];
+    loop {
+        let read = framing.read(reader, &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&buf[..read]);
+    }
+    Ok(Some(ReceivedRequest { method, path, headers, body }))
+}
+
+/// A proxy for tests, which tunnels connections to the host clients ask for, either as an http proxy
+/// (with `CONNECT`) or as a SOCKS5 proxy. It records the `host:port` of each tunnel it opens.
+pub struct TestProxy {
+    port: u16,
+    targets: Arc<Mutex<Vec<String>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl TestProxy {
+    /// Starts an http proxy, which asks for the given user name and password if there are any.
+    pub fn start_http(credentials: Option<(&str, &str)>) -> Self {
+        Self::start(credentials, false)
+    }
+
+    /// Starts a SOCKS5 proxy, which asks for the given user name and password if there are any.
+    pub fn start_socks5(credentials: Option<(&str, &str)>) -> Self {
+        Self::start(credentials, true)
+    }
+
+    fn start(credentials: Option<(&str, &str)>, socks5: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let credentials = credentials.map(|(user, password)| (user.to_string(), password.to_string()));
+        let targets = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let proxy = TestProxy { port, targets: Arc::clone(&targets), connections: Arc::clone(&connections) };
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                connections.fetch_add(1, Ordering::SeqCst);
+                let credentials = credentials.clone();
+                let targets = Arc::clone(&targets);
+                thread::spawn(move || {
+                    let target = if socks5 { socks5_handshake(&stream, &credentials) } else { http_handshake(&stream, &credentials) };
+                    if let Ok(Some((target, upstream))) = target {
+                        targets.lock().unwrap().push(target);
+                        tunnel(stream, upstream);
+                    }
+                });
+            }
+        });
+        proxy
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The `host:port` of every tunnel opened so far.
+    pub fn targets(&self) -> Vec<String> {
+        self.targets.lock().unwrap().clone()
+    }
+
+    /// How many connections have been made to the proxy.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+/// Answers a `CONNECT` request, returning the target and a connection to it if the tunnel was opened.
+fn http_handshake(mut stream: &TcpStream, credentials: &Option<(String, String)>) -> io::Result<Option<(String, TcpStream)>> {
+    let request = match read_request(&mut BufReader::new(stream))? {
+        Some(request) if request.method == "CONNECT" => request,
+        _ => return Ok(None),
+    };
+    if let Some((user, password)) = credentials {
+        let expected = openssl::base64::encode_block(format!("{}:{}", user, password).as_bytes());
+        let given = request.header("proxy-authorization").and_then(|authorization| authorization.split_once(' '));
+        if !given.is_some_and(|(scheme, token)| scheme.eq_ignore_ascii_case("basic") && token == expected) {
+            stream.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic\r\nContent-Length: 0\r\n\r\n")?;
+            return Ok(None);
+        }
+    }
+    let upstream = TcpStream::connect(&request.path)?;
+    stream.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")?;
+    Ok(Some((request.path, upstream)))
+}
+
+/// Answers a SOCKS5 greeting and connect request, returning the target and a connection to it.
+fn socks5_handshake(mut stream: &TcpStream, credentials: &Option<(String, String)>) -> io::Result<Option<(String, TcpStream)>> {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header)?;
+    let mut methods = vec![0; header[1] as usize];
+    stream.read_exact(&mut methods)?;
+
+    if let Some((user, password)) = credentials {
+        if !methods.contains(&2) {
+            stream.write_all(&[5, 0xff])?;
+            return Ok(None);
+        }
+        stream.write_all(&[5, 2])?;
+        let mut version_and_length = [0; 2];
+        stream.read_exact(&mut version_and_length)?;
+        let mut given_user = vec![0; version_and_length[1] as usize];
+        stream.read_exact(&mut given_user)?;
+        let mut length = [0; 1];
+        stream.read_exact(&mut length)?;
+        let mut given_password = vec![0; length[0] as usize];
+        stream.read_exact(&mut given_password)?;
+        if given_user != user.as_bytes() || given_password != password.as_bytes() {
+            stream.write_all(&[1, 1])?;
+            return Ok(None);
+        }
+        stream.write_all(&[1, 0])?;
+    } else {
+        stream.write_all(&[5, 0])?;
+    }
+
+    let mut request = [0; 4];
+    stream.read_exact(&mut request)?;
+    let host = match request[3] {
+        1 => {
+            let mut address = [0; 4];
+            stream.read_exact(&mut address)?;
+            std::net::Ipv4Addr::from(address).to_string()
+        },
+        4 => {
+            let mut address = [0; 16];
+            stream.read_exact(&mut address)?;
+            format!("[{}]", std::net::Ipv6Addr::from(address))
+        },
+        _ => {
+            let mut length = [0; 1];
+            stream.read_exact(&mut length)?;
+            let mut name = vec![0; length[0] as usize];
+            stream.read_exact(&mut name)?;
+            String::from_utf8_lossy(&name).to_string()
+        },
+    };
+    let mut port = [0; 2];
+    stream.read_exact(&mut port)?;
+    let target = format!("{}:{}", host, u16::from_be_bytes(port));
+
+    match TcpStream::connect(&target) {
+        Ok(upstream) => {
+            stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])?;
+            Ok(Some((target, upstream)))
+        },
+        Err(_) => {
+            stream.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0])?;
+            Ok(None)
+        },
+    }
+}
+
+/// Copies data both ways between the client and the target until either of them closes its connection.
+fn tunnel(client: TcpStream, upstream: TcpStream) {
+    let (mut client_reader, mut upstream_writer) = match (client.try_clone(), upstream.try_clone()) {
+        (Ok(client_reader), Ok(upstream_writer)) => (client_reader, upstream_writer),
+        _ => return,
+    };
+    let upload = thread::spawn(move || {
+        let _ = io::copy(&mut client_reader, &mut upstream_writer);
+        let _ = upstream_writer.shutdown(std::net::Shutdown::Write);
+    });
+    let (mut upstream_reader, mut client_writer) = (upstream, client);
+    let _ = io::copy(&mut upstream_reader, &mut client_writer);
+    let _ = client_writer.shutdown(std::net::Shutdown::Write);
+    let _ = upload.join();
+}
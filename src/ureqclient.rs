@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read};
+use std::sync::Arc;
+use ureq::{Agent, AgentBuilder};
+use crate::compression;
+use crate::httprequest::{self, HttpRequest, HttpResponse, RequestType, StreamingResponse, DEFAULT_REDIRECT_LIMIT};
+use crate::proxy::{Proxy, ProxyKind};
+use crate::resolver::{self, ResolveOverride};
+
+/// An `HttpRequest` sent with ureq, a widely used blocking http client, over native-tls (openssl on Linux).
+/// Like `LiteHttpClient`, responses with error codes are returned rather than being errors, compressed
+/// responses are decoded and redirects are followed without sending the credentials to other hosts.
+/// ureq's own redirect handling is turned off, as it sends the `Authorization` header on to any host.
+///
+/// Like `LiteHttpClient`, cloning the client is cheap and the clones share one pool of connections.
+#[derive(Clone)]
+pub struct UreqHttpClient {
+    agent: Agent,
+    tls_connector: Option<Arc<native_tls::TlsConnector>>,
+    proxy: Option<ureq::Proxy>,
+    /// Why requests can't be sent, for a proxy that ureq can't connect through.
+    proxy_error: Option<String>,
+    resolve_overrides: Vec<ResolveOverride>,
+    redirect_limit: u32,
+    host: String,
+    port: u32,
+    method: &'static str,
+    path: String,
+    headers: HashMap<String, String>,
+}
+
+impl UreqHttpClient {
+    pub fn new(base_address: String, port: u32) -> Self {
+        // without a connector ureq fails every https request with an explanation, so there's no need
+        // to fail here if the system's TLS can't be set up
+        let tls_connector = native_tls::TlsConnector::new().ok().map(Arc::new);
+        let host = base_address.trim_start_matches('[').trim_end_matches(']').to_string();
+        UreqHttpClient {
+            agent: Self::agent(&tls_connector, &None, &[]),
+            tls_connector,
+            proxy: None,
+            proxy_error: None,
+            resolve_overrides: Vec::new(),
+            redirect_limit: DEFAULT_REDIRECT_LIMIT,
+            host,
+            port,
+            method: "GET",
+            path: "/".to_string(),
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Uses the given connector for the TLS handshake instead of the system default,
+    /// ie to trust a self-signed certificate.
+    pub fn with_tls_connector(mut self, connector: native_tls::TlsConnector) -> Self {
+        self.tls_connector = Some(Arc::new(connector));
+        self.agent = Self::agent(&self.tls_connector, &self.proxy, &self.resolve_overrides);
+        self
+    }
+
+    /// Connects through the proxy, unless it's bypassed for the host. ureq needs another dependency
+    /// for SOCKS5, so only http proxies are supported and a SOCKS5 proxy fails every request.
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        if proxy.is_bypassed_for(&self.host) {
+            return self;
+        }
+        if proxy.kind != ProxyKind::Http {
+            self.proxy_error = Some(format!("SOCKS5 proxies (like {}:{}) aren't supported by the ureq backend", proxy.host, proxy.port));
+            return self;
+        }
+        // ureq splits the credentials off at the last '@' and the user name off at the first ':', without
+        // percent-decoding either, so they go in as they are and only a user name with a ':' can't be passed on
+        let credentials = match proxy.credentials {
+            Some((user, _)) if user.contains(':') => {
+                self.proxy_error = Some(format!("The proxy user name {} can't contain a ':' with the ureq backend", user));
+                return self;
+            },
+            Some((user, password)) => format!("{}:{}@", user, password),
+            None => String::new(),
+        };
+        match ureq::Proxy::new(format!("http://{}{}:{}", credentials, proxy.host, proxy.port)) {
+            Ok(proxy) => self.proxy = Some(proxy),
+            Err(e) => self.proxy_error = Some(format!("The proxy can't be used: {}", e)),
+        }
+        self.agent = Self::agent(&self.tls_connector, &self.proxy, &self.resolve_overrides);
+        self
+    }
+
+    /// Connects to the given addresses for a host and port instead of looking them up.
+    /// ureq tries the addresses one after another rather than racing them like `LiteHttpClient`.
+    pub fn with_resolve(mut self, resolve_overrides: Vec<ResolveOverride>) -> Self {
+        self.resolve_overrides = resolve_overrides;
+        self.agent = Self::agent(&self.tls_connector, &self.proxy, &self.resolve_overrides);
+        self
+    }
+
+    /// Sets how many redirects are followed before giving up on a request, like `LiteHttpClient::with_redirect_limit`.
+    pub fn with_redirect_limit(mut self, redirect_limit: u32) -> Self {
+        self.redirect_limit = redirect_limit;
+        self
+    }
+
+    fn agent(tls_connector: &Option<Arc<native_tls::TlsConnector>>, proxy: &Option<ureq::Proxy>, resolve_overrides: &[ResolveOverride]) -> Agent {
+        let mut builder = AgentBuilder::new().redirects(0);
+        if let Some(connector) = tls_connector {
+            builder = builder.tls_connector(Arc::clone(connector));
+        }
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        if !resolve_overrides.is_empty() {
+            let resolve_overrides = resolve_overrides.to_vec();
+            // ureq asks for `host:port`, with an IPv6 host in brackets
+            builder = builder.resolver(move |netloc: &str| {
+                let (host, port) = netloc.rsplit_once(':').unwrap_or((netloc, "443"));
+                let port = port.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid port in {}", netloc)))?;
+                resolver::resolve(host, port, &resolve_overrides)
+            });
+        }
+        builder.build()
+    }
+
+    fn request(&self) -> Result<ureq::Request, Box<dyn Error>> {
+        if let Some(proxy_error) = &self.proxy_error {
+            Err(proxy_error.as_str())?
+        }
+        let url = format!("https://{}{}", resolver::format_authority(&self.host, self.port), self.path);
+        let mut request = self.agent.request(self.method, &url);
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        Ok(request)
+    }
+
+    /// Points the request at the `Location` of a redirect response, returning false if the response
+    /// isn't a redirect that should be followed. `redirects` counts the redirects followed so far.
+    fn follow_redirect(&mut self, response: &ureq::Response, redirects: &mut u32) -> Result<bool, Box<dyn Error>> {
+        let location = match response.header("location") {
+            Some(location) if httprequest::is_followed_redirect(response.status(), self.redirect_limit) => location,
+            _ => return Ok(false),
+        };
+        httprequest::count_redirect(redirects, self.redirect_limit, location)?;
+        let (host, port, path) = httprequest::redirect(&self.host, self.port, &self.path, location, &mut self.headers)?;
+        // ureq sets the Host header itself
+        self.headers.remove("Host");
+        self.host = host;
+        self.port = port;
+        self.path = path;
+        Ok(true)
+    }
+}
+
+/// Reads the rest of a redirect response, so ureq puts its connection back in the pool.
+fn discard(response: ureq::Response) -> io::Result<()> {
+    io::copy(&mut response.into_reader(), &mut io::sink())?;
+    Ok(())
+}
+
+/// Responses with error codes are returned as `ureq::Error::Status`, where the other clients return
+/// them like any other response for the caller to check.
+fn into_response(result: Result<ureq::Response, ureq::Error>) -> Result<ureq::Response, Box<dyn Error>> {
+    match result {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => Ok(response),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+fn response_headers(response: &ureq::Response) -> HashMap<String, String> {
+    response
+        .headers_names()
+        .into_iter()
+        .filter_map(|name| {
+            let value = response.header(&name)?.to_string();
+            Some((name.to_lowercase(), value))
+        })
+        .collect()
+}
+
+impl HttpRequest for UreqHttpClient {
+    fn set_request(mut self, req_type: RequestType, remote_uri: &str) -> Self {
+        self.method = req_type.method();
+        self.path = remote_uri.to_string();
+        // ureq is built without its own decoding, so responses are decoded like LiteHttpClient's
+        self.headers.insert("Accept-Encoding".to_string(), compression::ACCEPT_ENCODING.to_string());
+        self
+    }
+
+    fn set_header(mut self, header: String, value: String) -> Self {
+        self.headers.insert(header, value);
+        self
+    }
+
+    fn basic_auth(mut self, user: &str, password: &str) -> Self {
+        let user_and_password = format!("{}:{}", user, password);
+        let encoded = openssl::base64::encode_block(user_and_password.as_bytes());
+        self.headers.insert(String::from("Authorization"), format!("Basic {}", encoded));
+        self
+    }
+
+    fn send_bytes(mut self, bytes: &[u8]) -> Result<HttpResponse, Box<dyn Error>> {
+        let mut redirects = 0;
+        let response = loop {
+            let response = into_response(self.request()?.send_bytes(bytes))?;
+            if !self.follow_redirect(&response, &mut redirects)? {
+                break response;
+            }
+            discard(response)?;
+        };
+        let response_code = response.status();
+        let mut headers = response_headers(&response);
+        let mut body = Vec::new();
+        compression::decode_body(&mut headers, response.into_reader()).read_to_end(&mut body)?;
+        Ok(HttpResponse {
+            response_code,
+            response_msg: String::from_utf8_lossy(&body).to_string(),
+            headers,
+        })
+    }
+
+    fn send_stream<R: Read>(mut self, mut body: R, length: Option<u64>) -> Result<StreamingResponse, Box<dyn Error>> {
+        // a body that's read as it's sent can't be sent again after a redirect, so small ones are read in first
+        let replayable = httprequest::read_replayable_body(&mut body, length)?;
+        let mut redirects = 0;
+        loop {
+            // ureq sends a body of unknown length with chunked encoding, like LiteHttpClient
+            let request = self.request()?;
+            let request = match length {
+                Some(length) => request.set("Content-Length", &length.to_string()),
+                None => request,
+            };
+            let response = match (&replayable, length) {
+                (Some(replayable), _) => into_response(request.send(&replayable[..]))?,
+                (None, Some(length)) => into_response(request.send((&mut body).take(length)))?,
+                (None, None) => into_response(request.send(&mut body))?,
+            };
+            if replayable.is_none() && httprequest::is_followed_redirect(response.status(), self.redirect_limit) {
+                if let Some(location) = response.header("location") {
+                    Err(httprequest::unreplayable_redirect(location))?
+                }
+            }
+            if !self.follow_redirect(&response, &mut redirects)? {
+                let response_code = response.status();
+                let mut headers = response_headers(&response);
+                let body = compression::decode_body(&mut headers, response.into_reader());
+                return Ok(StreamingResponse { response_code, headers, body });
+            }
+            discard(response)?;
+        }
+    }
+}
+
+/// ureq only sends requests one at a time, so requests sent at once each run on a thread of their own.
+#[cfg(feature = "async")]
+impl crate::asynchttp::ToAsync for UreqHttpClient {
+    type Async = crate::asynchttp::SpawnBlocking<UreqHttpClient>;
+
+    fn to_async(&self) -> Self::Async {
+        crate::asynchttp::SpawnBlocking::new(self.clone())
+    }
+}
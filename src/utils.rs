@@ -1,5 +1,9 @@
 use std::error::Error;
-use std::io::{BufRead, Write};
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::terminal;
 
 /// Gets the user input from the given `reader` BufRead buffer.
 /// Which will loop if the input is empty and `is_required` is true.
@@ -26,6 +30,22 @@ where
     Ok(buf)
 }
 
+/// Gets a password from the given `reader` like `get_user_input`, except that what's typed isn't shown
+/// when it's read from a terminal.
+pub fn get_password_input<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    request_msg: &str,
+    is_required: bool,
+) -> Result<String, Box<dyn Error>>
+where
+    R: BufRead,
+    W: Write,
+{
+    let _no_echo = terminal::NoEcho::stdin();
+    get_user_input(reader, writer, request_msg, is_required)
+}
+
 /// Trims any \r and \n characters from the `s` argument.
 fn trim_newline_characters(s: &mut String) {
     if s.ends_with('\n') {
@@ -36,3 +56,272 @@ fn trim_newline_characters(s: &mut String) {
     }
 }
 
+/// Formats the time in the local timezone using a strftime format string, ie `%Y-%m-%d`.
+#[cfg(unix)]
+pub fn format_local_time(time: SystemTime, format: &str) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0) as libc::time_t;
+    let format = match std::ffi::CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+    let mut buf = vec![0u8; 256];
+    // safe as tm is plain data that localtime_r fills in, and strftime is bounded by the buffer length
+    let written = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&seconds, &mut tm).is_null() {
+            return String::new();
+        }
+        libc::strftime(buf.as_mut_ptr() as *mut libc::c_char, buf.len(), format.as_ptr(), &tm)
+    };
+    buf.truncate(written);
+    String::from_utf8_lossy(&buf).to_string()
+}
+
+/// Formats the time in the local timezone as an ISO 8601 timestamp, ie `2021-06-15T12:00:00+01:00`.
+pub fn format_iso8601_local_time(time: SystemTime) -> String {
+    let mut formatted = format_local_time(time, "%Y-%m-%dT%H:%M:%S%z");
+    // strftime's %z leaves out the colon ISO 8601 puts between the hours and minutes of the offset
+    let offset = formatted.get(formatted.len().saturating_sub(5)..).unwrap_or("");
+    if offset.starts_with(['+', '-']) && offset[1..].chars().all(|c| c.is_ascii_digit()) {
+        formatted.insert(formatted.len() - 2, ':');
+    }
+    formatted
+}
+
+/// Formats the time as seconds since the unix epoch, as there's no strftime to use.
+#[cfg(not(unix))]
+pub fn format_local_time(time: SystemTime, _format: &str) -> String {
+    time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0).to_string()
+}
+
+/// A folder in the temporary directory that only the current user can open, for files holding the
+/// content of a note, ie while it's edited. It's removed with everything in it when this is dropped,
+/// unless it's kept with `keep`.
+pub struct PrivateTempDir {
+    path: PathBuf,
+}
+
+impl PrivateTempDir {
+    pub fn new(prefix: &str) -> io::Result<Self> {
+        let mut attempt = 0;
+        loop {
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.subsec_nanos()).unwrap_or(0);
+            let path = std::env::temp_dir().join(format!("{}-{}-{}", prefix, std::process::id(), nanos));
+            // creating the folder fails if something is already there, so it can't be one someone else made
+            match private_dir_builder().create(&path) {
+                Ok(()) => return Ok(PrivateTempDir { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 10 => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Creates a file in the folder that only the current user can read and write, failing if it exists.
+    pub fn create_file(&self, name: &str) -> io::Result<(PathBuf, File)> {
+        let path = self.path.join(name);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(&path)?;
+        Ok((path, file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leaves the folder and its files in place, ie so edits that couldn't be saved aren't lost.
+    pub fn keep(mut self) -> PathBuf {
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for PrivateTempDir {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+fn private_dir_builder() -> fs::DirBuilder {
+    #[allow(unused_mut)]
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+}
+
+/// Name of the machine the program is running on, or an empty string if it can't be found.
+#[cfg(unix)]
+pub fn hostname() -> String {
+    let mut buf = vec![0u8; 256];
+    // safe as gethostname is bounded by the buffer length
+    let result = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if result != 0 {
+        return String::new();
+    }
+    let length = buf.iter().position(|byte| *byte == 0).unwrap_or(buf.len());
+    buf.truncate(length);
+    String::from_utf8_lossy(&buf).to_string()
+}
+
+/// Name of the machine the program is running on, or an empty string if it can't be found.
+#[cfg(not(unix))]
+pub fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+/// The command line of the process piping into stdin, ie `grep -r TODO src` for `grep -r TODO src | nxcloudnotes -`.
+/// Found by looking for the process whose stdout is the same pipe as our stdin, so it's only available on Linux.
+#[cfg(target_os = "linux")]
+pub fn piped_command() -> Option<String> {
+    let stdin_pipe = std::fs::read_link("/proc/self/fd/0").ok()?;
+    if !stdin_pipe.to_string_lossy().starts_with("pipe:") {
+        return None;
+    }
+    let own_pid = std::process::id().to_string();
+    for process in std::fs::read_dir("/proc").ok()?.flatten() {
+        let pid = process.file_name().to_string_lossy().to_string();
+        if pid == own_pid || !pid.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        if std::fs::read_link(process.path().join("fd/1")).ok().as_ref() != Some(&stdin_pipe) {
+            continue;
+        }
+        let cmdline = std::fs::read(process.path().join("cmdline")).ok()?;
+        let args: Vec<String> = cmdline
+            .split(|byte| *byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+        return if args.is_empty() { None } else { Some(args.join(" ")) };
+    }
+    None
+}
+
+/// The command line of the process piping into stdin. Only available on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn piped_command() -> Option<String> {
+    None
+}
+
+/// Content type to upload a file with, based on its extension.
+/// Text types are marked as utf-8, as that's what notes are written in.
+pub fn content_type_for_path(path: &str) -> &'static str {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "md" | "markdown" => "text/markdown; charset=utf-8",
+        "txt" | "text" | "log" => "text/plain; charset=utf-8",
+        "html" | "htm" => "text/html; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Formats a byte count for display, ie `1.5 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Renders a single line progress bar for a transfer, ie `[=========>          ]  50% 1.0 MB/2.0 MB`.
+/// If the `total` isn't known only the amount transferred so far is shown.
+pub fn format_progress_bar(transferred: u64, total: Option<u64>, width: usize) -> String {
+    match total {
+        Some(total) if total > 0 => {
+            let fraction = (transferred as f64 / total as f64).min(1.0);
+            let filled = (fraction * width as f64) as usize;
+            let bar = if filled >= width {
+                "=".repeat(width)
+            } else {
+                format!("{}>{}", "=".repeat(filled), " ".repeat(width - filled - 1))
+            };
+            format!("[{}] {:>3}% {}/{}", bar, (fraction * 100.0) as u32, format_bytes(transferred), format_bytes(total))
+        },
+        _ => format!("{} transferred", format_bytes(transferred)),
+    }
+}
+
+/// Redraws the progress bar in place on the `writer`, intended to be called from a
+/// `httprequest::ProgressReader` callback.
+pub fn render_progress_bar<W: Write>(writer: &mut W, transferred: u64, total: Option<u64>) -> std::io::Result<()> {
+    write!(writer, "\r{}", format_progress_bar(transferred, total, 30))?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn should_create_private_temp_files_and_remove_them() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = PrivateTempDir::new("nxcloudnotes-test").unwrap();
+        let (path, mut file) = dir.create_file("note.md").unwrap();
+        file.write_all(b"secret").unwrap();
+
+        assert_eq!(fs::metadata(dir.path()).unwrap().permissions().mode() & 0o777, 0o700);
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(dir.create_file("note.md").is_err());
+
+        let dir_path = dir.path().to_path_buf();
+        drop(dir);
+        assert!(!dir_path.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn should_format_iso8601_offset_with_a_colon() {
+        let formatted = format_iso8601_local_time(UNIX_EPOCH);
+
+        assert_eq!(formatted.len(), "1970-01-01T00:00:00+00:00".len());
+        assert!(formatted.ends_with(|c: char| c.is_ascii_digit()));
+        assert_eq!(&formatted[formatted.len() - 3..formatted.len() - 2], ":");
+    }
+
+    #[test]
+    fn should_pick_content_type_from_extension() {
+        assert_eq!(content_type_for_path("/Notes/note.md"), "text/markdown; charset=utf-8");
+        assert_eq!(content_type_for_path("/Notes/note.TXT"), "text/plain; charset=utf-8");
+        assert_eq!(content_type_for_path("/Notes/.attachments.1/shot.png"), "image/png");
+        assert_eq!(content_type_for_path("/Notes/no-extension"), "application/octet-stream");
+    }
+
+    #[test]
+    fn should_format_byte_counts() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MB");
+    }
+
+    #[test]
+    fn should_format_progress_bar() {
+        assert_eq!(format_progress_bar(0, Some(2048), 10), "[>         ]   0% 0 B/2.0 KB");
+        assert_eq!(format_progress_bar(1024, Some(2048), 10), "[=====>    ]  50% 1.0 KB/2.0 KB");
+        assert_eq!(format_progress_bar(2048, Some(2048), 10), "[==========] 100% 2.0 KB/2.0 KB");
+        assert_eq!(format_progress_bar(2048, None, 10), "2.0 KB transferred");
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+/// An element parsed from a WebDAV XML document. Namespaces are dropped, so `<d:href>` and
+/// `<D:href>` both have the name `href`, which is all the multistatus responses we read need.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub text: String,
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    /// The first child element with the given local name.
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// All child elements with the given local name.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// A single `<d:response>` of a multistatus (207) response, ie one file or folder returned by a PROPFIND.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DavResponse {
+    /// The href of the resource, as sent by the server (so still percent-encoded).
+    pub href: String,
+    /// Properties that were found for the resource (from a propstat with a 200 status), keyed by local name.
+    pub properties: HashMap<String, XmlElement>,
+}
+
+impl DavResponse {
+    /// The text value of the given property, ie `getcontentlength`.
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(|element| element.text.as_str())
+    }
+
+    /// Whether the resource is a folder, according to its `resourcetype` property.
+    pub fn is_collection(&self) -> bool {
+        self.properties
+            .get("resourcetype")
+            .is_some_and(|resource_type| resource_type.child("collection").is_some())
+    }
+
+    /// The last segment of the href, percent-decoded, ie the file or folder name.
+    pub fn name(&self) -> String {
+        let segment = self.href.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+        percent_decode(segment)
+    }
+}
+
+/// Parses the body of a multistatus response into its individual responses.
+pub fn parse_multistatus(xml: &str) -> Result<Vec<DavResponse>, Box<dyn Error>> {
+    let root = parse_xml(xml)?;
+    if root.name != "multistatus" {
+        Err(format!("Expected a multistatus response but found <{}>", root.name))?
+    }
+
+    let responses = root
+        .children_named("response")
+        .map(|response| {
+            let href = response.child("href").map(|href| href.text.clone()).unwrap_or_default();
+            let mut properties = HashMap::new();
+            for propstat in response.children_named("propstat") {
+                let is_ok = propstat
+                    .child("status")
+                    .is_some_and(|status| status.text.split_whitespace().nth(1) == Some("200"));
+                if !is_ok {
+                    continue;
+                }
+                if let Some(prop) = propstat.child("prop") {
+                    for property in &prop.children {
+                        properties.insert(property.name.clone(), property.clone());
+                    }
+                }
+            }
+            DavResponse { href, properties }
+        })
+        .collect();
+    Ok(responses)
+}
+
+/// Builds the body of a PROPFIND request for the given properties. Names are prefixed with the
+/// namespace they belong to: `d:` for DAV, `oc:` for ownCloud and `nc:` for Nextcloud, ie `oc:fileid`.
+pub fn propfind_body(properties: &[&str]) -> String {
+    let mut body = String::from(
+        "<?xml version=\"1.0\"?>\n<d:propfind xmlns:d=\"DAV:\" xmlns:oc=\"http://owncloud.org/ns\" xmlns:nc=\"http://nextcloud.org/ns\">\n  <d:prop>\n",
+    );
+    for property in properties {
+        body.push_str(&format!("    <{}/>\n", property));
+    }
+    body.push_str("  </d:prop>\n</d:propfind>\n");
+    body
+}
+
+/// Percent-encodes everything in `value` apart from unreserved characters and `/`, so it can be used as
+/// (part of) a url path.
+pub fn percent_encode_path(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decodes %XX escapes in a url path segment. Invalid escapes are left as they are.
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Minimal XML parser, handling just enough of the format for the responses WebDAV servers send:
+/// elements, attributes (which are skipped), text, entities, comments, CDATA and processing instructions.
+fn parse_xml(xml: &str) -> Result<XmlElement, Box<dyn Error>> {
+    let mut stack: Vec<XmlElement> = vec![XmlElement::default()];
+    let mut rest = xml;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<?") {
+            rest = skip_past(after, "?>")?;
+        } else if let Some(after) = rest.strip_prefix("<!--") {
+            rest = skip_past(after, "-->")?;
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").ok_or("Unterminated CDATA section")?;
+            if let Some(current) = stack.last_mut() {
+                current.text.push_str(&after[..end]);
+            }
+            rest = &after[end + 3..];
+        } else if let Some(after) = rest.strip_prefix("<!") {
+            rest = skip_past(after, ">")?;
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').ok_or("Unterminated closing tag")?;
+            let name = local_name(after[..end].trim());
+            let element = stack.pop().ok_or("Unexpected closing tag")?;
+            if element.name != name || stack.is_empty() {
+                Err(format!("Mismatched closing tag </{}>", name))?
+            }
+            push_child(&mut stack, element);
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = after.find('>').ok_or("Unterminated tag")?;
+            let tag = &after[..end];
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let name = tag.split_whitespace().next().ok_or("Empty tag")?;
+            let element = XmlElement {
+                name: local_name(name).to_string(),
+                ..Default::default()
+            };
+            if self_closing {
+                push_child(&mut stack, element);
+            } else {
+                stack.push(element);
+            }
+            rest = &after[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = decode_entities(&rest[..end]);
+            if let Some(current) = stack.last_mut() {
+                // whitespace between elements is formatting rather than content
+                if !text.trim().is_empty() || !current.text.is_empty() {
+                    current.text.push_str(&text);
+                }
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    if stack.len() != 1 {
+        Err("Unexpected end of document, not all elements were closed")?
+    }
+    let mut document = stack.pop().unwrap_or_default();
+    if document.children.is_empty() {
+        Err("Document has no root element")?
+    }
+    Ok(document.children.remove(0))
+}
+
+fn skip_past<'a>(value: &'a str, terminator: &str) -> Result<&'a str, Box<dyn Error>> {
+    match value.find(terminator) {
+        Some(index) => Ok(&value[index + terminator.len()..]),
+        None => Err(format!("Expected '{}' before the end of the document", terminator))?,
+    }
+}
+
+fn push_child(stack: &mut [XmlElement], mut element: XmlElement) {
+    element.text = element.text.trim().to_string();
+    if let Some(parent) = stack.last_mut() {
+        parent.children.push(element);
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let entity_end = after.find(';');
+        let replacement = entity_end.and_then(|end| match &after[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            numeric if numeric.starts_with("#x") => u32::from_str_radix(&numeric[2..], 16).ok().and_then(char::from_u32),
+            numeric if numeric.starts_with('#') => numeric[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        });
+        match (replacement, entity_end) {
+            (Some(character), Some(end)) => {
+                decoded.push(character);
+                rest = &after[end + 1..];
+            },
+            _ => {
+                decoded.push('&');
+                rest = &after[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROPFIND_RESPONSE: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:s="http://sabredav.org/ns" xmlns:oc="http://owncloud.org/ns">
+  <d:response>
+    <d:href>/remote.php/dav/files/user/Notes/</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype><d:collection/></d:resourcetype>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+    <d:propstat>
+      <d:prop>
+        <d:getcontentlength/>
+      </d:prop>
+      <d:status>HTTP/1.1 404 Not Found</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/user/Notes/Tom%20%26%20Jerry.md</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype/>
+        <d:getcontentlength>42</d:getcontentlength>
+        <oc:fileid>1234</oc:fileid>
+        <d:displayname>Tom &amp; Jerry &#x263A;</d:displayname>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    #[test]
+    fn should_parse_multistatus_responses() {
+        let responses = parse_multistatus(PROPFIND_RESPONSE).unwrap();
+
+        assert_eq!(responses.len(), 2);
+        assert!(responses[0].is_collection());
+        assert_eq!(responses[0].name(), "Notes");
+        // properties that weren't found shouldn't be reported
+        assert_eq!(responses[0].property("getcontentlength"), None);
+
+        assert!(!responses[1].is_collection());
+        assert_eq!(responses[1].name(), "Tom & Jerry.md");
+        assert_eq!(responses[1].property("getcontentlength"), Some("42"));
+        assert_eq!(responses[1].property("fileid"), Some("1234"));
+        assert_eq!(responses[1].property("displayname"), Some("Tom & Jerry \u{263A}"));
+    }
+
+    #[test]
+    fn should_reject_malformed_documents() {
+        assert!(parse_multistatus("<d:multistatus><d:response></d:multistatus>").is_err());
+        assert!(parse_multistatus("<d:error>oops</d:error>").is_err());
+        assert!(parse_multistatus("").is_err());
+    }
+
+    #[test]
+    fn should_percent_decode() {
+        assert_eq!(percent_decode("a%20b%2Fc"), "a b/c");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+        assert_eq!(percent_decode("%C3%A9"), "é");
+    }
+
+    #[test]
+    fn should_percent_encode_paths() {
+        assert_eq!(percent_encode_path("/Notes/My note #1?.md"), "/Notes/My%20note%20%231%3F.md");
+        assert_eq!(percent_encode_path("café"), "caf%C3%A9");
+        assert_eq!(percent_decode(&percent_encode_path("a b&c/d")), "a b&c/d");
+    }
+}